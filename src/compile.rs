@@ -0,0 +1,617 @@
+use types::{Object, Type, HeapObject, ListIter};
+use error::{Err, Location, Sources};
+use interpreter::Interpreter;
+use environment::Global;
+use std::option::Option;
+use std::result::Result;
+use std::rc::Rc;
+use symbol::Symbol;
+
+pub type Code = Rc<Node>;
+
+// An expression with its special forms parsed and its variable references
+// resolved, ready to be evaluated.
+pub enum Node {
+    Const(HeapObject),
+    // the variable at index in the frame depth parents up from the
+    // current one
+    Local(usize, usize),
+    // the location of the reference is kept for unbound variable errors
+    Global(Rc<Global>, Option<Location>),
+    SetLocal(usize, usize, Code),
+    SetGlobal(Rc<Global>, Code, Option<Location>),
+    DefineGlobal(Rc<Global>, Code),
+    If(Code, Code, Option<Code>),
+    Lambda(Rc<LambdaCode>),
+    Seq(Rc<[Code]>),
+    // the symbol naming the procedure, if any, is kept for traces, and the
+    // location of the call for errors
+    Call(Option<Symbol>, Code, Rc<[Code]>, Option<Location>),
+    Let(Rc<LetCode>),
+    Letrec(Rc<LetCode>),
+    Cond(Rc<[Clause]>),
+    Case(Code, Rc<[CaseClause]>),
+    And(Rc<[Code]>),
+    Or(Rc<[Code]>),
+    While(Code, Rc<[Code]>),
+}
+
+// The frame of a call holds the required parameters, the list of the
+// remaining arguments if there is a rest parameter, and then the variables
+// defined in the body.
+pub struct LambdaCode {
+    pub required: usize,
+    pub rest: bool,
+    pub frame_size: usize,
+    pub body: Rc<[Code]>,
+    // a string the body starts with, when there is more after it
+    pub doc: Option<String>,
+}
+
+// let evaluates inits before entering the frame, letrec inside it. Either
+// way the value of the nth init goes in the nth slot.
+pub struct LetCode {
+    pub inits: Rc<[Code]>,
+    pub frame_size: usize,
+    pub body: Rc<[Code]>,
+}
+
+pub enum ClauseBody {
+    // (test), the value of the test is the value of the clause
+    Test,
+    Seq(Rc<[Code]>),
+    // (test => receiver)
+    Receiver(Code),
+}
+
+pub struct Clause {
+    pub test: Code,
+    pub body: ClauseBody,
+}
+
+pub struct CaseClause {
+    // None for the else clause
+    pub data: Option<Vec<HeapObject>>,
+    pub body: ClauseBody,
+}
+
+pub struct Compiler<'a> {
+    interpreter: &'a mut Interpreter,
+    // the names bound in each frame enclosing the expression, innermost
+    // last
+    scopes: Vec<Vec<Symbol>>,
+    // where the expression being compiled and what it includes were read
+    // from, if known
+    sources: Sources,
+}
+
+impl<'a> Compiler<'a> {
+    pub fn new(interpreter: &'a mut Interpreter, sources: Sources) -> Compiler<'a> {
+        Compiler{interpreter, scopes: Vec::new(), sources}
+    }
+
+    pub fn compile(&mut self, expr: &HeapObject) -> Result<Code, Err> {
+        match expr.object_type {
+            Type::Symbol(ref sym) => Result::Ok(Rc::new(self.lookup(sym, expr))),
+            Type::Pair(ref p) => {
+                let head = p.car.borrow().clone();
+                let args = p.cdr.borrow().clone();
+                let location = self.location(expr);
+                // keywords are interned, a local variable of the same name
+                // renamed by the expander is not
+                let name = if let Type::Symbol(ref s) = head.object_type {
+                    let form = if self.interpreter.is_interned(s) {
+                        self.special_form(s.name(), &args)
+                    } else {
+                        Option::None
+                    };
+                    if let Option::Some(res) = form {
+                        return res.map_err(|e| e.at(location));
+                    }
+                    Option::Some(s.clone())
+                } else {
+                    Option::None
+                };
+
+                let args = match Object::list_to_vec(&args) {
+                    Option::Some(args) => self.compile_all(&args)?,
+                    Option::None => return Result::Err(self.bad_syntax("procedure call").at(location)),
+                };
+                Result::Ok(Rc::new(Node::Call(name, self.compile(&head)?, args, location)))
+            },
+            _ => Result::Ok(Rc::new(Node::Const(expr.clone()))),
+        }
+    }
+
+    #[inline]
+    fn location(&self, expr: &HeapObject) -> Option<Location> {
+        self.sources.location(expr)
+    }
+
+    // Forms whose operands are not evaluated before the call. They are
+    // recognized by name before the head of a list is compiled.
+    fn special_form(&mut self, name: &str, args: &HeapObject) -> Option<Result<Code, Err>> {
+        Option::Some(match name {
+            "define" => self.define(args),
+            "while" => self.while_loop(args),
+            "quote" => self.quote(args),
+            "if" => self.if_form(args),
+            "lambda" => self.lambda(args),
+            "set!" => self.set(args),
+            "begin" => self.begin(args),
+            "let" => self.let_form(args),
+            "let*" => self.let_star(args),
+            "letrec" => self.letrec(args),
+            "cond" => self.cond(args),
+            "case" => self.case(args),
+            "and" => self.and(args),
+            "or" => self.or(args),
+            _ => return Option::None,
+        })
+    }
+
+    fn compile_all(&mut self, exprs: &[HeapObject]) -> Result<Rc<[Code]>, Err> {
+        let mut codes = Vec::with_capacity(exprs.len());
+        for expr in exprs {
+            codes.push(self.compile(expr)?);
+        }
+        Result::Ok(codes.into())
+    }
+
+    // Resolves the reference to name at expr.
+    fn lookup(&mut self, name: &Symbol, expr: &HeapObject) -> Node {
+        for (depth, scope) in self.scopes.iter().rev().enumerate() {
+            if let Option::Some(index) = scope.iter().rposition(|n| n == name) {
+                return Node::Local(depth, index)
+            }
+        }
+        Node::Global(self.interpreter.global(name.clone()), self.location(expr))
+    }
+
+    // Compiles body in a new frame binding names. Variables defined in the
+    // body get the slots after them. Returns the size of the frame.
+    fn compile_scope(&mut self, names: Vec<Symbol>, body: &HeapObject)
+                     -> Result<(usize, Rc<[Code]>), Err> {
+        self.scopes.push(names);
+        let res = self.scan_defines(body).and_then(|_| self.compile_body(body));
+        let scope = self.scopes.pop().unwrap();
+        Result::Ok((scope.len(), res?))
+    }
+
+    fn compile_body(&mut self, body: &HeapObject) -> Result<Rc<[Code]>, Err> {
+        match Object::list_to_vec(body) {
+            Option::Some(ref forms) if !forms.is_empty() => self.compile_all(forms),
+            _ => Result::Err(self.bad_syntax("body")),
+        }
+    }
+
+    // Adds the names defined at the top of body to the innermost scope, so
+    // the procedures in a body can refer to each other.
+    fn scan_defines(&mut self, body: &HeapObject) -> Result<(), Err> {
+        for form in ListIter::new(body) {
+            let (head, args) = match (form.car(), form.cdr()) {
+                (Option::Some(head), Option::Some(args)) => (head, args),
+                _ => continue,
+            };
+            if self.is_sym(&head, "begin") {
+                self.scan_defines(&args)?;
+            } else if self.is_sym(&head, "define") {
+                let (target, _) = self.split_args("define", &args, 1)?;
+                let name = match target[0].object_type {
+                    Type::Pair(ref p) => p.car.borrow().clone(),
+                    _ => target[0].clone(),
+                };
+                if let Type::Symbol(ref name) = name.object_type {
+                    self.declare(name.clone());
+                }
+            }
+        }
+        Result::Ok(())
+    }
+
+    // The slot of name in the innermost scope, added if it isn't there yet.
+    fn declare(&mut self, name: Symbol) -> usize {
+        let scope = self.scopes.last_mut().unwrap();
+        match scope.iter().rposition(|n| *n == name) {
+            Option::Some(index) => index,
+            Option::None => {
+                scope.push(name);
+                scope.len() - 1
+            },
+        }
+    }
+
+    #[inline]
+    fn bad_syntax(&self, form: &'static str) -> Err {
+        self.interpreter.bad_syntax(form)
+    }
+
+    // Whether obj is the keyword name.
+    #[inline]
+    fn is_sym(&self, obj: &HeapObject, name: &str) -> bool {
+        if let Type::Symbol(ref s) = obj.object_type {
+            s.name() == name && self.interpreter.is_interned(s)
+        } else {
+            false
+        }
+    }
+
+    // Splits the operands of a special form into its first n elements and
+    // the rest, which has to be a proper list.
+    fn split_args(&self, form: &'static str, args: &HeapObject, n: usize)
+                  -> Result<(Vec<HeapObject>, HeapObject), Err> {
+        let mut iter = ListIter::new(args);
+        let first: Vec<HeapObject> = iter.by_ref().take(n).collect();
+        let rest = iter.tail().clone();
+        if first.len() < n || !Object::is_list(&rest) {
+            return Result::Err(self.bad_syntax(form));
+        }
+        Result::Ok((first, rest))
+    }
+
+    // (a r g s), (a r . gs) or args
+    fn compile_lambda(&mut self, params: &HeapObject, body: &HeapObject) -> Result<Code, Err> {
+        let mut names = Vec::new();
+        let mut iter = ListIter::new(params);
+        for param in iter.by_ref() {
+            match param.object_type {
+                Type::Symbol(ref s) => names.push(s.clone()),
+                _ => return Result::Err(self.bad_syntax("lambda")),
+            }
+        }
+        let required = names.len();
+        let rest = match iter.tail().object_type {
+            Type::Symbol(ref s) => {
+                names.push(s.clone());
+                true
+            },
+            Type::Nil => false,
+            _ => return Result::Err(self.bad_syntax("lambda")),
+        };
+        self.compile_procedure(names, required, rest, body)
+    }
+
+    fn compile_procedure(&mut self, names: Vec<Symbol>, required: usize, rest: bool,
+                         body: &HeapObject) -> Result<Code, Err> {
+        let doc = match (body.car(), body.cdr().and_then(|rest| rest.car())) {
+            (Option::Some(first), Option::Some(_)) => match first.object_type {
+                Type::String(ref s) => Option::Some(s.borrow().clone()),
+                _ => Option::None,
+            },
+            _ => Option::None,
+        };
+        let (frame_size, body) = self.compile_scope(names, body)?;
+        Result::Ok(Rc::new(Node::Lambda(Rc::new(LambdaCode{required, rest, frame_size, body, doc}))))
+    }
+
+    // ((name init) ...), the names are returned as symbols
+    fn parse_bindings(&mut self, form: &'static str, bindings: &HeapObject)
+                      -> Result<Vec<(Symbol, HeapObject)>, Err> {
+        let list = match Object::list_to_vec(bindings) {
+            Option::Some(list) => list,
+            Option::None => return Result::Err(self.bad_syntax(form)),
+        };
+
+        let mut parsed = Vec::with_capacity(list.len());
+        for binding in list {
+            if let Option::Some(b) = Object::list_to_vec(&binding) {
+                if let (2, Type::Symbol(s)) = (b.len(), &b[0].object_type) {
+                    parsed.push((s.clone(), b[1].clone()));
+                    continue;
+                }
+            }
+            return Result::Err(self.bad_syntax(form));
+        }
+
+        Result::Ok(parsed)
+    }
+
+    // The body of a cond or case clause.
+    fn clause_body(&mut self, form: &'static str, body: &HeapObject) -> Result<ClauseBody, Err> {
+        if body.is_nil() {
+            return Result::Ok(ClauseBody::Test);
+        }
+
+        if self.is_sym(&body.car().unwrap(), "=>") {
+            let (receiver, rest) = self.split_args(form, &body.cdr().unwrap(), 1)?;
+            if !rest.is_nil() {
+                return Result::Err(self.bad_syntax(form));
+            }
+            return Result::Ok(ClauseBody::Receiver(self.compile(&receiver[0])?));
+        }
+
+        Result::Ok(ClauseBody::Seq(self.compile_body(body)?))
+    }
+
+    //special forms, they receive the list of operands
+    // (define name value) or (define (name a r g s) body...)
+    pub fn define(&mut self, args: &HeapObject) -> Result<Code, Err> {
+        let (target, body) = self.split_args("define", args, 1)?;
+        let (sym, value) = match target[0].object_type {
+            Type::Pair(ref p) => {
+                let sym = p.car.borrow().clone();
+                (sym, self.compile_lambda(&p.cdr.borrow(), &body)?)
+            },
+            _ => {
+                let (value, rest) = self.split_args("define", &body, 1)?;
+                if !rest.is_nil() {
+                    return Result::Err(self.bad_syntax("define"));
+                }
+                (target[0].clone(), self.compile(&value[0])?)
+            },
+        };
+        let sym = match sym.object_type {
+            Type::Symbol(ref s) => s.clone(),
+            _ => return Result::Err(self.bad_syntax("define")),
+        };
+
+        Result::Ok(Rc::new(if self.scopes.is_empty() {
+            Node::DefineGlobal(self.interpreter.global(sym), value)
+        } else {
+            Node::SetLocal(0, self.declare(sym), value)
+        }))
+    }
+
+    // (while test body...)
+    pub fn while_loop(&mut self, args: &HeapObject) -> Result<Code, Err> {
+        let (test, body) = self.split_args("while", args, 1)?;
+        let test = self.compile(&test[0])?;
+        let body = match Object::list_to_vec(&body) {
+            Option::Some(body) => self.compile_all(&body)?,
+            Option::None => return Result::Err(self.bad_syntax("while")),
+        };
+        Result::Ok(Rc::new(Node::While(test, body)))
+    }
+
+    pub fn quote(&mut self, args: &HeapObject) -> Result<Code, Err> {
+        let (datum, rest) = self.split_args("quote", args, 1)?;
+        if !rest.is_nil() {
+            return Result::Err(self.bad_syntax("quote"));
+        }
+        Result::Ok(Rc::new(Node::Const(datum[0].clone())))
+    }
+
+    // (if test consequent [alternate])
+    pub fn if_form(&mut self, args: &HeapObject) -> Result<Code, Err> {
+        let (args, rest) = self.split_args("if", args, 2)?;
+        let alternate = match Object::list_to_vec(&rest) {
+            Option::Some(ref alt) if alt.len() <= 1 => alt.first().cloned(),
+            _ => return Result::Err(self.bad_syntax("if")),
+        };
+        let alternate = match alternate {
+            Option::Some(alternate) => Option::Some(self.compile(&alternate)?),
+            Option::None => Option::None,
+        };
+        Result::Ok(Rc::new(Node::If(self.compile(&args[0])?, self.compile(&args[1])?, alternate)))
+    }
+
+    // (lambda (a r g s) body...), (lambda (a r . gs) body...) or
+    // (lambda args body...)
+    pub fn lambda(&mut self, args: &HeapObject) -> Result<Code, Err> {
+        let (params, body) = self.split_args("lambda", args, 1)?;
+        self.compile_lambda(&params[0], &body)
+    }
+
+    pub fn set(&mut self, args: &HeapObject) -> Result<Code, Err> {
+        let (args, rest) = self.split_args("set!", args, 2)?;
+        let sym = match args[0].object_type {
+            Type::Symbol(ref s) if rest.is_nil() => s.clone(),
+            _ => return Result::Err(self.bad_syntax("set!")),
+        };
+        let value = self.compile(&args[1])?;
+        Result::Ok(Rc::new(match self.lookup(&sym, &args[0]) {
+            Node::Local(depth, index) => Node::SetLocal(depth, index, value),
+            Node::Global(global, location) => Node::SetGlobal(global, value, location),
+            _ => unreachable!(),
+        }))
+    }
+
+    pub fn begin(&mut self, args: &HeapObject) -> Result<Code, Err> {
+        match Object::list_to_vec(args) {
+            Option::Some(forms) => Result::Ok(Rc::new(Node::Seq(self.compile_all(&forms)?))),
+            Option::None => Result::Err(self.bad_syntax("begin")),
+        }
+    }
+
+    // (let ((name init) ...) body...) or (let loop ((name init) ...) body...)
+    pub fn let_form(&mut self, args: &HeapObject) -> Result<Code, Err> {
+        let (first, mut rest) = self.split_args("let", args, 1)?;
+        let name = if let Type::Symbol(ref s) = first[0].object_type {
+            Option::Some(s.clone())
+        } else {
+            rest = args.clone();
+            Option::None
+        };
+
+        let (bindings, body) = self.split_args("let", &rest, 1)?;
+        if body.is_nil() {
+            return Result::Err(self.bad_syntax("let"));
+        }
+        let bindings = self.parse_bindings("let", &bindings[0])?;
+        let (names, inits): (Vec<_>, Vec<_>) = bindings.into_iter().unzip();
+        let inits = self.compile_all(&inits)?;
+
+        match name {
+            Option::None => {
+                let (frame_size, body) = self.compile_scope(names, &body)?;
+                Result::Ok(Rc::new(Node::Let(Rc::new(LetCode{inits, frame_size, body}))))
+            },
+            // the loop procedure is bound in a frame of its own, which it
+            // closes over
+            Option::Some(name) => {
+                let required = names.len();
+                self.scopes.push(vec![name.clone()]);
+                let lambda = self.compile_procedure(names, required, false, &body);
+                self.scopes.pop();
+
+                let proc = Rc::new(Node::Letrec(Rc::new(LetCode{
+                    inits: vec![lambda?].into(),
+                    frame_size: 1,
+                    body: vec![Rc::new(Node::Local(0, 0))].into(),
+                })));
+                Result::Ok(Rc::new(Node::Call(Option::Some(name), proc, inits, Option::None)))
+            },
+        }
+    }
+
+    // (let* ((name init) ...) body...), each init sees the bindings before it
+    pub fn let_star(&mut self, args: &HeapObject) -> Result<Code, Err> {
+        let (bindings, body) = self.split_args("let*", args, 1)?;
+        if body.is_nil() {
+            return Result::Err(self.bad_syntax("let*"));
+        }
+        let bindings = self.parse_bindings("let*", &bindings[0])?;
+        self.nest_lets(&bindings, &body)
+    }
+
+    // Binds each of bindings in a frame of its own.
+    fn nest_lets(&mut self, bindings: &[(Symbol, HeapObject)], body: &HeapObject)
+                 -> Result<Code, Err> {
+        let (names, inits) = match bindings.split_first() {
+            Option::Some(((name, init), _)) => (vec![name.clone()], vec![self.compile(init)?]),
+            Option::None => (Vec::new(), Vec::new()),
+        };
+
+        let (frame_size, body) = if bindings.len() > 1 {
+            self.scopes.push(names);
+            let inner = self.nest_lets(&bindings[1..], body);
+            let scope = self.scopes.pop().unwrap();
+            (scope.len(), vec![inner?].into())
+        } else {
+            self.compile_scope(names, body)?
+        };
+        Result::Ok(Rc::new(Node::Let(Rc::new(LetCode{inits: inits.into(), frame_size, body}))))
+    }
+
+    // (letrec ((name init) ...) body...), inits are evaluated with every
+    // name already in scope
+    pub fn letrec(&mut self, args: &HeapObject) -> Result<Code, Err> {
+        let (bindings, body) = self.split_args("letrec", args, 1)?;
+        if body.is_nil() {
+            return Result::Err(self.bad_syntax("letrec"));
+        }
+        let bindings = self.parse_bindings("letrec", &bindings[0])?;
+        let (names, inits): (Vec<_>, Vec<_>) = bindings.into_iter().unzip();
+
+        self.scopes.push(names);
+        let res = self.scan_defines(&body)
+            .and_then(|_| self.compile_all(&inits))
+            .and_then(|inits| Result::Ok((inits, self.compile_body(&body)?)));
+        let scope = self.scopes.pop().unwrap();
+        let (inits, body) = res?;
+        Result::Ok(Rc::new(Node::Letrec(Rc::new(LetCode{inits, frame_size: scope.len(), body}))))
+    }
+
+    // (cond (test expr...) (test => receiver) (else expr...))
+    pub fn cond(&mut self, args: &HeapObject) -> Result<Code, Err> {
+        let clauses = match Object::list_to_vec(args) {
+            Option::Some(clauses) => clauses,
+            Option::None => return Result::Err(self.bad_syntax("cond")),
+        };
+
+        let mut compiled = Vec::with_capacity(clauses.len());
+        for clause in clauses {
+            let (test, body) = self.split_args("cond", &clause, 1)?;
+            let (test, body) = if self.is_sym(&test[0], "else") {
+                if body.is_nil() {
+                    return Result::Err(self.bad_syntax("cond"));
+                }
+                (Rc::new(Node::Const(self.interpreter.new_true())), ClauseBody::Seq(self.compile_body(&body)?))
+            } else {
+                (self.compile(&test[0])?, self.clause_body("cond", &body)?)
+            };
+            compiled.push(Clause{test, body});
+        }
+        Result::Ok(Rc::new(Node::Cond(compiled.into())))
+    }
+
+    // (case key ((datum...) expr...) (else expr...))
+    pub fn case(&mut self, args: &HeapObject) -> Result<Code, Err> {
+        let (key, clauses) = self.split_args("case", args, 1)?;
+        let key = self.compile(&key[0])?;
+
+        let mut compiled = Vec::new();
+        for clause in ListIter::new(&clauses) {
+            let (data, body) = self.split_args("case", &clause, 1)?;
+            let data = if self.is_sym(&data[0], "else") {
+                Option::None
+            } else {
+                match Object::list_to_vec(&data[0]) {
+                    Option::Some(data) => Option::Some(data),
+                    Option::None => return Result::Err(self.bad_syntax("case")),
+                }
+            };
+            let body = self.clause_body("case", &body)?;
+            compiled.push(CaseClause{data, body});
+        }
+        Result::Ok(Rc::new(Node::Case(key, compiled.into())))
+    }
+
+    pub fn and(&mut self, args: &HeapObject) -> Result<Code, Err> {
+        match Object::list_to_vec(args) {
+            Option::Some(ref exprs) if exprs.is_empty() => Result::Ok(Rc::new(Node::Const(self.interpreter.new_true()))),
+            Option::Some(exprs) => Result::Ok(Rc::new(Node::And(self.compile_all(&exprs)?))),
+            Option::None => Result::Err(self.bad_syntax("and")),
+        }
+    }
+
+    pub fn or(&mut self, args: &HeapObject) -> Result<Code, Err> {
+        match Object::list_to_vec(args) {
+            Option::Some(ref exprs) if exprs.is_empty() => Result::Ok(Rc::new(Node::Const(self.interpreter.new_false()))),
+            Option::Some(exprs) => Result::Ok(Rc::new(Node::Or(self.compile_all(&exprs)?))),
+            Option::None => Result::Err(self.bad_syntax("or")),
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use parse::parse_sexp;
+
+    fn compile_str(interpreter: &mut Interpreter, s: &str) -> Code {
+        let obj = parse_sexp(s, interpreter).unwrap();
+        Compiler::new(interpreter, Sources::new(Option::None)).compile(&obj).unwrap()
+    }
+
+    fn lambda_body(code: &Code) -> Rc<[Code]> {
+        match **code {
+            Node::Lambda(ref l) => l.body.clone(),
+            _ => panic!("not a lambda"),
+        }
+    }
+
+    #[test]
+    fn test_lexical_addresses() {
+        let mut i = Interpreter::new();
+        let code = compile_str(&mut i, "(lambda (a b) (lambda (c) (define d c) (list a b c d)))");
+        let inner = lambda_body(&code)[0].clone();
+        match *inner {
+            Node::Lambda(ref l) => assert_eq!((l.required, l.rest, l.frame_size), (1, false, 2)),
+            _ => panic!("not a lambda"),
+        }
+        let call = lambda_body(&inner)[1].clone();
+        let addrs: Vec<(usize, usize)> = match *call {
+            Node::Call(_, ref proc, ref args, _) => {
+                assert!(matches!(**proc, Node::Global(ref g, _) if g.name.name() == "list"));
+                args.iter().map(|a| match **a {
+                    Node::Local(depth, index) => (depth, index),
+                    _ => panic!("not a local"),
+                }).collect()
+            },
+            _ => panic!("not a call"),
+        };
+        assert_eq!(addrs, vec![(1, 0), (1, 1), (0, 0), (0, 1)]);
+    }
+
+    #[test]
+    fn test_global_slots() {
+        let mut i = Interpreter::new();
+        let a = compile_str(&mut i, "undefined-yet");
+        let b = compile_str(&mut i, "(set! undefined-yet 1)");
+        match (&*a, &*b) {
+            (Node::Global(a, _), Node::SetGlobal(b, _, _)) => assert!(Rc::ptr_eq(a, b)),
+            _ => panic!("not globals"),
+        }
+    }
+}
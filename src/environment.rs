@@ -1,67 +1,104 @@
 use std::collections::HashMap;
 use std::result::Result;
 use std::option::Option;
+use std::cell::RefCell;
 use std::rc::Rc;
-use types::HeapObject;
+use symbol::Symbol;
+use types::{Object, Type, Frame, HeapObject};
 use error::ErrType;
 
-pub struct Environment(Vec<HashMap<Rc<String>, HeapObject>>);
+// A global variable. Compiled code holds on to the slot itself, so global
+// references are resolved once, including those to variables that aren't
+// defined yet.
+pub struct Global {
+    pub name: Symbol,
+    pub value: RefCell<Option<HeapObject>>,
+}
+
+// The global variables, and the frame evaluation is currently happening
+// in. Frames are heap objects linked to the frame they were created in, so
+// a lambda keeps the frame it was created in alive and sees later changes
+// to it. Their variables are addressed by position, as resolved by the
+// compiler.
+pub struct Environment {
+    globals: HashMap<Symbol, Rc<Global>>,
+    current: HeapObject,
+}
+
+#[inline(always)]
+fn as_frame(obj: &HeapObject) -> &Frame {
+    match obj.object_type {
+        Type::Frame(ref f) => f,
+        _ => panic!("environment is not a frame"),
+    }
+}
 
 impl Environment {
+    // The root frame lives as long as the interpreter, so it is not
+    // tracked by the GC.
     pub fn new() -> Environment {
-        let mut e = Environment(Vec::with_capacity(1));
-        e.push();
-        e
+        Environment{
+            globals: HashMap::new(),
+            current: Rc::new(Box::new(Object::new(Type::Frame(Frame::new(Vec::new(), Option::None))))),
+        }
     }
 
     #[inline(always)]
-    pub fn push_env(&mut self, e: HashMap<Rc<String>, HeapObject>) {
-        self.0.push(e)
+    pub fn current(&self) -> &HeapObject {
+        &self.current
     }
 
     #[inline(always)]
-    pub fn push(&mut self) {
-        self.0.push(HashMap::new());
+    pub fn set_current(&mut self, frame: HeapObject) {
+        debug_assert!(matches!(frame.object_type, Type::Frame(_)));
+        self.current = frame;
     }
 
-    #[inline(always)]
-    pub fn pop(&mut self) {
-        self.0.pop().expect("popping the root environment");
+    #[cfg(test)]
+    pub fn is_global(&self) -> bool {
+        as_frame(&self.current).parent.is_none()
+    }
+
+    // The slot of the global variable name, created unbound if it doesn't
+    // exist yet.
+    pub fn global(&mut self, name: Symbol) -> Rc<Global> {
+        self.globals.entry(name.clone())
+            .or_insert_with(|| Rc::new(Global{name, value: RefCell::new(Option::None)}))
+            .clone()
     }
 
     #[inline(always)]
-    pub fn insert_sym(&mut self, name: Rc<String>, value: HeapObject) {
-        self.0.last_mut().unwrap().insert(name, value);
+    pub fn insert_sym(&mut self, name: Symbol, value: HeapObject) {
+        *self.global(name).value.borrow_mut() = Option::Some(value);
     }
 
-    pub fn find_sym(&self, name: Rc<String>) -> Result<&HeapObject, ErrType> {
-        if self.0.len() == 1 {
-            let val = self.0[0].get(&name);
-            return match val {
-                Option::Some(val) => Result::Ok(val),
-                Option::None => Result::Err(ErrType::SymbolNotFound(name))
-            }
-        }
+    // The global variables that have values, in no particular order.
+    pub fn globals(&self) -> impl Iterator<Item = (&Symbol, HeapObject)> {
+        self.globals.iter().filter_map(|(name, global)| global.value.borrow().clone().map(|value| (name, value)))
+    }
 
-        for i in self.0.len()-1..0 {
-            if let Option::Some(val) = self.0[i].get(&name) {
-                return Result::Ok(val)
-            }
+    pub fn find_sym(&self, name: Symbol) -> Result<HeapObject, ErrType> {
+        match self.globals.get(&name).and_then(|g| g.value.borrow().clone()) {
+            Option::Some(val) => Result::Ok(val),
+            Option::None => Result::Err(ErrType::SymbolNotFound(name)),
         }
-
-        Result::Err(ErrType::SymbolNotFound(name))
     }
 
-    pub fn mark_all(&mut self) {
-        for env in self.0.iter_mut() {
-            for (_, object) in env {
-                object.mark();
-            }
+    fn frame_at(&self, depth: usize) -> &Frame {
+        let mut frame = as_frame(&self.current);
+        for _ in 0..depth {
+            frame = as_frame(frame.parent.as_ref().expect("variable depth out of range"));
         }
+        frame
     }
 
-    #[inline(always)]
-    pub fn cur_env_pop(&mut self) -> HashMap<Rc<String>, HeapObject> {
-        self.0.pop().unwrap()
+    #[inline]
+    pub fn local(&self, depth: usize, index: usize) -> HeapObject {
+        self.frame_at(depth).vars.borrow()[index].clone()
+    }
+
+    #[inline]
+    pub fn set_local(&mut self, depth: usize, index: usize, value: HeapObject) {
+        self.frame_at(depth).vars.borrow_mut()[index] = value;
     }
 }
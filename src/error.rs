@@ -1,44 +1,197 @@
 use std::fmt;
+use std::borrow::Cow;
 use std::rc::Rc;
+use std::option::Option;
+use std::collections::HashMap;
+use symbol::Symbol;
+use parse::{Span, ReadError, datum_len};
+use types::{Object, HeapObject, Type, ListIter};
 
 pub enum ErrType {
-    WrongType{wanted: &'static str, got: &'static str},
+    WrongType{wanted: Cow<'static, str>, got: Cow<'static, str>},
     WrongArgsNum{wanted: usize, got: usize},
     WrongMinArgsNum{min: usize, got: usize},
-    NotCallable(&'static str),
-    SymbolNotFound(Rc<String>),
+    NotCallable(Cow<'static, str>),
+    SymbolNotFound(Symbol),
+    BadSyntax(&'static str),
+    Read(&'static str),
+    // a macro was used in a way none of its rules match
+    NoMatchingRule(Symbol),
+    KeywordAsVariable(Symbol),
+    ExpansionTooDeep,
+    // a form the reader produces that has no meaning yet, like quasiquote
+    Unsupported(&'static str),
+    DivisionByZero,
+    // a continuation and the value passed to it, on the way to the run it
+    // was captured in
+    Escape(HeapObject, HeapObject),
+    // raise with no handler to catch it
+    Raised(HeapObject),
+    HandlerReturned,
+    File(String),
+    IndexOutOfRange{index: i64, len: usize},
+    // the length of a sequence to be made, as written
+    NegativeLength(String),
+    TooLong(String),
+    // the key, as written
+    KeyNotFound(String),
+    // exit with the process status, on the way to the top level
+    Exit(i32),
+    Interrupted,
+}
+
+// Source text data were read from, kept so errors can quote it.
+pub struct Source {
+    pub name: String,
+    pub text: String,
+}
+
+impl Source {
+    pub fn new(name: &str, text: String) -> Source {
+        Source{name: name.to_string(), text}
+    }
+}
+
+// Where in its source an expression starts.
+#[derive(Clone)]
+pub struct Location {
+    pub source: Rc<Source>,
+    pub span: Span,
+}
+
+impl Location {
+    // Where in source the reader found obj.
+    pub fn of(source: &Option<Rc<Source>>, obj: &HeapObject) -> Option<Location> {
+        match (source, obj.span.get()) {
+            (Option::Some(source), Option::Some(span)) => Option::Some(Location{source: source.clone(), span}),
+            _ => Option::None,
+        }
+    }
+}
+
+// The sources of an expression being expanded and compiled: the one it was
+// read from, and those of the files included in it for the data read from
+// them and the code made from those.
+pub struct Sources {
+    source: Option<Rc<Source>>,
+    included: HashMap<*const Object, (HeapObject, Rc<Source>)>,
+}
+
+impl Sources {
+    pub fn new(source: Option<Rc<Source>>) -> Sources {
+        Sources{source, included: HashMap::new()}
+    }
+
+    // Where in its source the reader found obj.
+    pub fn location(&self, obj: &HeapObject) -> Option<Location> {
+        match self.included.get(&(&***obj as *const Object)) {
+            Option::Some((_, source)) => Location::of(&Option::Some(source.clone()), obj),
+            Option::None => Location::of(&self.source, obj),
+        }
+    }
+
+    // Notes that obj was read from source.
+    pub fn include(&mut self, obj: &HeapObject, source: &Rc<Source>) {
+        self.included.insert(&***obj as *const Object, (obj.clone(), source.clone()));
+    }
+
+    // Gives obj the position of from, if it has none.
+    pub fn locate(&mut self, obj: &HeapObject, from: &HeapObject) {
+        if obj.span.get().is_some() {
+            return;
+        }
+        obj.span.set(from.span.get());
+        if let Option::Some((_, source)) = self.included.get(&(&***from as *const Object)) {
+            let source = source.clone();
+            self.include(obj, &source);
+        }
+    }
 }
 
 pub struct Err {
-    err_type: ErrType,
-    trace: Vec<Rc<String>>
+    pub err_type: ErrType,
+    trace: Vec<Symbol>,
+    location: Option<Location>,
 }
 
 impl Err {
-    pub fn new(err_type: ErrType, trace: Vec<Rc<String>>) -> Err {
-        Err{err_type: err_type, trace: trace}
+    pub fn new(err_type: ErrType, trace: Vec<Symbol>) -> Err {
+        Err{err_type, trace, location: Option::None}
+    }
+
+    pub fn read(err: ReadError, source: &Rc<Source>) -> Err {
+        Err{
+            err_type: ErrType::Read(err.message),
+            trace: Vec::new(),
+            location: Option::Some(Location{source: source.clone(), span: err.span}),
+        }
+    }
+
+    // Blames the expression at location, unless the error has already been
+    // located at an expression nested in it.
+    pub fn at(mut self, location: Option<Location>) -> Err {
+        if self.location.is_none() {
+            self.location = location;
+        }
+        self
+    }
+
+    pub fn location(&self) -> Option<&Location> {
+        self.location.as_ref()
     }
 }
 
 impl fmt::Display for Err {
     fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
-        write!(f, "in function: \n");
-        if self.trace.len() != 0 {
-            for (i, fn_name) in (&self.trace).into_iter().enumerate() {
-                let res = write!(f, "{}: {}\n", i, fn_name);
-                if let Result::Err(e) = res {
-                    return Result::Err(e);
-                }
+        write!(f, "{}", self.err_type)?;
+        if let Option::Some(ref location) = self.location {
+            write!(f, "\n{}", location)?;
+        }
+        if !self.trace.is_empty() {
+            write!(f, "\nin function:")?;
+            for (i, fn_name) in self.trace.iter().enumerate() {
+                write!(f, "\n{}: {}", i, fn_name)?;
             }
         }
-        write!(f, "{}", self.err_type)
+        Result::Ok(())
+    }
+}
+
+// The position, and the line it is on with the expression underlined:
+//
+//   --> file.scm:2:3
+//    |
+//  2 |   (car 1)
+//    |   ^^^^^^^
+impl fmt::Display for Location {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let span = self.span;
+        let gutter = " ".repeat(span.line.to_string().len());
+        write!(f, "{}--> {}:{}:{}", gutter, self.source.name, span.line, span.column)?;
+
+        let text = &self.source.text;
+        let offset = (span.offset as usize).min(text.len());
+        if !text.is_char_boundary(offset) {
+            return Result::Ok(());
+        }
+        let start = text[..offset].rfind('\n').map_or(0, |i| i + 1);
+        let end = text[offset..].find('\n').map_or(text.len(), |i| offset + i);
+        let line = text[start..end].trim_end_matches('\r');
+
+        // tabs are shown as single spaces so the carets line up
+        let prefix: String = text[start..offset].chars()
+            .map(|c| if c == '\t' {' '} else {c}).collect();
+        let carets = text[offset..offset + datum_len(&text[offset..])].chars()
+            .take_while(|&c| c != '\n').count().max(1);
+        write!(f, "\n{} |\n{} | {}\n{} | {}{}", gutter, span.line, line.replace('\t', " "),
+               gutter, " ".repeat(prefix.chars().count()), "^".repeat(carets))
     }
 }
 
 impl fmt::Display for ErrType {
     fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
         match *self {
-            ErrType::WrongType{wanted: w, got: g} =>write!(
+            ErrType::WrongType{wanted: ref w, got: ref g} =>write!(
                     f,"Wrong argument type, wanted: {}, got: {}", w, g),
             ErrType::WrongArgsNum{wanted: w, got: g} => write!(
                 f, "Wrong number of arguments, wanted: {}, got: {}", w, g),
@@ -46,7 +199,33 @@ impl fmt::Display for ErrType {
                 f, "Wanted minimum {} args, got: {}", m, g
             ),
             ErrType::SymbolNotFound(ref sym) => write!(f, "Couldn't find symbol {}", sym),
-            ErrType::NotCallable(t) => write!(f, "Type {} is not callable", t)
+            ErrType::NotCallable(ref t) => write!(f, "Type {} is not callable", t),
+            ErrType::BadSyntax(form) => write!(f, "Bad syntax in {} form", form),
+            ErrType::Read(message) => write!(f, "{}", message),
+            ErrType::NoMatchingRule(ref name) => write!(f, "No syntax rule of {} matches its use", name),
+            ErrType::KeywordAsVariable(ref name) => write!(f, "Syntactic keyword {} used as a variable", name),
+            ErrType::ExpansionTooDeep => write!(f, "Macro expansion too deep"),
+            ErrType::Unsupported(form) => write!(f, "{} is not supported", form),
+            ErrType::DivisionByZero => write!(f, "Division by zero"),
+            ErrType::Raised(ref obj) => match obj.object_type {
+                Type::Error(ref e) => {
+                    write!(f, "{}", e.message)?;
+                    for irritant in ListIter::new(&e.irritants) {
+                        write!(f, " {}", irritant)?;
+                    }
+                    Result::Ok(())
+                },
+                _ => write!(f, "Uncaught exception: {}", obj),
+            },
+            ErrType::HandlerReturned => write!(f, "Exception handler returned from a non-continuable raise"),
+            ErrType::File(ref message) => write!(f, "{}", message),
+            ErrType::IndexOutOfRange{index, len} => write!(f, "Index {} out of range for length {}", index, len),
+            ErrType::NegativeLength(ref len) => write!(f, "Length {} is negative", len),
+            ErrType::TooLong(ref len) => write!(f, "Length {} is too long to allocate", len),
+            ErrType::KeyNotFound(ref key) => write!(f, "Key {} not found in hash table", key),
+            ErrType::Interrupted => write!(f, "Interrupted"),
+            ErrType::Exit(status) => write!(f, "Exit with status {}", status),
+            ErrType::Escape(..) => write!(f, "Continuation called after the primitive call it was captured in returned"),
         }
     }
 }
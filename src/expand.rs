@@ -0,0 +1,1266 @@
+use types::{Object, Type, HeapObject, ListIter};
+use error::{Err, ErrType, Source, Sources};
+use interpreter::Interpreter;
+use symbol::Symbol;
+use load::resolve;
+use std::cell::RefCell;
+use std::collections::{HashMap, HashSet, VecDeque};
+use std::option::Option;
+use std::result::Result;
+use std::rc::Rc;
+use std::path::PathBuf;
+
+// The forms the compiler implements, and those the expander removes.
+pub const CORE_FORMS: &[&str] = &[
+    "define", "while", "quote", "if", "lambda", "set!", "begin", "let", "let*", "letrec",
+    "cond", "case", "and", "or", "define-syntax", "let-syntax", "letrec-syntax", "syntax-rules",
+    "include", "include-ci", "quasiquote", "unquote", "unquote-splicing",
+];
+
+// More macro uses than this expanded inside each other are an error rather
+// than a stack overflow, or a macro that never stops expanding.
+const MAX_DEPTH: usize = 500;
+
+// What an identifier means where it is used.
+#[derive(Clone)]
+enum Binding {
+    // a local variable, renamed to a symbol unique to its binding
+    Variable(Symbol),
+    Macro(Rc<Macro>),
+    Core(&'static str),
+    Global(Symbol),
+}
+
+impl Binding {
+    fn same(&self, other: &Binding) -> bool {
+        match (self, other) {
+            (Binding::Variable(a), Binding::Variable(b)) | (Binding::Global(a), Binding::Global(b)) => a == b,
+            (Binding::Macro(a), Binding::Macro(b)) => Rc::ptr_eq(a, b),
+            (Binding::Core(a), Binding::Core(b)) => a == b,
+            _ => false,
+        }
+    }
+}
+
+// The identifiers bound by a binding form, inside those of the forms
+// enclosing it. None is the top level.
+pub struct Scope {
+    bindings: RefCell<Vec<(Symbol, Binding)>>,
+    parent: Env,
+}
+
+type Env = Option<Rc<Scope>>;
+
+fn new_scope(parent: &Env) -> Env {
+    Option::Some(Rc::new(Scope{bindings: RefCell::new(Vec::new()), parent: parent.clone()}))
+}
+
+// Binds id in the innermost scope of env, which is not the top level.
+fn bind(env: &Env, id: Symbol, binding: Binding) {
+    env.as_ref().unwrap().bindings.borrow_mut().push((id, binding));
+}
+
+fn lookup(env: &Env, id: &Symbol, innermost: bool) -> Option<Binding> {
+    let mut scope = env.as_ref();
+    while let Option::Some(s) = scope {
+        if let Option::Some((_, binding)) = s.bindings.borrow().iter().rev().find(|(name, _)| name == id) {
+            return Option::Some(binding.clone());
+        }
+        if innermost {
+            break;
+        }
+        scope = s.parent.as_ref();
+    }
+    Option::None
+}
+
+// (syntax-rules (literal...) (pattern template)...)
+pub struct Macro {
+    name: Symbol,
+    // None for ...
+    ellipsis: Option<Symbol>,
+    literals: Vec<Symbol>,
+    rules: Vec<(HeapObject, HeapObject)>,
+    // the scope the macro was defined in, where the identifiers its
+    // templates introduce are looked up
+    env: Env,
+}
+
+// An identifier inserted by a macro template stands for name as seen from
+// env.
+struct Alias {
+    name: Symbol,
+    env: Env,
+}
+
+// Expansion state kept between top level forms.
+pub struct Syntax {
+    macros: HashMap<Symbol, Rc<Macro>>,
+    aliases: HashMap<Symbol, Alias>,
+}
+
+impl Syntax {
+    pub fn new() -> Syntax {
+        Syntax{macros: HashMap::new(), aliases: HashMap::new()}
+    }
+
+    // The names of the macros defined at the top level.
+    pub fn macro_names(&self) -> impl Iterator<Item = &Symbol> {
+        self.macros.keys()
+    }
+}
+
+impl Default for Syntax {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+// What pattern variables matched, with a level of nesting per ellipsis
+// they were followed by.
+#[derive(Clone)]
+enum Match {
+    One(HeapObject),
+    Many(Vec<Match>),
+}
+
+type Bindings = HashMap<Symbol, Match>;
+
+// Rewrites expressions into the forms the compiler implements, expanding
+// macro uses. Each local variable is renamed to a fresh uninterned symbol
+// and the identifiers inserted by a macro to aliases of the identifiers in
+// its templates, so neither can capture the other.
+pub struct Expander<'a> {
+    interpreter: &'a mut Interpreter,
+    sources: Sources,
+    depth: usize,
+    // the includes in included files, with the files that led to each,
+    // the one it is in last
+    included: HashMap<*const Object, (HeapObject, Rc<Vec<PathBuf>>)>,
+}
+
+impl<'a> Expander<'a> {
+    pub fn new(interpreter: &'a mut Interpreter, source: Option<Rc<Source>>) -> Expander<'a> {
+        Expander{interpreter, sources: Sources::new(source), depth: 0, included: HashMap::new()}
+    }
+
+    // Where what was expanded was read from, for compiling it.
+    pub fn into_sources(self) -> Sources {
+        self.sources
+    }
+
+    // Expands a form at the top level, where definitions are global.
+    pub fn expand_toplevel(&mut self, form: &HeapObject) -> Result<HeapObject, Err> {
+        let depth = self.depth;
+        let res = self.expand_toplevel_form(form);
+        self.depth = depth;
+        res
+    }
+
+    fn expand_toplevel_form(&mut self, form: &HeapObject) -> Result<HeapObject, Err> {
+        let form = self.expand_head(form, &Option::None)?;
+        match self.core_form(&form, &Option::None) {
+            Option::Some("begin") => {
+                let forms = self.forms("begin", &form)?;
+                let mut expanded = Vec::with_capacity(forms.len());
+                for form in &forms[1..] {
+                    expanded.push(self.expand_toplevel(form)?);
+                }
+                Result::Ok(self.rebuild(&form, "begin", expanded))
+            },
+            Option::Some("define-syntax") => {
+                self.define_syntax(&form, &Option::None)?;
+                Result::Ok(self.rebuild(&form, "begin", Vec::new()))
+            },
+            _ => self.expand(&form, &Option::None),
+        }
+    }
+
+    #[inline]
+    fn syntax(&mut self) -> &mut Syntax {
+        self.interpreter.syntax()
+    }
+
+    fn bad_syntax(&self, form: &'static str, at: &HeapObject) -> Err {
+        self.interpreter.bad_syntax(form).at(self.sources.location(at))
+    }
+
+    // What id means in env.
+    fn resolve(&mut self, id: &Symbol, env: &Env) -> Binding {
+        let mut id = id.clone();
+        let mut env = env.clone();
+        loop {
+            if let Option::Some(binding) = lookup(&env, &id, false) {
+                return binding;
+            }
+            match self.syntax().aliases.get(&id) {
+                Option::Some(alias) => {
+                    env = alias.env.clone();
+                    id = alias.name.clone();
+                },
+                Option::None => break,
+            }
+        }
+
+        if let Option::Some(mac) = self.syntax().macros.get(&id) {
+            return Binding::Macro(mac.clone());
+        }
+        if self.interpreter.is_interned(&id) {
+            if let Option::Some(&core) = CORE_FORMS.iter().find(|&&name| name == id.name()) {
+                return Binding::Core(core);
+            }
+        }
+        Binding::Global(id)
+    }
+
+    // The identifier an alias was made from, through any number of
+    // expansions.
+    fn base(&mut self, id: &Symbol) -> Symbol {
+        let mut id = id.clone();
+        while let Option::Some(alias) = self.syntax().aliases.get(&id) {
+            id = alias.name.clone();
+        }
+        id
+    }
+
+    // Whether id means the free identifier name, like else in cond.
+    fn is_keyword(&mut self, obj: &HeapObject, name: &str, env: &Env) -> bool {
+        match obj.object_type {
+            Type::Symbol(ref id) => match self.resolve(id, env) {
+                Binding::Global(ref s) => s.name() == name && self.interpreter.is_interned(s),
+                _ => false,
+            },
+            _ => false,
+        }
+    }
+
+    // The name of the core form form is a use of, if any.
+    fn core_form(&mut self, form: &HeapObject, env: &Env) -> Option<&'static str> {
+        match form.car() {
+            Option::Some(head) => match head.object_type {
+                Type::Symbol(ref id) => match self.resolve(id, env) {
+                    Binding::Core(name) => Option::Some(name),
+                    _ => Option::None,
+                },
+                _ => Option::None,
+            },
+            Option::None => Option::None,
+        }
+    }
+
+    // Expands form for as long as it is a macro use. Each expansion counts
+    // towards the depth of those nested in it, until the caller restores
+    // the depth.
+    fn expand_head(&mut self, form: &HeapObject, env: &Env) -> Result<HeapObject, Err> {
+        let mut form = form.clone();
+        while let Option::Some(id) = form.car().and_then(|head| head.object_type.as_symbol()) {
+            let mac = match self.resolve(&id, env) {
+                Binding::Macro(mac) => mac,
+                Binding::Core(name @ ("include" | "include-ci")) => {
+                    form = self.include(name, &form)?;
+                    continue;
+                },
+                _ => break,
+            };
+            self.depth += 1;
+            if self.depth > MAX_DEPTH {
+                return Result::Err(Err::new(ErrType::ExpansionTooDeep, Vec::new())
+                                   .at(self.sources.location(&form)));
+            }
+            form = self.transform(&mac, &form, env)?;
+        }
+        Result::Ok(form)
+    }
+
+    // (include file...) and (include-ci file...) become a begin with the
+    // data in the files, read case-insensitively for include-ci. Files are
+    // relative to the one the include is in.
+    fn include(&mut self, name: &'static str, form: &HeapObject) -> Result<HeapObject, Err> {
+        let forms = self.forms(name, form)?;
+        let chain = match self.included.get(&(&***form as *const Object)) {
+            Option::Some((_, chain)) => chain.clone(),
+            Option::None => Rc::new(self.interpreter.loading().clone()),
+        };
+        let location = self.sources.location(form);
+        let mut data = Vec::new();
+        for file in &forms[1..] {
+            let path = match file.object_type {
+                Type::String(ref s) => resolve(chain.last().map(PathBuf::as_path), &s.borrow()),
+                _ => return Result::Err(self.bad_syntax(name, form)),
+            };
+            let canonical = self.interpreter.check_cycle(&path, &chain).map_err(|e| e.at(location.clone()))?;
+            let (read, source) = self.interpreter.read_file(&path, name == "include-ci")
+                .map_err(|e| e.at(location.clone()))?;
+            let mut inner = (*chain).clone();
+            inner.push(canonical);
+            let inner = Rc::new(inner);
+            for datum in read {
+                self.mark_included(&datum, &inner, &source);
+                data.push(datum);
+            }
+        }
+        Result::Ok(self.rebuild(form, "begin", data))
+    }
+
+    // Notes that datum was read from source, another file than the one
+    // being expanded, so errors in it point there, and the includes in it.
+    fn mark_included(&mut self, datum: &HeapObject, chain: &Rc<Vec<PathBuf>>, source: &Rc<Source>) {
+        let mut seen = HashSet::new();
+        let mut pending = vec![datum.clone()];
+        while let Option::Some(obj) = pending.pop() {
+            if !seen.insert(&**obj as *const Object) {
+                continue;
+            }
+            self.sources.include(&obj, source);
+            match obj.object_type {
+                Type::Pair(ref p) => {
+                    let car = p.car.borrow().clone();
+                    if matches!(car.object_type, Type::Symbol(ref s) if s.name() == "include" || s.name() == "include-ci") {
+                        self.included.insert(&**obj as *const Object, (obj.clone(), chain.clone()));
+                    }
+                    pending.push(car);
+                    pending.push(p.cdr.borrow().clone());
+                },
+                Type::Vector(ref v) => pending.extend(v.borrow().iter().cloned()),
+                _ => {},
+            }
+        }
+    }
+
+    // Expands an expression in env.
+    pub fn expand(&mut self, form: &HeapObject, env: &Env) -> Result<HeapObject, Err> {
+        let depth = self.depth;
+        let res = self.expand_form(form, env);
+        self.depth = depth;
+        res
+    }
+
+    fn expand_form(&mut self, form: &HeapObject, env: &Env) -> Result<HeapObject, Err> {
+        let form = self.expand_head(form, env)?;
+        match form.object_type {
+            Type::Symbol(ref id) => self.variable(id, &form, env),
+            Type::Pair(_) => match self.core_form(&form, env) {
+                Option::Some(name) => self.expand_core(name, &form, env),
+                Option::None => {
+                    let elems = self.expand_all("procedure call", &form, env)?;
+                    Result::Ok(self.list(&form, elems, Option::None))
+                },
+            },
+            _ => Result::Ok(self.strip(&form)),
+        }
+    }
+
+    // Each form gets a method of its own, so the frames of nested
+    // expansions stay small.
+    fn expand_core(&mut self, name: &'static str, form: &HeapObject, env: &Env) -> Result<HeapObject, Err> {
+        let args = form.cdr().unwrap();
+        match name {
+            "quote" => {
+                let datum = self.strip(&args);
+                Result::Ok(self.cons_keyword(form, name, datum))
+            },
+            "if" | "begin" | "and" | "or" | "while" => {
+                let elems = self.expand_all(name, &args, env)?;
+                Result::Ok(self.rebuild(form, name, elems))
+            },
+            "set!" => self.expand_set(form, env),
+            "lambda" => self.expand_lambda(form, env),
+            "define" => {
+                let id = self.define_name(form)?;
+                if env.is_some() && lookup(env, &id, true).is_none() {
+                    // not at the start of a body, the variable is still
+                    // local to it
+                    self.bind_variable(id, env);
+                }
+                self.expand_define(form, env)
+            },
+            "let" => self.expand_let(form, env),
+            "let*" => self.expand_let_star(form, env),
+            "letrec" => self.expand_letrec(form, env),
+            "cond" | "case" => self.expand_conditional(name, form, env),
+            "define-syntax" => {
+                self.define_syntax(form, env)?;
+                Result::Ok(self.rebuild(form, "begin", Vec::new()))
+            },
+            "let-syntax" | "letrec-syntax" => self.expand_let_syntax(name, form, env),
+            // read, but not evaluated, so they aren't taken for calls
+            "quasiquote" | "unquote" | "unquote-splicing" => Result::Err(
+                Err::new(ErrType::Unsupported(name), Vec::new()).at(self.sources.location(form))),
+            _ => Result::Err(self.bad_syntax(name, form)),
+        }
+    }
+
+    // (set! name value)
+    fn expand_set(&mut self, form: &HeapObject, env: &Env) -> Result<HeapObject, Err> {
+        let (target, value) = match self.forms("set!", form)?.as_slice() {
+            [_, target, value] => (target.clone(), value.clone()),
+            _ => return Result::Err(self.bad_syntax("set!", form)),
+        };
+        let target = match target.object_type {
+            Type::Symbol(ref id) => self.variable(id, &target, env)?,
+            _ => return Result::Err(self.bad_syntax("set!", form)),
+        };
+        let value = self.expand(&value, env)?;
+        Result::Ok(self.rebuild(form, "set!", vec![target, value]))
+    }
+
+    // (lambda params body...)
+    fn expand_lambda(&mut self, form: &HeapObject, env: &Env) -> Result<HeapObject, Err> {
+        let args = form.cdr().unwrap();
+        let (params, body) = match (args.car(), args.cdr()) {
+            (Option::Some(params), Option::Some(body)) => (params, body),
+            _ => return Result::Err(self.bad_syntax("lambda", form)),
+        };
+        let scope = new_scope(env);
+        let params = self.bind_params("lambda", &params, &scope)?;
+        let mut elems = vec![params];
+        elems.extend(self.expand_body("lambda", &body, &scope)?);
+        Result::Ok(self.rebuild(form, "lambda", elems))
+    }
+
+    // (letrec ((name init) ...) body...)
+    fn expand_letrec(&mut self, form: &HeapObject, env: &Env) -> Result<HeapObject, Err> {
+        let bindings = self.bindings("letrec", form)?;
+        let scope = new_scope(env);
+        let mut renamed = Vec::with_capacity(bindings.len());
+        for (id, init) in bindings {
+            renamed.push((self.bind_variable(id, &scope), init));
+        }
+        let mut expanded = Vec::with_capacity(renamed.len());
+        for (name, init) in renamed {
+            let init = self.expand(&init, &scope)?;
+            expanded.push(self.list(form, vec![name, init], Option::None));
+        }
+        let mut elems = vec![self.list(form, expanded, Option::None)];
+        let body = form.cdr().unwrap().cdr().unwrap();
+        elems.extend(self.expand_body("letrec", &body, &scope)?);
+        Result::Ok(self.rebuild(form, "letrec", elems))
+    }
+
+    // (cond clause...) or (case key clause...)
+    fn expand_conditional(&mut self, name: &'static str, form: &HeapObject, env: &Env) -> Result<HeapObject, Err> {
+        let forms = self.forms(name, form)?;
+        let mut elems = Vec::with_capacity(forms.len());
+        let clauses = if name == "case" {
+            match forms.get(1) {
+                Option::Some(key) => elems.push(self.expand(key, env)?),
+                Option::None => return Result::Err(self.bad_syntax(name, form)),
+            }
+            &forms[2..]
+        } else {
+            &forms[1..]
+        };
+        for clause in clauses {
+            elems.push(self.expand_clause(name, clause, env, name == "cond")?);
+        }
+        Result::Ok(self.rebuild(form, name, elems))
+    }
+
+    // (let-syntax ((name spec) ...) body...), becomes a let without
+    // bindings
+    fn expand_let_syntax(&mut self, name: &'static str, form: &HeapObject, env: &Env) -> Result<HeapObject, Err> {
+        let bindings = self.bindings(name, form)?;
+        let scope = new_scope(env);
+        let macro_env = if name == "let-syntax" {env.clone()} else {scope.clone()};
+        for (id, spec) in bindings {
+            let mac = self.syntax_rules(&id, &spec, &macro_env)?;
+            bind(&scope, id, Binding::Macro(mac));
+        }
+        let body = form.cdr().unwrap().cdr().unwrap();
+        let mut elems = vec![self.interpreter.new_nil()];
+        elems.extend(self.expand_body(name, &body, &new_scope(&scope))?);
+        Result::Ok(self.rebuild(form, "let", elems))
+    }
+
+    // A reference to the variable id.
+    fn variable(&mut self, id: &Symbol, at: &HeapObject, env: &Env) -> Result<HeapObject, Err> {
+        match self.resolve(id, env) {
+            Binding::Variable(s) | Binding::Global(s) => Result::Ok(self.symbol(s, at)),
+            Binding::Macro(_) | Binding::Core(_) => {
+                let name = self.base(id);
+                Result::Err(Err::new(ErrType::KeywordAsVariable(name), Vec::new()).at(self.sources.location(at)))
+            },
+        }
+    }
+
+    fn expand_all(&mut self, form: &'static str, args: &HeapObject, env: &Env) -> Result<Vec<HeapObject>, Err> {
+        let args = match Object::list_to_vec(args) {
+            Option::Some(args) => args,
+            Option::None => return Result::Err(self.bad_syntax(form, args)),
+        };
+        let mut expanded = Vec::with_capacity(args.len());
+        for arg in args {
+            expanded.push(self.expand(&arg, env)?);
+        }
+        Result::Ok(expanded)
+    }
+
+    // Renames the parameters in params, binding them in scope.
+    fn bind_params(&mut self, form: &'static str, params: &HeapObject, scope: &Env) -> Result<HeapObject, Err> {
+        let mut iter = ListIter::new(params);
+        let mut renamed = Vec::new();
+        for param in iter.by_ref() {
+            match param.object_type {
+                Type::Symbol(ref id) => renamed.push(self.bind_variable(id.clone(), scope)),
+                _ => return Result::Err(self.bad_syntax(form, &param)),
+            }
+        }
+        let tail = iter.tail().clone();
+        let tail = match tail.object_type {
+            Type::Symbol(ref id) => Option::Some(self.bind_variable(id.clone(), scope)),
+            Type::Nil => Option::None,
+            _ => return Result::Err(self.bad_syntax(form, &tail)),
+        };
+        Result::Ok(self.list(params, renamed, tail))
+    }
+
+    // Binds id to a fresh variable in scope, returning the renamed symbol.
+    fn bind_variable(&mut self, id: Symbol, scope: &Env) -> HeapObject {
+        let renamed = Symbol::uninterned(id.name());
+        bind(scope, id, Binding::Variable(renamed.clone()));
+        self.interpreter.new_object(Type::Symbol(renamed))
+    }
+
+    // The forms of a body, with the variables and macros it defines bound
+    // in scope before any of them is expanded.
+    fn expand_body(&mut self, form: &'static str, body: &HeapObject, scope: &Env) -> Result<Vec<HeapObject>, Err> {
+        let mut pending: VecDeque<HeapObject> = match Object::list_to_vec(body) {
+            Option::Some(forms) => forms.into(),
+            Option::None => return Result::Err(self.bad_syntax(form, body)),
+        };
+
+        let mut forms = Vec::new();
+        while let Option::Some(form) = pending.pop_front() {
+            let depth = self.depth;
+            let form = self.expand_head(&form, scope);
+            self.depth = depth;
+            let form = form?;
+            match self.core_form(&form, scope) {
+                Option::Some("begin") => {
+                    for inner in self.forms("begin", &form)?.into_iter().skip(1).rev() {
+                        pending.push_front(inner);
+                    }
+                },
+                Option::Some("define") => {
+                    let name = self.define_name(&form)?;
+                    if lookup(scope, &name, true).is_none() {
+                        self.bind_variable(name, scope);
+                    }
+                    forms.push(form);
+                },
+                Option::Some("define-syntax") => self.define_syntax(&form, scope)?,
+                _ => forms.push(form),
+            }
+        }
+
+        let mut expanded = Vec::with_capacity(forms.len());
+        for form in forms {
+            expanded.push(match self.core_form(&form, scope) {
+                Option::Some("define") => self.expand_define(&form, scope)?,
+                _ => self.expand(&form, scope)?,
+            });
+        }
+        Result::Ok(expanded)
+    }
+
+    // The identifier (define name ...) or (define (name ...) ...) defines.
+    fn define_name(&self, form: &HeapObject) -> Result<Symbol, Err> {
+        let target = form.cdr().and_then(|args| args.car());
+        let name = match target {
+            Option::Some(ref target) if target.car().is_some() => target.car(),
+            _ => target,
+        };
+        match name.as_ref().and_then(|name| name.object_type.as_symbol()) {
+            Option::Some(id) => Result::Ok(id),
+            Option::None => Result::Err(self.bad_syntax("define", form)),
+        }
+    }
+
+    // A define whose name is already bound, or global at the top level.
+    fn expand_define(&mut self, form: &HeapObject, env: &Env) -> Result<HeapObject, Err> {
+        let id = self.define_name(form)?;
+        let name = match *env {
+            Option::None => {
+                let name = self.base(&id);
+                self.syntax().macros.remove(&name);
+                name
+            },
+            Option::Some(_) => match lookup(env, &id, true) {
+                Option::Some(Binding::Variable(s)) => s,
+                _ => return Result::Err(self.bad_syntax("define", form)),
+            },
+        };
+
+        let args = form.cdr().unwrap();
+        let target = args.car().unwrap();
+        let rest = args.cdr().unwrap();
+        let elems = if target.car().is_some() {
+            // (define (name . params) body...)
+            let scope = new_scope(env);
+            let params = self.bind_params("define", &target.cdr().unwrap(), &scope)?;
+            let name = self.symbol(name, &target.car().unwrap());
+            let signature = self.interpreter.cons(name, params);
+            let signature = self.located(signature, &target);
+            let mut elems = vec![signature];
+            elems.extend(self.expand_body("define", &rest, &scope)?);
+            elems
+        } else {
+            let mut elems = vec![self.symbol(name, &target)];
+            elems.extend(self.expand_all("define", &rest, env)?);
+            elems
+        };
+        Result::Ok(self.rebuild(form, "define", elems))
+    }
+
+    // ((name init) ...)
+    fn bindings(&mut self, form_name: &'static str, form: &HeapObject) -> Result<Vec<(Symbol, HeapObject)>, Err> {
+        let list = form.cdr().and_then(|args| args.car());
+        let list = match list.as_ref().and_then(Object::list_to_vec) {
+            Option::Some(list) => list,
+            Option::None => return Result::Err(self.bad_syntax(form_name, form)),
+        };
+        let mut parsed = Vec::with_capacity(list.len());
+        for binding in list {
+            match Object::list_to_vec(&binding) {
+                Option::Some(ref b) if b.len() == 2 => match b[0].object_type.as_symbol() {
+                    Option::Some(id) => parsed.push((id, b[1].clone())),
+                    Option::None => return Result::Err(self.bad_syntax(form_name, &binding)),
+                },
+                _ => return Result::Err(self.bad_syntax(form_name, &binding)),
+            }
+        }
+        Result::Ok(parsed)
+    }
+
+    // (let ((name init) ...) body...) or (let loop ((name init) ...) body...)
+    fn expand_let(&mut self, form: &HeapObject, env: &Env) -> Result<HeapObject, Err> {
+        let args = form.cdr().unwrap();
+        let (loop_name, rest) = match args.car().and_then(|first| first.object_type.as_symbol()) {
+            Option::Some(id) => (Option::Some(id), args.cdr().unwrap()),
+            Option::None => (Option::None, args.clone()),
+        };
+        let inner = self.interpreter.cons(form.car().unwrap(), rest.clone());
+        let bindings = self.bindings("let", &inner)?;
+        let body = match rest.cdr() {
+            Option::Some(body) => body,
+            Option::None => return Result::Err(self.bad_syntax("let", form)),
+        };
+
+        let mut inits = Vec::with_capacity(bindings.len());
+        for (_, init) in &bindings {
+            inits.push(self.expand(init, env)?);
+        }
+        // a named let's procedure is bound around its body
+        let outer = match loop_name {
+            Option::Some(_) => new_scope(env),
+            Option::None => env.clone(),
+        };
+        let mut elems = Vec::new();
+        if let Option::Some(id) = loop_name {
+            elems.push(self.bind_variable(id, &outer));
+        }
+        let scope = new_scope(&outer);
+        let mut renamed = Vec::with_capacity(bindings.len());
+        for ((id, _), init) in bindings.into_iter().zip(inits) {
+            let name = self.bind_variable(id, &scope);
+            renamed.push(self.list(form, vec![name, init], Option::None));
+        }
+        elems.push(self.list(form, renamed, Option::None));
+        elems.extend(self.expand_body("let", &body, &scope)?);
+        Result::Ok(self.rebuild(form, "let", elems))
+    }
+
+    // (let* ((name init) ...) body...), each binding in a scope of its own
+    fn expand_let_star(&mut self, form: &HeapObject, env: &Env) -> Result<HeapObject, Err> {
+        let bindings = self.bindings("let*", form)?;
+        let body = form.cdr().unwrap().cdr().unwrap();
+        let mut scope = env.clone();
+        let mut renamed = Vec::with_capacity(bindings.len());
+        for (id, init) in bindings {
+            let init = self.expand(&init, &scope)?;
+            scope = new_scope(&scope);
+            let name = self.bind_variable(id, &scope);
+            renamed.push(self.list(form, vec![name, init], Option::None));
+        }
+        let mut elems = vec![self.list(form, renamed, Option::None)];
+        elems.extend(self.expand_body("let*", &body, &new_scope(&scope))?);
+        Result::Ok(self.rebuild(form, "let*", elems))
+    }
+
+    // A cond clause (test expr...), (test => receiver) or (else expr...),
+    // or a case clause ((datum...) ...).
+    fn expand_clause(&mut self, form: &'static str, clause: &HeapObject, env: &Env, cond: bool)
+                     -> Result<HeapObject, Err> {
+        let elems = match Object::list_to_vec(clause) {
+            Option::Some(ref elems) if !elems.is_empty() => elems.clone(),
+            _ => return Result::Err(self.bad_syntax(form, clause)),
+        };
+        let mut expanded = Vec::with_capacity(elems.len());
+        expanded.push(if self.is_keyword(&elems[0], "else", env) {
+            self.keyword("else", &elems[0])
+        } else if cond {
+            self.expand(&elems[0], env)?
+        } else {
+            self.strip(&elems[0])
+        });
+        for (i, elem) in elems.iter().enumerate().skip(1) {
+            expanded.push(if i == 1 && self.is_keyword(elem, "=>", env) {
+                self.keyword("=>", elem)
+            } else {
+                self.expand(elem, env)?
+            });
+        }
+        Result::Ok(self.list(clause, expanded, Option::None))
+    }
+
+    // (define-syntax name (syntax-rules ...))
+    fn define_syntax(&mut self, form: &HeapObject, env: &Env) -> Result<(), Err> {
+        let (id, spec) = match self.forms("define-syntax", form)?.as_slice() {
+            [_, name, spec] => match name.object_type.as_symbol() {
+                Option::Some(id) => (id, spec.clone()),
+                Option::None => return Result::Err(self.bad_syntax("define-syntax", form)),
+            },
+            _ => return Result::Err(self.bad_syntax("define-syntax", form)),
+        };
+        if env.is_none() {
+            let name = self.base(&id);
+            // defined first so the rules can refer to the macro itself
+            let mac = self.syntax_rules(&name, &spec, env)?;
+            self.syntax().macros.insert(name, mac);
+        } else {
+            let mac = self.syntax_rules(&id, &spec, env)?;
+            bind(env, id, Binding::Macro(mac));
+        }
+        Result::Ok(())
+    }
+
+    // Parses (syntax-rules (literal...) rule...) or
+    // (syntax-rules ellipsis (literal...) rule...) in env.
+    fn syntax_rules(&mut self, name: &Symbol, spec: &HeapObject, env: &Env) -> Result<Rc<Macro>, Err> {
+        let err = |e: &Self| e.bad_syntax("syntax-rules", spec);
+        let elems = match Object::list_to_vec(spec) {
+            Option::Some(elems) => elems,
+            Option::None => return Result::Err(err(self)),
+        };
+        if elems.len() < 2 || self.core_form(spec, env) != Option::Some("syntax-rules") {
+            return Result::Err(err(self));
+        }
+
+        let (ellipsis, rest) = match elems[1].object_type.as_symbol() {
+            Option::Some(id) => (Option::Some(id), &elems[2..]),
+            Option::None => (Option::None, &elems[1..]),
+        };
+        let literals = match rest.first().and_then(Object::list_to_vec) {
+            Option::Some(literals) => literals,
+            Option::None => return Result::Err(err(self)),
+        };
+        let mut literal_ids = Vec::with_capacity(literals.len());
+        for literal in literals {
+            match literal.object_type.as_symbol() {
+                Option::Some(id) => literal_ids.push(id),
+                Option::None => return Result::Err(err(self)),
+            }
+        }
+
+        let mut rules = Vec::new();
+        for rule in &rest[1..] {
+            match Object::list_to_vec(rule) {
+                Option::Some(ref rule) if rule.len() == 2 && rule[0].car().is_some() => {
+                    rules.push((rule[0].clone(), rule[1].clone()));
+                },
+                _ => return Result::Err(self.bad_syntax("syntax-rules", rule)),
+            }
+        }
+        Result::Ok(Rc::new(Macro{name: name.clone(), ellipsis, literals: literal_ids, rules, env: env.clone()}))
+    }
+
+    // Expands one use of mac.
+    fn transform(&mut self, mac: &Rc<Macro>, form: &HeapObject, env: &Env) -> Result<HeapObject, Err> {
+        let args = form.cdr().unwrap();
+        for (pattern, template) in &mac.rules {
+            let mut bindings = HashMap::new();
+            if self.match_pattern(mac, &pattern.cdr().unwrap(), &args, env, &mut bindings) {
+                let mut renames = HashMap::new();
+                let expansion = self.transcribe(mac, template, &bindings, &mut renames, form, true)?;
+                self.sources.locate(&expansion, form);
+                return Result::Ok(expansion);
+            }
+        }
+        Result::Err(Err::new(ErrType::NoMatchingRule(self.base(&mac.name)), Vec::new())
+                    .at(self.sources.location(form)))
+    }
+
+    fn is_literal(mac: &Macro, id: &Symbol) -> bool {
+        mac.literals.iter().any(|l| l == id)
+    }
+
+    fn is_ellipsis(&mut self, mac: &Macro, obj: &HeapObject) -> bool {
+        let id = match obj.object_type {
+            Type::Symbol(ref id) => id,
+            _ => return false,
+        };
+        if Expander::is_literal(mac, id) {
+            return false;
+        }
+        match mac.ellipsis {
+            Option::Some(ref ellipsis) => id == ellipsis,
+            Option::None => {
+                let base = self.base(id);
+                base.name() == "..." && self.interpreter.is_interned(&base)
+            },
+        }
+    }
+
+    fn is_underscore(&mut self, mac: &Macro, id: &Symbol) -> bool {
+        let base = self.base(id);
+        !Expander::is_literal(mac, id) && base.name() == "_" && self.interpreter.is_interned(&base)
+    }
+
+    // Matches form against pattern, adding what the pattern variables
+    // matched to bindings.
+    fn match_pattern(&mut self, mac: &Macro, pattern: &HeapObject, form: &HeapObject, env: &Env,
+                     bindings: &mut Bindings) -> bool {
+        match pattern.object_type {
+            Type::Symbol(ref id) => {
+                if Expander::is_literal(mac, id) {
+                    match form.object_type {
+                        Type::Symbol(ref f) => {
+                            let used = self.resolve(f, env);
+                            self.resolve(id, &mac.env).same(&used)
+                        },
+                        _ => false,
+                    }
+                } else {
+                    if !self.is_underscore(mac, id) {
+                        bindings.insert(id.clone(), Match::One(form.clone()));
+                    }
+                    true
+                }
+            },
+            Type::Pair(_) => {
+                let mut iter = ListIter::new(pattern);
+                let pats: Vec<HeapObject> = iter.by_ref().collect();
+                let tail = iter.tail().clone();
+                let mut iter = ListIter::new(form);
+                let elems: Vec<HeapObject> = iter.by_ref().collect();
+                let form_tail = iter.tail().clone();
+                self.match_sequence(mac, &pats, &tail, elems, form_tail, env, bindings)
+            },
+            Type::Vector(ref v) => match form.object_type {
+                Type::Vector(ref f) => {
+                    let pats = v.borrow().clone();
+                    let elems = f.borrow().clone();
+                    let nil = self.interpreter.new_nil();
+                    self.match_sequence(mac, &pats, &nil, elems, nil.clone(), env, bindings)
+                },
+                _ => false,
+            },
+            Type::Nil => form.is_nil(),
+            Type::String(ref s) => matches!(form.object_type, Type::String(ref f) if *s.borrow() == *f.borrow()),
+            _ => pattern.eqv(form),
+        }
+    }
+
+    // Matches the elements of a list or vector, at most one of whose
+    // patterns is followed by an ellipsis.
+    #[allow(clippy::too_many_arguments)]
+    fn match_sequence(&mut self, mac: &Macro, pats: &[HeapObject], tail: &HeapObject,
+                      elems: Vec<HeapObject>, form_tail: HeapObject, env: &Env,
+                      bindings: &mut Bindings) -> bool {
+        let ellipsis = (1..pats.len()).find(|&i| self.is_ellipsis(mac, &pats[i]));
+        let (before, repeated, after) = match ellipsis {
+            Option::Some(i) => (&pats[..i - 1], Option::Some(&pats[i - 1]), &pats[i + 1..]),
+            Option::None => (pats, Option::None, &pats[pats.len()..]),
+        };
+        let min = before.len() + after.len();
+        if elems.len() < min || (repeated.is_none() && tail.is_nil() && elems.len() != min) {
+            return false;
+        }
+
+        for (pat, elem) in before.iter().zip(&elems) {
+            if !self.match_pattern(mac, pat, elem, env, bindings) {
+                return false;
+            }
+        }
+
+        // without an ellipsis the tail pattern matches what is left after
+        // the patterns before it
+        let rest = match repeated {
+            Option::Some(repeated) => {
+                let count = elems.len() - min;
+                let mut matches = Vec::with_capacity(count);
+                for elem in &elems[before.len()..before.len() + count] {
+                    let mut inner = HashMap::new();
+                    if !self.match_pattern(mac, repeated, elem, env, &mut inner) {
+                        return false;
+                    }
+                    matches.push(inner);
+                }
+                let mut vars = Vec::new();
+                self.pattern_vars(mac, repeated, &mut vars);
+                for var in vars {
+                    let seq = matches.iter_mut().map(|m| m.remove(&var).unwrap()).collect();
+                    bindings.insert(var, Match::Many(seq));
+                }
+                for (pat, elem) in after.iter().zip(&elems[before.len() + count..]) {
+                    if !self.match_pattern(mac, pat, elem, env, bindings) {
+                        return false;
+                    }
+                }
+                form_tail
+            },
+            Option::None => {
+                let mut rest = form_tail;
+                for elem in elems[before.len()..].iter().rev() {
+                    rest = self.interpreter.cons(elem.clone(), rest);
+                }
+                rest
+            },
+        };
+        self.match_pattern(mac, tail, &rest, env, bindings)
+    }
+
+    // The pattern variables in pattern.
+    fn pattern_vars(&mut self, mac: &Macro, pattern: &HeapObject, vars: &mut Vec<Symbol>) {
+        match pattern.object_type {
+            Type::Symbol(ref id) if !Expander::is_literal(mac, id) && !self.is_ellipsis(mac, pattern)
+                && !self.is_underscore(mac, id) => vars.push(id.clone()),
+            Type::Pair(ref p) => {
+                let (car, cdr) = (p.car.borrow().clone(), p.cdr.borrow().clone());
+                self.pattern_vars(mac, &car, vars);
+                self.pattern_vars(mac, &cdr, vars);
+            },
+            Type::Vector(ref v) => {
+                for elem in v.borrow().clone() {
+                    self.pattern_vars(mac, &elem, vars);
+                }
+            },
+            _ => {},
+        }
+    }
+
+    // Instantiates template with bindings. The identifiers it inserts are
+    // renamed to aliases, the same one for each occurrence of an identifier.
+    fn transcribe(&mut self, mac: &Rc<Macro>, template: &HeapObject, bindings: &Bindings,
+                  renames: &mut HashMap<Symbol, Symbol>, form: &HeapObject, ellipses: bool)
+                  -> Result<HeapObject, Err> {
+        match template.object_type {
+            Type::Symbol(ref id) => match bindings.get(id) {
+                Option::Some(Match::One(obj)) => Result::Ok(obj.clone()),
+                Option::Some(Match::Many(_)) => Result::Err(self.bad_syntax("syntax-rules", form)),
+                Option::None => {
+                    let alias = match renames.get(id) {
+                        Option::Some(alias) => alias.clone(),
+                        Option::None => {
+                            let alias = Symbol::uninterned(id.name());
+                            self.syntax().aliases.insert(alias.clone(), Alias{name: id.clone(), env: mac.env.clone()});
+                            renames.insert(id.clone(), alias.clone());
+                            alias
+                        },
+                    };
+                    Result::Ok(self.symbol(alias, form))
+                },
+            },
+            Type::Pair(_) => {
+                let mut iter = ListIter::new(template);
+                let elems: Vec<HeapObject> = iter.by_ref().collect();
+                let tail = iter.tail().clone();
+                // (... template) inserts template with ... as an ordinary
+                // identifier
+                if ellipses && elems.len() == 2 && tail.is_nil() && self.is_ellipsis(mac, &elems[0]) {
+                    return self.transcribe(mac, &elems[1], bindings, renames, form, false);
+                }
+                let elems = self.transcribe_sequence(mac, &elems, bindings, renames, form, ellipses)?;
+                let tail = self.transcribe(mac, &tail, bindings, renames, form, ellipses)?;
+                Result::Ok(self.list(form, elems, Option::Some(tail)))
+            },
+            Type::Vector(ref v) => {
+                let elems = v.borrow().clone();
+                let elems = self.transcribe_sequence(mac, &elems, bindings, renames, form, ellipses)?;
+                Result::Ok(self.interpreter.new_object(Type::Vector(RefCell::new(elems))))
+            },
+            _ => Result::Ok(template.clone()),
+        }
+    }
+
+    fn transcribe_sequence(&mut self, mac: &Rc<Macro>, elems: &[HeapObject], bindings: &Bindings,
+                           renames: &mut HashMap<Symbol, Symbol>, form: &HeapObject, ellipses: bool)
+                           -> Result<Vec<HeapObject>, Err> {
+        let mut out = Vec::with_capacity(elems.len());
+        let mut i = 0;
+        while i < elems.len() {
+            let mut depth = 0;
+            while ellipses && i + depth + 1 < elems.len() && self.is_ellipsis(mac, &elems[i + depth + 1]) {
+                depth += 1;
+            }
+            if depth == 0 {
+                out.push(self.transcribe(mac, &elems[i], bindings, renames, form, ellipses)?);
+            } else {
+                self.transcribe_repeated(mac, &elems[i], depth, bindings, renames, form, &mut out)?;
+            }
+            i += depth + 1;
+        }
+        Result::Ok(out)
+    }
+
+    // Instantiates template followed by depth ellipses once for each of the
+    // matches of the variables in it.
+    #[allow(clippy::too_many_arguments)]
+    fn transcribe_repeated(&mut self, mac: &Rc<Macro>, template: &HeapObject, depth: usize,
+                           bindings: &Bindings, renames: &mut HashMap<Symbol, Symbol>,
+                           form: &HeapObject, out: &mut Vec<HeapObject>) -> Result<(), Err> {
+        let mut vars = Vec::new();
+        self.template_vars(template, bindings, &mut vars);
+        let mut count = Option::None;
+        for var in &vars {
+            if let Option::Some(Match::Many(ref seq)) = bindings.get(var) {
+                if count.is_some_and(|n| n != seq.len()) {
+                    return Result::Err(self.bad_syntax("syntax-rules", form));
+                }
+                count = Option::Some(seq.len());
+            }
+        }
+        let count = match count {
+            Option::Some(count) => count,
+            Option::None => return Result::Err(self.bad_syntax("syntax-rules", form)),
+        };
+
+        for i in 0..count {
+            let mut inner = bindings.clone();
+            for var in &vars {
+                if let Option::Some(Match::Many(seq)) = bindings.get(var) {
+                    inner.insert(var.clone(), seq[i].clone());
+                }
+            }
+            if depth > 1 {
+                self.transcribe_repeated(mac, template, depth - 1, &inner, renames, form, out)?;
+            } else {
+                out.push(self.transcribe(mac, template, &inner, renames, form, true)?);
+            }
+        }
+        Result::Ok(())
+    }
+
+    // The pattern variables in template.
+    fn template_vars(&self, template: &HeapObject, bindings: &Bindings, vars: &mut Vec<Symbol>) {
+        match template.object_type {
+            Type::Symbol(ref id) if bindings.contains_key(id) && !vars.contains(id) => vars.push(id.clone()),
+            Type::Pair(ref p) => {
+                self.template_vars(&p.car.borrow(), bindings, vars);
+                self.template_vars(&p.cdr.borrow(), bindings, vars);
+            },
+            Type::Vector(ref v) => for elem in v.borrow().iter() {
+                self.template_vars(elem, bindings, vars);
+            },
+            _ => {},
+        }
+    }
+
+    // Quoted data with the aliases in it replaced by the identifiers they
+    // were made from. Data without aliases is returned as it is.
+    fn strip(&mut self, obj: &HeapObject) -> HeapObject {
+        if !self.has_alias(obj) {
+            return obj.clone();
+        }
+        match obj.object_type {
+            Type::Symbol(ref id) => {
+                let base = self.base(id);
+                self.symbol(base, obj)
+            },
+            Type::Pair(ref p) => {
+                let (car, cdr) = (p.car.borrow().clone(), p.cdr.borrow().clone());
+                let (car, cdr) = (self.strip(&car), self.strip(&cdr));
+                let pair = self.interpreter.cons(car, cdr);
+                self.located(pair, obj)
+            },
+            Type::Vector(ref v) => {
+                let elems: Vec<HeapObject> = v.borrow().clone();
+                let elems = elems.iter().map(|e| self.strip(e)).collect();
+                self.interpreter.new_object(Type::Vector(RefCell::new(elems)))
+            },
+            _ => obj.clone(),
+        }
+    }
+
+    // Whether obj contains aliases. Quoted data can be cyclic, templates
+    // cannot, so data that is not checked again needs no stripping.
+    fn has_alias(&mut self, obj: &HeapObject) -> bool {
+        let mut seen = HashSet::new();
+        let mut pending = vec![obj.clone()];
+        while let Option::Some(obj) = pending.pop() {
+            if !seen.insert(&**obj as *const Object) {
+                continue;
+            }
+            match obj.object_type {
+                Type::Symbol(ref id) => if self.syntax().aliases.contains_key(id) {
+                    return true;
+                },
+                _ => obj.for_each_ref(&mut |child| pending.push(child.clone())),
+            }
+        }
+        false
+    }
+
+    // The elements of the list form, or an error about the form called
+    // name.
+    fn forms(&self, name: &'static str, form: &HeapObject) -> Result<Vec<HeapObject>, Err> {
+        Object::list_to_vec(form).ok_or_else(|| self.bad_syntax(name, form))
+    }
+
+    #[inline]
+    fn located(&mut self, obj: HeapObject, from: &HeapObject) -> HeapObject {
+        self.sources.locate(&obj, from);
+        obj
+    }
+
+    fn symbol(&mut self, sym: Symbol, from: &HeapObject) -> HeapObject {
+        let obj = self.interpreter.new_object(Type::Symbol(sym));
+        self.located(obj, from)
+    }
+
+    // The core form keyword name, which always means the form.
+    fn keyword(&mut self, name: &str, from: &HeapObject) -> HeapObject {
+        let sym = self.interpreter.intern(name);
+        self.symbol(sym, from)
+    }
+
+    fn list(&mut self, from: &HeapObject, elems: Vec<HeapObject>, tail: Option<HeapObject>) -> HeapObject {
+        let mut list = match tail {
+            Option::Some(tail) => tail,
+            Option::None => self.interpreter.new_nil(),
+        };
+        for elem in elems.into_iter().rev() {
+            list = self.interpreter.cons(elem, list);
+        }
+        if list.is_nil() {
+            return list;
+        }
+        self.located(list, from)
+    }
+
+    fn cons_keyword(&mut self, from: &HeapObject, name: &str, rest: HeapObject) -> HeapObject {
+        let keyword = self.keyword(name, from.car().as_ref().unwrap_or(from));
+        let form = self.interpreter.cons(keyword, rest);
+        self.located(form, from)
+    }
+
+    // (name elems...) in place of the form from.
+    fn rebuild(&mut self, from: &HeapObject, name: &str, elems: Vec<HeapObject>) -> HeapObject {
+        let rest = self.list(from, elems, Option::None);
+        self.cons_keyword(from, name, rest)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use interpreter::Interpreter;
+    use error::{Err, ErrType};
+    use test_util::{eval_str, assert_eval};
+
+    #[test]
+    fn test_hygiene() {
+        let mut i = Interpreter::new();
+        eval_str(&mut i, "(define-syntax swap!
+                            (syntax-rules ()
+                              ((_ a b) (let ((tmp a)) (set! a b) (set! b tmp)))))").unwrap();
+        assert_eval(&mut i, "(let ((tmp 1) (other 2)) (swap! tmp other) (list tmp other))", "(2 1)");
+
+        // the if and list the template refers to are not the caller's
+        eval_str(&mut i, "(define-syntax my-if
+                            (syntax-rules () ((_ c a b) (cond (c a) (else b)))))").unwrap();
+        assert_eval(&mut i, "(let ((if list) (cond 1) (else #f)) (my-if #f 1 2))", "2");
+        eval_str(&mut i, "(define-syntax pair-up (syntax-rules () ((_ a) (list a a))))").unwrap();
+        assert_eval(&mut i, "(let ((list cons)) (pair-up 1))", "(1 1)");
+
+        eval_str(&mut i, "(define-syntax my-or
+                            (syntax-rules ()
+                              ((_) #f)
+                              ((_ e) e)
+                              ((_ e r ...) (let ((t e)) (if t t (my-or r ...))))))").unwrap();
+        assert_eval(&mut i, "(let ((t 5)) (my-or #f t))", "5");
+        assert_eval(&mut i, "(my-or #f #f)", "#f");
+        assert_eval(&mut i, "(macroexpand '(my-or))", "#f");
+        assert_eval(&mut i, "(car (cdr '#0=(a b . #0#)))", "b");
+    }
+
+    #[test]
+    fn test_patterns() {
+        let mut i = Interpreter::new();
+        eval_str(&mut i, "(define-syntax my-let*
+                            (syntax-rules ()
+                              ((_ () body ...) (let () body ...))
+                              ((_ ((n v) rest ...) body ...) (let ((n v)) (my-let* (rest ...) body ...)))))").unwrap();
+        assert_eval(&mut i, "(my-let* ((a 1) (b (+ a 1))) (list a b))", "(1 2)");
+
+        eval_str(&mut i, "(define-syntax flatten
+                            (syntax-rules () ((_ (a b ...) ...) '(a ... b ... ...))))").unwrap();
+        assert_eval(&mut i, "(flatten (1 2 3) (4) (5 6))", "(1 4 5 2 3 6)");
+
+        eval_str(&mut i, "(define-syntax for
+                            (syntax-rules (in from)
+                              ((_ x in lst) (quote (in x lst)))
+                              ((_ x from a) (quote (from x a)))))").unwrap();
+        assert_eval(&mut i, "(for y in z)", "(in y z)");
+        assert_eval(&mut i, "(for y from z)", "(from y z)");
+        // a bound in is not the literal
+        match eval_str(&mut i, "(let ((in 1)) (for y in z))") {
+            Result::Err(Err{err_type: ErrType::NoMatchingRule(ref name), ..}) => assert_eq!(name.name(), "for"),
+            res => panic!("matched a shadowed literal: {:?}", res.map(|o| o.to_string())),
+        }
+
+        eval_str(&mut i, "(define-syntax tail (syntax-rules () ((_ #(x y ...)) '(y ... x)) ((_ a . b) 'b)))").unwrap();
+        assert_eval(&mut i, "(tail 1 2 3)", "(2 3)");
+        assert_eval(&mut i, "(tail #(1 2 3))", "(2 3 1)");
+
+        eval_str(&mut i, "(define-syntax ends
+                            (syntax-rules ::: () ((_ a b ::: c) '(a c (b :::) ...))))").unwrap();
+        assert_eval(&mut i, "(ends 1 2 3 4)", "(1 4 (2 3) ...)");
+        eval_str(&mut i, "(define-syntax dots (syntax-rules () ((_ a) '(a (... ...)))))").unwrap();
+        assert_eval(&mut i, "(dots 1)", "(1 ...)");
+    }
+
+    #[test]
+    fn test_local_macros() {
+        let mut i = Interpreter::new();
+        assert_eval(&mut i, "(let ((x 'outer))
+                               (let-syntax ((m (syntax-rules () ((_) x))))
+                                 (let ((x 'inner)) (m))))", "outer");
+        assert_eval(&mut i, "(letrec-syntax ((ev? (syntax-rules () ((_) #t) ((_ x . r) (od? . r))))
+                                             (od? (syntax-rules () ((_) #f) ((_ x . r) (ev? . r)))))
+                               (ev? 1 2 3 4))", "#t");
+        assert_eval(&mut i, "((lambda ()
+                                (define-syntax twice (syntax-rules () ((_ e) (begin e e))))
+                                (define n 0)
+                                (twice (set! n (+ n 1)))
+                                n))", "2");
+
+        // a macro defining macros and variables
+        eval_str(&mut i, "(define-syntax def-getter
+                            (syntax-rules ()
+                              ((_ name val) (begin (define tmp val)
+                                                   (define-syntax name (syntax-rules () ((_) tmp)))))))").unwrap();
+        eval_str(&mut i, "(def-getter get-five 5)").unwrap();
+        assert_eval(&mut i, "(let ((tmp 0)) (get-five))", "5");
+    }
+
+    #[test]
+    fn test_expansion_errors() {
+        let mut i = Interpreter::new();
+        eval_str(&mut i, "(define-syntax one (syntax-rules () ((_ a) a)))").unwrap();
+        assert!(matches!(eval_str(&mut i, "(one 1 2)"),
+                         Result::Err(Err{err_type: ErrType::NoMatchingRule(_), ..})));
+        assert!(matches!(eval_str(&mut i, "(list one)"),
+                         Result::Err(Err{err_type: ErrType::KeywordAsVariable(_), ..})));
+        eval_str(&mut i, "(define-syntax forever (syntax-rules () ((_ x) (list (forever x)))))").unwrap();
+        assert!(matches!(eval_str(&mut i, "(forever 1)"),
+                         Result::Err(Err{err_type: ErrType::ExpansionTooDeep, ..})));
+        assert!(eval_str(&mut i, "(define-syntax bad (syntax-rules (1) ((_) 1)))").is_err());
+        for src in ["`(a ,(car '(b)))", "(list ,@x)", "(lambda (x) ,x)"] {
+            match eval_str(&mut i, src) {
+                Result::Err(Err{err_type: ErrType::Unsupported(_), ..}) => {},
+                _ => panic!("{}", src),
+            }
+        }
+        assert_eval(&mut i, "'`(a ,b)", "(quasiquote (a (unquote b)))");
+    }
+}
@@ -0,0 +1,324 @@
+// A source formatter. Code is laid out the way the pretty-printer lays out
+// data, but comments and the text of atoms are kept as written.
+
+use std::option::Option;
+use std::result::Result;
+use std::string::String;
+use parse::{Lexer, TokenKind, ReadError, Span, MAX_DEPTH, shebang_len};
+use printer::header_forms;
+
+enum Node {
+    Symbol(String),
+    // any other atom, the . of a dotted list and directives like
+    // #!fold-case
+    Atom(String),
+    // ; and #| |# comments
+    Comment(String),
+    // (, #( or #u8( and what is up to the )
+    List(&'static str, Vec<Item>),
+    // ' ` , ,@ #; and #n= before a node
+    Prefixed(String, Box<Node>),
+}
+
+struct Item {
+    node: Node,
+    // line breaks before it in the source
+    newlines: usize,
+}
+
+impl Node {
+    fn is_line_comment(&self) -> bool {
+        matches!(*self, Node::Comment(ref text) if text.starts_with(';'))
+    }
+
+    // Appends node on one line to out, false if it can't go on one line.
+    fn flat(&self, out: &mut String) -> bool {
+        match *self {
+            Node::Symbol(ref text) | Node::Atom(ref text) | Node::Comment(ref text) => {
+                if self.is_line_comment() || text.contains('\n') {
+                    return false;
+                }
+                out.push_str(text);
+                true
+            },
+            Node::List(open, ref items) => {
+                out.push_str(open);
+                for (i, item) in items.iter().enumerate() {
+                    if i > 0 {
+                        out.push(' ');
+                    }
+                    if !item.node.flat(out) {
+                        return false;
+                    }
+                }
+                out.push(')');
+                true
+            },
+            Node::Prefixed(ref prefix, ref node) => {
+                out.push_str(prefix);
+                node.flat(out)
+            },
+        }
+    }
+}
+
+enum Frame {
+    List{open: &'static str, span: Span, newlines: usize, items: Vec<Item>},
+    Prefix{prefix: String, span: Span, newlines: usize},
+}
+
+// Adds a finished node to the innermost open list, or to top if there is
+// none, applying the prefixes in between.
+fn add(mut node: Node, mut newlines: usize, open: &mut Vec<Frame>, top: &mut Vec<Item>) {
+    loop {
+        match open.last_mut() {
+            Option::Some(&mut Frame::List{ref mut items, ..}) => return items.push(Item{node, newlines}),
+            Option::Some(&mut Frame::Prefix{..}) => {},
+            Option::None => return top.push(Item{node, newlines}),
+        }
+        if let Option::Some(Frame::Prefix{prefix, newlines: n, ..}) = open.pop() {
+            node = Node::Prefixed(prefix, Box::new(node));
+            newlines = n;
+        }
+    }
+}
+
+fn parse(src: &str) -> Result<Vec<Item>, ReadError> {
+    let mut lexer = Lexer::new(src);
+    let mut top = Vec::new();
+    let mut open: Vec<Frame> = Vec::new();
+    // where the last token ended
+    let mut end = 0;
+    while let Option::Some(token) = lexer.next_token()? {
+        let start = token.span.offset as usize;
+        let gap = &src[end..start];
+        end = lexer.span().offset as usize;
+        let text = &src[start..end];
+
+        // the lexer skips directives
+        let mut newlines = gap.matches('\n').count();
+        let directive = gap.trim();
+        if !directive.is_empty() {
+            let before = gap[..gap.find(directive).unwrap()].matches('\n').count();
+            add(Node::Atom(directive.to_string()), before, &mut open, &mut top);
+            newlines -= before;
+        }
+
+        if open.len() == MAX_DEPTH {
+            return Result::Err(ReadError::new("nesting too deep", token.span));
+        }
+        let span = token.span;
+        let node = match token.kind {
+            TokenKind::ParenOpen | TokenKind::VectorOpen | TokenKind::BytevectorOpen => {
+                let open_text = match token.kind {
+                    TokenKind::ParenOpen => "(",
+                    TokenKind::VectorOpen => "#(",
+                    _ => "#u8(",
+                };
+                open.push(Frame::List{open: open_text, span, newlines, items: Vec::new()});
+                continue;
+            },
+            TokenKind::Quote | TokenKind::Quasiquote | TokenKind::Unquote | TokenKind::UnquoteSplicing
+            | TokenKind::DatumComment | TokenKind::Label(_) => {
+                open.push(Frame::Prefix{prefix: text.to_string(), span, newlines});
+                continue;
+            },
+            TokenKind::ParenClose => match open.pop() {
+                Option::Some(Frame::List{open: open_text, newlines: n, items, ..}) => {
+                    newlines = n;
+                    Node::List(open_text, items)
+                },
+                Option::Some(Frame::Prefix{..}) => return Result::Err(ReadError::new("expected datum before )", span)),
+                Option::None => return Result::Err(ReadError::new("unexpected )", span)),
+            },
+            // a comment after a prefix goes before it
+            TokenKind::Comment(_) => {
+                let comment = Node::Comment(text.trim_end().to_string());
+                let item = Item{node: comment, newlines};
+                match open.iter_mut().rev().find(|frame| matches!(**frame, Frame::List{..})) {
+                    Option::Some(&mut Frame::List{ref mut items, ..}) => items.push(item),
+                    _ => top.push(item),
+                }
+                continue;
+            },
+            TokenKind::Symbol(_) => Node::Symbol(text.to_string()),
+            _ => Node::Atom(text.to_string()),
+        };
+        add(node, newlines, &mut open, &mut top);
+    }
+
+    match open.last() {
+        Option::Some(&Frame::List{open, span, ..}) => Result::Err(ReadError::new(match open {
+            "(" => "unclosed (",
+            "#(" => "unclosed #(",
+            _ => "unclosed #u8(",
+        }, span)),
+        Option::Some(&Frame::Prefix{span, ..}) => Result::Err(ReadError::new("expected datum after prefix", span)),
+        Option::None => Result::Ok(top),
+    }
+}
+
+struct Formatter {
+    width: usize,
+    out: String,
+}
+
+impl Formatter {
+    fn column(&self) -> usize {
+        self.out[self.out.rfind('\n').map_or(0, |i| i + 1)..].chars().count()
+    }
+
+    fn newline(&mut self, indent: usize) {
+        self.out.push('\n');
+        self.out.extend(::std::iter::repeat_n(' ', indent));
+    }
+
+    fn node(&mut self, node: &Node) {
+        let mut flat = String::new();
+        if node.flat(&mut flat) && self.column() + flat.chars().count() <= self.width {
+            self.out.push_str(&flat);
+            return;
+        }
+        match *node {
+            Node::Symbol(ref text) | Node::Atom(ref text) | Node::Comment(ref text) => self.out.push_str(text),
+            Node::Prefixed(ref prefix, ref node) => {
+                self.out.push_str(prefix);
+                self.node(node);
+            },
+            Node::List(open, ref items) => self.list(open, items),
+        }
+    }
+
+    // Special forms keep their header forms on the first line, calls line
+    // their arguments up after the operator and other lists line their
+    // elements up under the first.
+    fn list(&mut self, open: &str, items: &[Item]) {
+        let start = self.column();
+        self.out.push_str(open);
+        let keyword = match items.first() {
+            Option::Some(&Item{node: Node::Symbol(ref keyword), ..}) if open == "(" => keyword,
+            _ => {
+                self.items(items, start + open.len(), true);
+                self.out.push(')');
+                return;
+            },
+        };
+
+        self.out.push_str(keyword);
+        let mut rest = &items[1..];
+        let named = matches!(rest.first(), Option::Some(&Item{node: Node::Symbol(_), ..}));
+        // comments and the . of a dotted list don't go on the first line
+        let stop = |rest: &[Item]| match rest.first() {
+            Option::Some(&Item{node: Node::Comment(_), ..}) => true,
+            Option::Some(&Item{node: Node::Atom(ref text), ..}) => text == ".",
+            _ => rest.is_empty(),
+        };
+        let indent = match header_forms(keyword, named) {
+            Option::Some(n) => {
+                for _ in 0..n {
+                    if stop(rest) {
+                        break;
+                    }
+                    self.out.push(' ');
+                    self.node(&rest[0].node);
+                    rest = &rest[1..];
+                }
+                start + 2
+            },
+            // too far right to line arguments up after the operator
+            Option::None if stop(rest) || self.column() + 1 > start + self.width.saturating_sub(start) / 2 => start + 2,
+            Option::None => {
+                self.out.push(' ');
+                let indent = self.column();
+                self.node(&rest[0].node);
+                rest = &rest[1..];
+                indent
+            },
+        };
+        self.items(rest, indent, false);
+        self.out.push(')');
+    }
+
+    // Lays items out one to a line at indent, except that comments on the
+    // same line as what they follow stay there. fresh is whether the first
+    // item goes where the output is now.
+    fn items(&mut self, items: &[Item], indent: usize, mut fresh: bool) {
+        let mut line_comment = false;
+        let mut dot = false;
+        for item in items {
+            let trailing = item.newlines == 0 && matches!(item.node, Node::Comment(_));
+            if fresh {
+                fresh = false;
+            } else if (trailing && !line_comment) || dot {
+                self.out.push(' ');
+            } else {
+                // blank lines are kept, but only one
+                if item.newlines > 1 {
+                    self.out.push('\n');
+                }
+                self.newline(indent);
+            }
+            self.node(&item.node);
+            line_comment = item.node.is_line_comment();
+            dot = matches!(item.node, Node::Atom(ref text) if text == ".");
+        }
+        if line_comment {
+            self.newline(indent);
+        }
+    }
+}
+
+// src laid out to fit in width columns where it can, with code indented in
+// the usual Scheme style and comments kept, as is a #! line at the start.
+pub fn format(src: &str, width: usize) -> Result<String, ReadError> {
+    let shebang = &src[..shebang_len(src)];
+    // what follows starts with the line break, so errors have the right
+    // line numbers
+    let items = parse(&src[shebang.len()..])?;
+    let mut formatter = Formatter{width, out: shebang.to_string()};
+    formatter.items(&items, 0, shebang.is_empty());
+    // the line break after a last line comment
+    let mut out = formatter.out.trim_end().to_string();
+    if !out.is_empty() {
+        out.push('\n');
+    }
+    Result::Ok(out)
+}
+
+#[cfg(test)]
+mod test {
+    use super::format;
+
+    #[test]
+    fn test_format() {
+        let cases = [
+            ("(define  x\n   1)", 80, "(define x 1)\n"),
+            ("(define (f x) (if (< x 0) (- x) x))", 20,
+             "(define (f x)\n  (if (< x 0)\n      (- x)\n      x))\n"),
+            ("(define (f x) ; the absolute value\n (if (< x 0) (- x) x))", 80,
+             "(define (f x) ; the absolute value\n  (if (< x 0) (- x) x))\n"),
+            ("(let ((a 1)) ; one\n ; two\n a)", 80, "(let ((a 1)) ; one\n  ; two\n  a)\n"),
+            ("(list 1 2 ; two\n)", 80, "(list 1\n      2 ; two\n      )\n"),
+            ("#| header |#\n\n\n(define x #xFF)\n;; done", 80, "#| header |#\n\n(define x #xFF)\n;; done\n"),
+            ("'(a . b) #;(skipped) `(x ,@y)", 80, "'(a . b)\n#;(skipped)\n`(x ,@y)\n"),
+            ("#!fold-case\n(DISPLAY \"a\\x41;\")", 80, "#!fold-case\n(DISPLAY \"a\\x41;\")\n"),
+            ("(1000 2000 (3000 4000) 5000)", 14, "(1000\n 2000\n (3000 4000)\n 5000)\n"),
+            ("(aaaa . (b c))", 10, "(aaaa\n  . (b c))\n"),
+            ("", 80, ""),
+            ("#!/usr/bin/env skeem\n\n(display  1)", 80, "#!/usr/bin/env skeem\n\n(display 1)\n"),
+            ("#! skeem -q\n(a)\n", 80, "#! skeem -q\n(a)\n"),
+            ("#!/usr/bin/env skeem", 80, "#!/usr/bin/env skeem\n"),
+        ];
+        for &(src, width, expected) in cases.iter() {
+            let formatted = format(src, width).unwrap();
+            assert_eq!(formatted, expected, "{}", src);
+            assert_eq!(format(&formatted, width).unwrap(), formatted, "{}", src);
+        }
+
+        assert_eq!(format("(a (b", 80).unwrap_err().message, "unclosed (");
+        assert_eq!(format("(a))", 80).unwrap_err().message, "unexpected )");
+        assert_eq!(format("'", 80).unwrap_err().message, "expected datum after prefix");
+        let err = format("#!/usr/bin/env skeem\n(a", 80).unwrap_err();
+        assert_eq!((err.message, err.span.line), ("unclosed (", 2));
+    }
+}
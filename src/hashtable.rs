@@ -0,0 +1,585 @@
+use interpreter::{Interpreter, Step, Each};
+use types::{Object, Type, HeapObject, Procedure, PrimitiveFn};
+use error::Err;
+use std::cell::RefCell;
+use std::collections::HashMap;
+use std::collections::hash_map::DefaultHasher;
+use std::hash::{Hash, Hasher};
+use std::rc::Rc;
+use std::option::Option;
+use std::result::Result;
+
+// How a hash table decides two keys are the same.
+#[derive(Clone)]
+pub enum Equiv {
+    Eq,
+    Eqv,
+    Equal,
+    String,
+    // scheme procedures; keys all hash the same without a hash procedure
+    Custom{same: HeapObject, hash: Option<HeapObject>},
+}
+
+// Entries are kept in buckets by the hash of their key, and compared with
+// the table's equivalence within a bucket.
+pub struct HashTable {
+    pub equiv: Equiv,
+    // the table doesn't keep its keys alive: when the GC frees a key, its
+    // entry goes with it. Values are still held strongly, so a key referred
+    // to by its own value is never freed.
+    pub weak: bool,
+    entries: RefCell<HashMap<u64, Vec<(HeapObject, HeapObject)>>>,
+}
+
+impl HashTable {
+    pub fn new(equiv: Equiv, weak: bool) -> HashTable {
+        HashTable{equiv, weak, entries: RefCell::new(HashMap::new())}
+    }
+
+    pub fn len(&self) -> usize {
+        self.entries.borrow().values().map(|bucket| bucket.len()).sum()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.entries.borrow().is_empty()
+    }
+
+    pub fn entries(&self) -> Vec<(HeapObject, HeapObject)> {
+        self.entries.borrow().values().flat_map(|bucket| bucket.iter().cloned()).collect()
+    }
+
+    // Sets the value of found, the key already in the bucket for hash, or
+    // adds key if there is none.
+    fn put(&self, hash: u64, found: Option<&HeapObject>, key: HeapObject, value: HeapObject) {
+        let mut entries = self.entries.borrow_mut();
+        let bucket = entries.entry(hash).or_default();
+        if let Option::Some(found) = found {
+            if let Option::Some(entry) = bucket.iter_mut().find(|entry| Rc::ptr_eq(&entry.0, found)) {
+                entry.1 = value;
+                return;
+            }
+        }
+        bucket.push((key, value));
+    }
+
+    fn remove(&self, hash: u64, found: &HeapObject) {
+        let mut entries = self.entries.borrow_mut();
+        let empty = match entries.get_mut(&hash) {
+            Option::Some(bucket) => {
+                bucket.retain(|entry| !Rc::ptr_eq(&entry.0, found));
+                bucket.is_empty()
+            },
+            Option::None => false,
+        };
+        if empty {
+            entries.remove(&hash);
+        }
+    }
+
+    // Drops the entries whose keys live doesn't keep.
+    pub fn retain_keys(&self, live: &dyn Fn(&HeapObject) -> bool) {
+        let mut entries = self.entries.borrow_mut();
+        for bucket in entries.values_mut() {
+            bucket.retain(|entry| live(&entry.0));
+        }
+        entries.retain(|_, bucket| !bucket.is_empty());
+    }
+
+    pub fn clear(&self) {
+        self.entries.borrow_mut().clear();
+    }
+
+    pub fn for_each_ref(&self, f: &mut dyn FnMut(&HeapObject)) {
+        if let Equiv::Custom{ref same, ref hash} = self.equiv {
+            f(same);
+            if let Option::Some(ref hash) = *hash {
+                f(hash);
+            }
+        }
+        for bucket in self.entries.borrow().values() {
+            for (key, value) in bucket {
+                if !self.weak {
+                    f(key);
+                }
+                f(value);
+            }
+        }
+    }
+
+    pub fn for_each_weak_ref(&self, f: &mut dyn FnMut(&HeapObject)) {
+        if self.weak {
+            for bucket in self.entries.borrow().values() {
+                bucket.iter().for_each(|entry| f(&entry.0));
+            }
+        }
+    }
+}
+
+fn finish(value: impl Hash) -> u64 {
+    let mut hasher = DefaultHasher::new();
+    value.hash(&mut hasher);
+    hasher.finish()
+}
+
+// Consistent with eq?: by identity, except for symbols.
+pub fn hash_eq(obj: &HeapObject) -> u64 {
+    match obj.object_type {
+        Type::Symbol(ref s) => finish(s),
+        _ => finish(&***obj as *const Object),
+    }
+}
+
+// Consistent with eqv?: atoms by value, everything else by identity.
+pub fn hash_eqv(obj: &HeapObject) -> u64 {
+    match obj.object_type {
+        Type::Bool(b) => finish(b),
+        Type::Integer(n) => finish(n),
+        Type::BigInt(ref n) => finish(n),
+        Type::Rational(ref r) => finish(r),
+        Type::Float(n) => finish(n.to_bits()),
+        Type::Character(c) => finish(c),
+        Type::Nil => finish(()),
+        _ => hash_eq(obj),
+    }
+}
+
+// Consistent with equal?. Only looks at the first few elements of a
+// structure, which also keeps it finite for cyclic ones.
+pub fn hash_equal(obj: &HeapObject) -> u64 {
+    let mut hasher = DefaultHasher::new();
+    let mut budget = 16;
+    let mut pending = vec![obj.clone()];
+    while let Option::Some(obj) = pending.pop() {
+        if budget == 0 {
+            break;
+        }
+        budget -= 1;
+        match obj.object_type {
+            Type::String(ref s) => s.borrow().hash(&mut hasher),
+            Type::Bytevector(ref v) => v.borrow().hash(&mut hasher),
+            Type::Pair(ref p) => {
+                "pair".hash(&mut hasher);
+                pending.push(p.cdr.borrow().clone());
+                pending.push(p.car.borrow().clone());
+            },
+            Type::Vector(ref v) => {
+                let v = v.borrow();
+                v.len().hash(&mut hasher);
+                pending.extend(v.iter().rev().cloned());
+            },
+            _ => hash_eqv(&obj).hash(&mut hasher),
+        }
+    }
+    hasher.finish()
+}
+
+// The hash table primitives, after SRFI 69.
+impl Interpreter {
+    fn as_hash_table<'a>(&self, obj: &'a HeapObject) -> Result<&'a HashTable, Err> {
+        match obj.object_type {
+            Type::HashTable(ref t) => Result::Ok(t),
+            _ => Result::Err(self.wrong_type("hash-table", obj)),
+        }
+    }
+
+    // The equivalence a table compares keys with, from the optional
+    // equivalence and hash procedures in args. The builtin equivalences are
+    // recognized and hashed natively.
+    fn equiv(&self, args: &[HeapObject]) -> Result<Equiv, Err> {
+        let same = match args.first() {
+            Option::Some(same) => same,
+            Option::None => return Result::Ok(Equiv::Equal),
+        };
+        let builtin: [(PrimitiveFn, PrimitiveFn, Equiv); 4] = [
+            (Interpreter::eq_p, Interpreter::hash_by_identity, Equiv::Eq),
+            (Interpreter::eqv_p, Interpreter::hash_by_identity, Equiv::Eqv),
+            (Interpreter::equal_p, Interpreter::hash, Equiv::Equal),
+            (Interpreter::string_eq, Interpreter::string_hash, Equiv::String),
+        ];
+        let primitive = |obj: &HeapObject| match obj.object_type {
+            Type::Procedure(ref p) => match **p {
+                Procedure::Primitive(prim) => Option::Some(prim.1 as usize),
+                _ => Option::None,
+            },
+            _ => Option::None,
+        };
+        // a native hash replaces one that agrees with the equivalence
+        let hash = args.get(1).map(primitive);
+        for (same_fn, hash_fn, equiv) in builtin.iter() {
+            let agrees = match hash {
+                Option::Some(hash) => hash == Option::Some(*hash_fn as usize),
+                Option::None => true,
+            };
+            if primitive(same) == Option::Some(*same_fn as usize) && agrees {
+                return Result::Ok(equiv.clone());
+            }
+        }
+
+        match same.object_type {
+            Type::Procedure(_) => {},
+            _ => return Result::Err(self.wrong_type("procedure", same)),
+        }
+        Result::Ok(Equiv::Custom{same: same.clone(), hash: args.get(1).cloned()})
+    }
+
+    fn hash_key(&mut self, table: &HashTable, key: &HeapObject) -> Result<u64, Err> {
+        match table.equiv {
+            Equiv::Eq => Result::Ok(hash_eq(key)),
+            Equiv::Eqv => Result::Ok(hash_eqv(key)),
+            Equiv::Equal => Result::Ok(hash_equal(key)),
+            Equiv::String => match key.object_type {
+                Type::String(ref s) => Result::Ok(finish(&*s.borrow())),
+                _ => Result::Err(self.wrong_type("string", key)),
+            },
+            Equiv::Custom{hash: Option::None, ..} => Result::Ok(0),
+            Equiv::Custom{hash: Option::Some(ref hash), ..} => {
+                let hash = self.apply(hash.clone(), vec![key.clone()])?;
+                match hash.object_type {
+                    Type::Integer(n) => Result::Ok(n as u64),
+                    Type::BigInt(ref n) => Result::Ok(finish(n)),
+                    _ => Result::Err(self.wrong_type("exact integer", &hash)),
+                }
+            },
+        }
+    }
+
+    fn same_key(&mut self, table: &HashTable, a: &HeapObject, b: &HeapObject) -> Result<bool, Err> {
+        match table.equiv {
+            Equiv::Eq => Result::Ok(a.is_eq(b)),
+            Equiv::Eqv => Result::Ok(a.eqv(b)),
+            Equiv::Equal | Equiv::String => Result::Ok(Object::equal(a, b)),
+            Equiv::Custom{ref same, ..} => Result::Ok(self.apply(same.clone(), vec![a.clone(), b.clone()])?.is_true()),
+        }
+    }
+
+    // The hash of key, and the entry in table with the same key, if any.
+    fn find(&mut self, table: &HashTable, key: &HeapObject)
+            -> Result<(u64, Option<(HeapObject, HeapObject)>), Err> {
+        let hash = self.hash_key(table, key)?;
+        // copied, since comparing may run scheme code that changes the table
+        let bucket = table.entries.borrow().get(&hash).cloned().unwrap_or_default();
+        for (k, v) in bucket {
+            if self.same_key(table, key, &k)? {
+                return Result::Ok((hash, Option::Some((k, v))));
+            }
+        }
+        Result::Ok((hash, Option::None))
+    }
+
+    fn new_hash_table(&mut self, args: &[HeapObject], weak: bool) -> Result<HeapObject, Err> {
+        let equiv = self.equiv(args)?;
+        Result::Ok(self.new_object(Type::HashTable(Box::new(HashTable::new(equiv, weak)))))
+    }
+
+    // (make-hash-table [equivalence [hash]]), comparing keys with equal? by
+    // default
+    pub fn make_hash_table(&mut self, args: &[HeapObject]) -> Result<HeapObject, Err> {
+        self.new_hash_table(args, false)
+    }
+
+    pub fn make_weak_key_hash_table(&mut self, args: &[HeapObject]) -> Result<HeapObject, Err> {
+        self.new_hash_table(args, true)
+    }
+
+    pub fn hash_table_p(&mut self, args: &[HeapObject]) -> Result<HeapObject, Err> {
+        Result::Ok(self.new_bool(matches!(args[0].object_type, Type::HashTable(_))))
+    }
+
+    // (hash-table-ref table key [failure [success]]) calls failure with no
+    // arguments when key is missing, and success with the value otherwise.
+    pub fn hash_table_ref(&mut self, args: &[HeapObject]) -> Result<HeapObject, Err> {
+        let table = self.as_hash_table(&args[0])?;
+        match self.find(table, &args[1])? {
+            (_, Option::Some((_, value))) => match args.get(3) {
+                Option::Some(success) => self.apply(success.clone(), vec![value]),
+                Option::None => Result::Ok(value),
+            },
+            (_, Option::None) => match args.get(2) {
+                Option::Some(failure) => self.apply(failure.clone(), Vec::new()),
+                Option::None => Result::Err(self.key_not_found(&args[1])),
+            },
+        }
+    }
+
+    pub fn hash_table_ref_default(&mut self, args: &[HeapObject]) -> Result<HeapObject, Err> {
+        let table = self.as_hash_table(&args[0])?;
+        match self.find(table, &args[1])? {
+            (_, Option::Some((_, value))) => Result::Ok(value),
+            (_, Option::None) => Result::Ok(args[2].clone()),
+        }
+    }
+
+    pub fn hash_table_set(&mut self, args: &[HeapObject]) -> Result<HeapObject, Err> {
+        let table = self.as_hash_table(&args[0])?;
+        let (hash, found) = self.find(table, &args[1])?;
+        table.put(hash, found.as_ref().map(|entry| &entry.0), args[1].clone(), args[2].clone());
+        Result::Ok(self.new_nil())
+    }
+
+    pub fn hash_table_delete(&mut self, args: &[HeapObject]) -> Result<HeapObject, Err> {
+        let table = self.as_hash_table(&args[0])?;
+        if let (hash, Option::Some((key, _))) = self.find(table, &args[1])? {
+            table.remove(hash, &key);
+        }
+        Result::Ok(self.new_nil())
+    }
+
+    pub fn hash_table_contains(&mut self, args: &[HeapObject]) -> Result<HeapObject, Err> {
+        let table = self.as_hash_table(&args[0])?;
+        let found = self.find(table, &args[1])?.1.is_some();
+        Result::Ok(self.new_bool(found))
+    }
+
+    // (hash-table-update! table key f [failure]) sets key to f applied to
+    // its value, or to the result of failure if it is missing.
+    pub fn hash_table_update(&mut self, args: &[HeapObject]) -> Result<HeapObject, Err> {
+        let table = self.as_hash_table(&args[0])?;
+        let (hash, found) = self.find(table, &args[1])?;
+        let (key, value) = match found {
+            Option::Some((key, value)) => (Option::Some(key), value),
+            Option::None => match args.get(3) {
+                Option::Some(failure) => (Option::None, self.apply(failure.clone(), Vec::new())?),
+                Option::None => return Result::Err(self.key_not_found(&args[1])),
+            },
+        };
+        let value = self.apply(args[2].clone(), vec![value])?;
+        table.put(hash, key.as_ref(), args[1].clone(), value);
+        Result::Ok(self.new_nil())
+    }
+
+    // (hash-table-update!/default table key f default)
+    pub fn hash_table_update_default(&mut self, args: &[HeapObject]) -> Result<HeapObject, Err> {
+        let table = self.as_hash_table(&args[0])?;
+        let (hash, found) = self.find(table, &args[1])?;
+        let (key, value) = match found {
+            Option::Some((key, value)) => (Option::Some(key), value),
+            Option::None => (Option::None, args[3].clone()),
+        };
+        let value = self.apply(args[2].clone(), vec![value])?;
+        table.put(hash, key.as_ref(), args[1].clone(), value);
+        Result::Ok(self.new_nil())
+    }
+
+    pub fn hash_table_size(&mut self, args: &[HeapObject]) -> Result<HeapObject, Err> {
+        let len = self.as_hash_table(&args[0])?.len();
+        Result::Ok(self.new_object(Type::Integer(len as i64)))
+    }
+
+    pub fn hash_table_keys(&mut self, args: &[HeapObject]) -> Result<HeapObject, Err> {
+        let keys = self.as_hash_table(&args[0])?.entries().into_iter().map(|entry| entry.0).collect();
+        Result::Ok(self.vec_to_list(keys))
+    }
+
+    pub fn hash_table_values(&mut self, args: &[HeapObject]) -> Result<HeapObject, Err> {
+        let values = self.as_hash_table(&args[0])?.entries().into_iter().map(|entry| entry.1).collect();
+        Result::Ok(self.vec_to_list(values))
+    }
+
+    // The entries of table as a vector of pairs, which stays as it is if
+    // the table changes while they are walked.
+    fn entry_vector(&mut self, table: &HeapObject) -> Result<HeapObject, Err> {
+        let entries = self.as_hash_table(table)?.entries();
+        let pairs = entries.into_iter().map(|(key, value)| self.cons(key, value)).collect();
+        Result::Ok(self.new_vector(pairs))
+    }
+
+    // (hash-table-walk table f) calls f with each key and its value.
+    pub fn hash_table_walk(&mut self, args: Vec<HeapObject>) -> Result<Step, Err> {
+        let entries = self.entry_vector(&args[0])?;
+        self.each(Each::HashTableWalk, args[1].clone(), vec![entries], 0, Vec::new())
+    }
+
+    // (hash-table-fold table f init) calls f with each key, its value and
+    // the result so far.
+    pub fn hash_table_fold(&mut self, args: Vec<HeapObject>) -> Result<Step, Err> {
+        let entries = self.entry_vector(&args[0])?;
+        self.each(Each::HashTableFold, args[1].clone(), vec![entries], 0, vec![args[2].clone()])
+    }
+
+    pub fn hash_table_to_alist(&mut self, args: &[HeapObject]) -> Result<HeapObject, Err> {
+        let entries = self.as_hash_table(&args[0])?.entries();
+        let pairs = entries.into_iter().map(|(key, value)| self.cons(key, value)).collect();
+        Result::Ok(self.vec_to_list(pairs))
+    }
+
+    // (alist->hash-table alist [equivalence [hash]]), where earlier entries
+    // win over later ones with the same key
+    pub fn alist_to_hash_table(&mut self, args: &[HeapObject]) -> Result<HeapObject, Err> {
+        let alist = match Object::list_to_vec(&args[0]) {
+            Option::Some(alist) => alist,
+            Option::None => return Result::Err(self.wrong_type("list", &args[0])),
+        };
+        let obj = self.new_hash_table(&args[1..], false)?;
+        let table = self.as_hash_table(&obj)?;
+        for entry in alist {
+            let (key, value) = match (entry.car(), entry.cdr()) {
+                (Option::Some(key), Option::Some(value)) => (key, value),
+                _ => return Result::Err(self.wrong_type("pair", &entry)),
+            };
+            if let (hash, Option::None) = self.find(table, &key)? {
+                table.put(hash, Option::None, key, value);
+            }
+        }
+        Result::Ok(obj)
+    }
+
+    pub fn hash_table_copy(&mut self, args: &[HeapObject]) -> Result<HeapObject, Err> {
+        let table = self.as_hash_table(&args[0])?;
+        let copy = HashTable::new(table.equiv.clone(), table.weak);
+        *copy.entries.borrow_mut() = table.entries.borrow().clone();
+        Result::Ok(self.new_object(Type::HashTable(Box::new(copy))))
+    }
+
+    pub fn hash_table_clear(&mut self, args: &[HeapObject]) -> Result<HeapObject, Err> {
+        self.as_hash_table(&args[0])?.clear();
+        Result::Ok(self.new_nil())
+    }
+
+    // A non-negative fixnum from hash, below the optional bound in args[1].
+    fn hash_result(&mut self, hash: u64, args: &[HeapObject]) -> Result<HeapObject, Err> {
+        let mut n = (hash >> 1) as i64;
+        if let Option::Some(bound) = args.get(1) {
+            match bound.object_type {
+                Type::Integer(b) if b > 0 => n %= b,
+                _ => return Result::Err(self.wrong_type("positive integer", bound)),
+            }
+        }
+        Result::Ok(self.new_object(Type::Integer(n)))
+    }
+
+    // (hash obj [bound]), consistent with equal?
+    pub fn hash(&mut self, args: &[HeapObject]) -> Result<HeapObject, Err> {
+        self.hash_result(hash_equal(&args[0]), args)
+    }
+
+    pub fn hash_by_identity(&mut self, args: &[HeapObject]) -> Result<HeapObject, Err> {
+        self.hash_result(hash_eqv(&args[0]), args)
+    }
+
+    pub fn string_hash(&mut self, args: &[HeapObject]) -> Result<HeapObject, Err> {
+        let hash = match args[0].object_type {
+            Type::String(ref s) => finish(&*s.borrow()),
+            _ => return Result::Err(self.wrong_type("string", &args[0])),
+        };
+        self.hash_result(hash, args)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use interpreter::Interpreter;
+    use error::ErrType;
+    use test_util::{eval, eval_str, assert_eval};
+
+    #[test]
+    fn test_equivalence() {
+        let mut i = Interpreter::new();
+        let cases = [
+            ("(eqv? 2 2)", "#t"),
+            ("(eqv? 100000000000000000000 100000000000000000000)", "#t"),
+            ("(eqv? 1/2 (/ 2 4))", "#t"),
+            ("(eqv? 2 2.0)", "#f"),
+            ("(eqv? 0.0 -0.0)", "#f"),
+            ("(eqv? (quote a) (quote a))", "#t"),
+            ("(eqv? (quote ()) (quote ()))", "#t"),
+            ("(eqv? (cons 1 2) (cons 1 2))", "#f"),
+            ("(eqv? \"\" \"\")", "#f"),
+            ("(let ((p (lambda (x) x))) (eqv? p p))", "#t"),
+            ("(equal? (quote a) (quote a))", "#t"),
+            ("(equal? (quote (a)) (quote (a)))", "#t"),
+            ("(equal? (quote (a (b) c)) (quote (a (b) c)))", "#t"),
+            ("(equal? (quote (a (b) c)) (quote (a (b) d)))", "#f"),
+            ("(equal? \"abc\" \"abc\")", "#t"),
+            ("(equal? 2 2)", "#t"),
+            ("(equal? 2 2.0)", "#f"),
+            ("(equal? (make-vector 5 (quote a)) (make-vector 5 (quote a)))", "#t"),
+            ("(equal? #(1 2) #(1 2 3))", "#f"),
+            ("(equal? #u8(1 2) (bytevector 1 2))", "#t"),
+            ("(let ((a (list 1 2)) (b (list 1 2))) (set-cdr! (cdr a) a) (set-cdr! (cdr b) b) (equal? a b))",
+             "#t"),
+            ("(let ((a (list 1 2)) (b (list 1 3))) (set-cdr! (cdr a) a) (set-cdr! (cdr b) b) (equal? a b))",
+             "#f"),
+        ];
+        for &(src, expected) in cases.iter() {
+            assert_eval(&mut i, src, expected);
+        }
+    }
+
+    #[test]
+    fn test_hash_tables() {
+        let mut i = Interpreter::new();
+        eval(&mut i, "(define t (make-hash-table))");
+        eval(&mut i, "(hash-table-set! t (list 1 2) (quote a))");
+        eval(&mut i, "(hash-table-set! t \"b\" 2)");
+        eval(&mut i, "(hash-table-set! t (list 1 2) (quote c))");
+        let cases = [
+            ("(hash-table-ref t (list 1 2))", "c"),
+            ("(hash-table-ref t \"b\" (lambda () 0) (lambda (x) (+ x 1)))", "3"),
+            ("(hash-table-ref t 5 (lambda () (quote none)))", "none"),
+            ("(hash-table-ref/default t \"c\" 0)", "0"),
+            ("(hash-table-size t)", "2"),
+            ("(hash-table-contains? t \"b\")", "#t"),
+            ("(begin (hash-table-update! t \"b\" (lambda (x) (* x 10))) (hash-table-ref t \"b\"))", "20"),
+            ("(begin (hash-table-update!/default t 7 (lambda (x) (+ x 1)) 0) (hash-table-ref t 7))", "1"),
+            ("(begin (hash-table-update! t 8 (lambda (x) x) (lambda () 8)) (hash-table-ref t 8))", "8"),
+            ("(begin (hash-table-delete! t (list 1 2)) (hash-table-contains? t (list 1 2)))", "#f"),
+            ("(hash-table-fold t (lambda (k v acc) (+ v acc)) 0)", "29"),
+            ("(let ((n 0)) (hash-table-walk t (lambda (k v) (set! n (+ n 1)))) n)", "3"),
+            ("(hash-table-size (hash-table-copy t))", "3"),
+            ("(begin (hash-table-clear! t) (hash-table->alist t))", "()"),
+            ("(hash-table->alist (alist->hash-table (quote ((a . 1) (a . 2)))))", "((a . 1))"),
+            ("(hash-table-keys (alist->hash-table (quote ((a . 1))) eq?))", "(a)"),
+            ("(hash-table-values (alist->hash-table (quote ((a . 1))) eqv?))", "(1)"),
+            ("(let ((t (make-hash-table eq?))) (hash-table-set! t (list 1) 1) (hash-table-ref/default t (list 1) 0))",
+             "0"),
+            ("(let ((t (make-hash-table string=? string-hash))) (hash-table-set! t \"x\" 1) (hash-table-ref t \"x\"))",
+             "1"),
+            ("(let ((t (make-hash-table (lambda (a b) (= (modulo a 10) (modulo b 10))) (lambda (n) (modulo n 10)))))
+                (hash-table-set! t 3 (quote a)) (hash-table-set! t 13 (quote b)) (hash-table->alist t))",
+             "((3 . b))"),
+            ("(let ((t (make-hash-table (lambda (a b) (= a b))))) (hash-table-set! t 1 2) (hash-table-ref t 1.0))",
+             "2"),
+            ("(= (hash (list 1 \"a\")) (hash (list 1 \"a\")))", "#t"),
+            ("(< (string-hash \"abc\" 10) 10)", "#t"),
+            ("(hash-table? t)", "#t"),
+        ];
+        for &(src, expected) in cases.iter() {
+            assert_eval(&mut i, src, expected);
+        }
+
+        assert!(matches!(eval_str(&mut i, "(hash-table-ref t 1)").map_err(|e| e.err_type),
+                         Result::Err(ErrType::KeyNotFound(_))));
+    }
+
+    #[test]
+    fn test_weak_keys() {
+        let mut i = Interpreter::new();
+        eval(&mut i, "(define t (make-weak-key-hash-table eq?))");
+        eval(&mut i, "(define kept (list 1))");
+        eval(&mut i, "(hash-table-set! t kept 1)");
+        eval(&mut i, "(hash-table-set! t (list 2) 2)");
+        i.gc();
+        assert_eval(&mut i, "(hash-table->alist t)", "(((1) . 1))");
+
+        // a strong table keeps its keys
+        eval(&mut i, "(define s (make-hash-table eq?))");
+        eval(&mut i, "(hash-table-set! s (list 2) 2)");
+        i.gc();
+        assert_eval(&mut i, "(hash-table-size s)", "1");
+    }
+
+    #[test]
+    fn test_gc_grown_tables() {
+        // tables that grew after they were made are freed by the GC
+        let mut i = Interpreter::new();
+        eval(&mut i, "(define n 0)");
+        eval(&mut i, "(while (< n 500)
+                        (let ((t (make-hash-table)))
+                          (hash-table-set! t 1 1)
+                          (hash-table-set! t 2 2)
+                          (hash-table-set! t 3 3))
+                        (set! n (+ n 1)))");
+        i.gc();
+        assert_eval(&mut i, "n", "500");
+    }
+}
@@ -1,35 +1,547 @@
-use types::{Object, Type, HeapObject, Lambda, Procedure, List, new_list};
-use error::{Err, ErrType};
-use environment::Environment;
+use types::{Object, Type, HeapObject, Lambda, Procedure, PrimitiveFn, ControlFn, Builtin, Pair, Frame,
+            ListIter, ErrorObject, ErrorKind};
+use parse::Reader;
+use error::{Err, ErrType, Location, Source};
+use environment::{Environment, Global};
+use expand::{Expander, Syntax, CORE_FORMS};
+use compile::{Compiler, Code, Node, LetCode, Clause, ClauseBody, CaseClause};
 use std::option::Option;
 use std::result::Result;
 use std::rc::Rc;
+use std::cell::RefCell;
+use std::mem::size_of;
+use symbol::{Symbol, Interner};
+use number::{self, Number};
+use std::cmp::Ordering;
+use std::collections::HashMap;
+use std::borrow::Cow;
+use std::path::PathBuf;
+use std::sync::Arc;
+use std::sync::atomic::{self, AtomicBool};
+
+// Builtins bound in the root frame by Interpreter::new, keyed by their
+// Scheme name, with the number of arguments they take, which is checked
+// before they are called. Primitives receive their arguments evaluated.
+const PRIMITIVES: &[Builtin<PrimitiveFn>] = &[
+    ("print", Interpreter::print, 1, Option::None),
+    ("write", Interpreter::write, 1, Option::Some(1)),
+    ("write-shared", Interpreter::write_shared, 1, Option::Some(1)),
+    ("write-simple", Interpreter::write_simple, 1, Option::Some(1)),
+    ("display", Interpreter::display, 1, Option::Some(1)),
+    ("write-string", Interpreter::write_string, 1, Option::Some(1)),
+    ("write-char", Interpreter::write_char, 1, Option::Some(1)),
+    ("newline", Interpreter::newline, 0, Option::Some(0)),
+    ("pretty-print", Interpreter::pretty_print, 1, Option::Some(2)),
+    ("+", Interpreter::add, 0, Option::None),
+    ("-", Interpreter::sub, 1, Option::None),
+    ("*", Interpreter::mul, 0, Option::None),
+    ("/", Interpreter::div, 1, Option::None),
+    ("=", Interpreter::num_eq, 1, Option::None),
+    ("<", Interpreter::num_lt, 1, Option::None),
+    (">", Interpreter::num_gt, 1, Option::None),
+    ("<=", Interpreter::num_le, 1, Option::None),
+    (">=", Interpreter::num_ge, 1, Option::None),
+    ("quotient", Interpreter::quotient, 2, Option::Some(2)),
+    ("remainder", Interpreter::remainder, 2, Option::Some(2)),
+    ("modulo", Interpreter::modulo, 2, Option::Some(2)),
+    ("abs", Interpreter::abs, 1, Option::Some(1)),
+    ("min", Interpreter::min, 1, Option::None),
+    ("max", Interpreter::max, 1, Option::None),
+    ("gcd", Interpreter::gcd, 0, Option::None),
+    ("lcm", Interpreter::lcm, 0, Option::None),
+    ("floor", Interpreter::floor, 1, Option::Some(1)),
+    ("ceiling", Interpreter::ceiling, 1, Option::Some(1)),
+    ("round", Interpreter::round, 1, Option::Some(1)),
+    ("truncate", Interpreter::truncate, 1, Option::Some(1)),
+    ("exp", Interpreter::exp, 1, Option::Some(1)),
+    ("log", Interpreter::log, 1, Option::Some(2)),
+    ("sin", Interpreter::sin, 1, Option::Some(1)),
+    ("cos", Interpreter::cos, 1, Option::Some(1)),
+    ("tan", Interpreter::tan, 1, Option::Some(1)),
+    ("asin", Interpreter::asin, 1, Option::Some(1)),
+    ("acos", Interpreter::acos, 1, Option::Some(1)),
+    ("atan", Interpreter::atan, 1, Option::Some(2)),
+    ("sqrt", Interpreter::sqrt, 1, Option::Some(1)),
+    ("exact-integer-sqrt", Interpreter::exact_integer_sqrt, 1, Option::Some(1)),
+    ("expt", Interpreter::expt, 2, Option::Some(2)),
+    ("number->string", Interpreter::number_to_string, 1, Option::Some(2)),
+    ("string->number", Interpreter::string_to_number, 1, Option::Some(2)),
+    ("number?", Interpreter::number_p, 1, Option::Some(1)),
+    ("integer?", Interpreter::integer_p, 1, Option::Some(1)),
+    ("zero?", Interpreter::zero_p, 1, Option::Some(1)),
+    ("positive?", Interpreter::positive_p, 1, Option::Some(1)),
+    ("negative?", Interpreter::negative_p, 1, Option::Some(1)),
+    ("odd?", Interpreter::odd_p, 1, Option::Some(1)),
+    ("even?", Interpreter::even_p, 1, Option::Some(1)),
+    ("exact?", Interpreter::exact_p, 1, Option::Some(1)),
+    ("inexact?", Interpreter::inexact_p, 1, Option::Some(1)),
+    ("exact", Interpreter::exact, 1, Option::Some(1)),
+    ("inexact", Interpreter::inexact, 1, Option::Some(1)),
+    ("inexact->exact", Interpreter::exact, 1, Option::Some(1)),
+    ("exact->inexact", Interpreter::inexact, 1, Option::Some(1)),
+    ("refcount", Interpreter::refcount, 1, Option::Some(1)),
+    ("eval", Interpreter::eval_pub, 1, Option::Some(1)),
+    ("cons", Interpreter::cons_pub, 2, Option::Some(2)),
+    ("car", Interpreter::car, 1, Option::Some(1)),
+    ("cdr", Interpreter::cdr, 1, Option::Some(1)),
+    ("set-car!", Interpreter::set_car, 2, Option::Some(2)),
+    ("set-cdr!", Interpreter::set_cdr, 2, Option::Some(2)),
+    ("list", Interpreter::list, 0, Option::None),
+    ("pair?", Interpreter::pair_p, 1, Option::Some(1)),
+    ("null?", Interpreter::null_p, 1, Option::Some(1)),
+    ("list?", Interpreter::list_p, 1, Option::Some(1)),
+    ("eq?", Interpreter::eq_p, 2, Option::Some(2)),
+    ("eqv?", Interpreter::eqv_p, 2, Option::Some(2)),
+    ("equal?", Interpreter::equal_p, 2, Option::Some(2)),
+    ("symbol?", Interpreter::symbol_p, 1, Option::Some(1)),
+    ("string->symbol", Interpreter::string_to_symbol, 1, Option::Some(1)),
+    ("symbol->string", Interpreter::symbol_to_string, 1, Option::Some(1)),
+    ("gensym", Interpreter::gensym, 0, Option::Some(1)),
+    ("symbol-interned?", Interpreter::symbol_interned_p, 1, Option::Some(1)),
+    ("macroexpand", Interpreter::macroexpand, 1, Option::Some(1)),
+    ("vector?", Interpreter::vector_p, 1, Option::Some(1)),
+    ("make-vector", Interpreter::make_vector, 1, Option::Some(2)),
+    ("vector", Interpreter::vector, 0, Option::None),
+    ("vector-length", Interpreter::vector_length, 1, Option::Some(1)),
+    ("vector-ref", Interpreter::vector_ref, 2, Option::Some(2)),
+    ("vector-set!", Interpreter::vector_set, 3, Option::Some(3)),
+    ("vector->list", Interpreter::vector_to_list, 1, Option::Some(3)),
+    ("list->vector", Interpreter::list_to_vector, 1, Option::Some(1)),
+    ("vector-fill!", Interpreter::vector_fill, 2, Option::Some(4)),
+    ("vector-copy", Interpreter::vector_copy, 1, Option::Some(3)),
+    ("vector-copy!", Interpreter::vector_copy_to, 3, Option::Some(5)),
+    ("vector-append", Interpreter::vector_append, 0, Option::None),
+    ("bytevector?", Interpreter::bytevector_p, 1, Option::Some(1)),
+    ("make-bytevector", Interpreter::make_bytevector, 1, Option::Some(2)),
+    ("bytevector", Interpreter::bytevector, 0, Option::None),
+    ("bytevector-length", Interpreter::bytevector_length, 1, Option::Some(1)),
+    ("bytevector-u8-ref", Interpreter::bytevector_u8_ref, 2, Option::Some(2)),
+    ("bytevector-u8-set!", Interpreter::bytevector_u8_set, 3, Option::Some(3)),
+    ("bytevector-copy", Interpreter::bytevector_copy, 1, Option::Some(3)),
+    ("bytevector-copy!", Interpreter::bytevector_copy_to, 3, Option::Some(5)),
+    ("bytevector-append", Interpreter::bytevector_append, 0, Option::None),
+    ("string?", Interpreter::string_p, 1, Option::Some(1)),
+    ("make-string", Interpreter::make_string, 1, Option::Some(2)),
+    ("string", Interpreter::string, 0, Option::None),
+    ("string-length", Interpreter::string_length, 1, Option::Some(1)),
+    ("string-ref", Interpreter::string_ref, 2, Option::Some(2)),
+    ("string-set!", Interpreter::string_set, 3, Option::Some(3)),
+    ("substring", Interpreter::substring, 2, Option::Some(3)),
+    ("string-append", Interpreter::string_append, 0, Option::None),
+    ("string-copy", Interpreter::string_copy, 1, Option::Some(3)),
+    ("string-copy!", Interpreter::string_copy_to, 3, Option::Some(5)),
+    ("string-fill!", Interpreter::string_fill, 2, Option::Some(4)),
+    ("string->list", Interpreter::string_to_list, 1, Option::Some(3)),
+    ("list->string", Interpreter::list_to_string, 1, Option::Some(1)),
+    ("string-upcase", Interpreter::string_upcase, 1, Option::Some(1)),
+    ("string-downcase", Interpreter::string_downcase, 1, Option::Some(1)),
+    ("string-foldcase", Interpreter::string_foldcase, 1, Option::Some(1)),
+    ("string=?", Interpreter::string_eq, 1, Option::None),
+    ("string<?", Interpreter::string_lt, 1, Option::None),
+    ("string>?", Interpreter::string_gt, 1, Option::None),
+    ("string<=?", Interpreter::string_le, 1, Option::None),
+    ("string>=?", Interpreter::string_ge, 1, Option::None),
+    ("string-ci=?", Interpreter::string_ci_eq, 1, Option::None),
+    ("string-ci<?", Interpreter::string_ci_lt, 1, Option::None),
+    ("string-ci>?", Interpreter::string_ci_gt, 1, Option::None),
+    ("string-ci<=?", Interpreter::string_ci_le, 1, Option::None),
+    ("string-ci>=?", Interpreter::string_ci_ge, 1, Option::None),
+    ("string-split", Interpreter::string_split, 1, Option::Some(2)),
+    ("string-join", Interpreter::string_join, 1, Option::Some(2)),
+    ("string-index", Interpreter::string_index, 2, Option::Some(4)),
+    ("char?", Interpreter::char_p, 1, Option::Some(1)),
+    ("char-alphabetic?", Interpreter::char_alphabetic_p, 1, Option::Some(1)),
+    ("char-numeric?", Interpreter::char_numeric_p, 1, Option::Some(1)),
+    ("char-whitespace?", Interpreter::char_whitespace_p, 1, Option::Some(1)),
+    ("char-upper-case?", Interpreter::char_upper_case_p, 1, Option::Some(1)),
+    ("char-lower-case?", Interpreter::char_lower_case_p, 1, Option::Some(1)),
+    ("char-upcase", Interpreter::char_upcase, 1, Option::Some(1)),
+    ("char-downcase", Interpreter::char_downcase, 1, Option::Some(1)),
+    ("char-foldcase", Interpreter::char_foldcase, 1, Option::Some(1)),
+    ("digit-value", Interpreter::digit_value, 1, Option::Some(1)),
+    ("char->integer", Interpreter::char_to_integer, 1, Option::Some(1)),
+    ("integer->char", Interpreter::integer_to_char, 1, Option::Some(1)),
+    ("char=?", Interpreter::char_eq, 1, Option::None),
+    ("char<?", Interpreter::char_lt, 1, Option::None),
+    ("char>?", Interpreter::char_gt, 1, Option::None),
+    ("char<=?", Interpreter::char_le, 1, Option::None),
+    ("char>=?", Interpreter::char_ge, 1, Option::None),
+    ("char-ci=?", Interpreter::char_ci_eq, 1, Option::None),
+    ("char-ci<?", Interpreter::char_ci_lt, 1, Option::None),
+    ("char-ci>?", Interpreter::char_ci_gt, 1, Option::None),
+    ("char-ci<=?", Interpreter::char_ci_le, 1, Option::None),
+    ("char-ci>=?", Interpreter::char_ci_ge, 1, Option::None),
+    ("utf8->string", Interpreter::utf8_to_string, 1, Option::Some(3)),
+    ("string->utf8", Interpreter::string_to_utf8, 1, Option::Some(3)),
+    ("make-hash-table", Interpreter::make_hash_table, 0, Option::Some(2)),
+    ("make-weak-key-hash-table", Interpreter::make_weak_key_hash_table, 0, Option::Some(2)),
+    ("hash-table?", Interpreter::hash_table_p, 1, Option::Some(1)),
+    ("hash-table-ref", Interpreter::hash_table_ref, 2, Option::Some(4)),
+    ("hash-table-ref/default", Interpreter::hash_table_ref_default, 3, Option::Some(3)),
+    ("hash-table-set!", Interpreter::hash_table_set, 3, Option::Some(3)),
+    ("hash-table-delete!", Interpreter::hash_table_delete, 2, Option::Some(2)),
+    ("hash-table-contains?", Interpreter::hash_table_contains, 2, Option::Some(2)),
+    ("hash-table-exists?", Interpreter::hash_table_contains, 2, Option::Some(2)),
+    ("hash-table-update!", Interpreter::hash_table_update, 3, Option::Some(4)),
+    ("hash-table-update!/default", Interpreter::hash_table_update_default, 4, Option::Some(4)),
+    ("hash-table-size", Interpreter::hash_table_size, 1, Option::Some(1)),
+    ("hash-table-keys", Interpreter::hash_table_keys, 1, Option::Some(1)),
+    ("hash-table-values", Interpreter::hash_table_values, 1, Option::Some(1)),
+    ("hash-table->alist", Interpreter::hash_table_to_alist, 1, Option::Some(1)),
+    ("alist->hash-table", Interpreter::alist_to_hash_table, 1, Option::Some(3)),
+    ("hash-table-copy", Interpreter::hash_table_copy, 1, Option::Some(1)),
+    ("hash-table-clear!", Interpreter::hash_table_clear, 1, Option::Some(1)),
+    ("hash", Interpreter::hash, 1, Option::Some(2)),
+    ("string-hash", Interpreter::string_hash, 1, Option::Some(2)),
+    ("hash-by-identity", Interpreter::hash_by_identity, 1, Option::Some(2)),
+    ("make-record-type", Interpreter::make_record_type, 2, Option::Some(2)),
+    ("record-constructor", Interpreter::record_constructor, 2, Option::Some(2)),
+    ("record-predicate", Interpreter::record_predicate, 1, Option::Some(1)),
+    ("record-accessor", Interpreter::record_accessor, 2, Option::Some(2)),
+    ("record-modifier", Interpreter::record_modifier, 2, Option::Some(2)),
+    ("error-object?", Interpreter::error_object_p, 1, Option::Some(1)),
+    ("error-object-message", Interpreter::error_object_message, 1, Option::Some(1)),
+    ("error-object-irritants", Interpreter::error_object_irritants, 1, Option::Some(1)),
+    ("read-error?", Interpreter::read_error_p, 1, Option::Some(1)),
+    ("file-error?", Interpreter::file_error_p, 1, Option::Some(1)),
+    ("command-line", Interpreter::command_line, 0, Option::Some(0)),
+    ("exit", Interpreter::exit, 0, Option::Some(1)),
+    ("load", Interpreter::load, 1, Option::Some(1)),
+    ("apropos", Interpreter::apropos, 1, Option::Some(1)),
+    ("describe", Interpreter::describe, 1, Option::Some(1)),
+];
+
+// Builtins that decide what is evaluated next themselves, instead of
+// returning a value.
+const CONTROL: &[Builtin<ControlFn>] = &[
+    ("call-with-current-continuation", Interpreter::call_cc, 1, Option::Some(1)),
+    ("call/cc", Interpreter::call_cc, 1, Option::Some(1)),
+    ("dynamic-wind", Interpreter::dynamic_wind, 3, Option::Some(3)),
+    ("with-exception-handler", Interpreter::with_exception_handler, 2, Option::Some(2)),
+    ("raise", Interpreter::raise_pub, 1, Option::Some(1)),
+    ("raise-continuable", Interpreter::raise_continuable, 1, Option::Some(1)),
+    ("error", Interpreter::error, 1, Option::None),
+    ("vector-map", Interpreter::vector_map, 2, Option::None),
+    ("vector-for-each", Interpreter::vector_for_each, 2, Option::None),
+    ("string-map", Interpreter::string_map, 2, Option::None),
+    ("string-for-each", Interpreter::string_for_each, 2, Option::None),
+    ("hash-table-walk", Interpreter::hash_table_walk, 2, Option::Some(2)),
+    ("hash-table-fold", Interpreter::hash_table_fold, 3, Option::Some(3)),
+];
+
+// Library syntax defined in Scheme, evaluated by Interpreter::new.
+const PRELUDE: &str = "
+(define-syntax define-record-type
+  (syntax-rules ()
+    ((_ type (constructor constructor-field ...) predicate (field accessor more ...) ...)
+     (begin
+       (define type (make-record-type 'type '(field ...)))
+       (define constructor (record-constructor type '(constructor-field ...)))
+       (define predicate (record-predicate type))
+       (define-record-field type field accessor more ...) ...))))
+
+(define-syntax define-record-field
+  (syntax-rules ()
+    ((_ type field accessor)
+     (define accessor (record-accessor type 'field)))
+    ((_ type field accessor modifier)
+     (begin
+       (define accessor (record-accessor type 'field))
+       (define modifier (record-modifier type 'field))))))
+
+; The clause tests are evaluated in the handler, so a condition no clause
+; matches is raised again from there, and the body of the clause that
+; matches outside the guard body.
+(define-syntax guard
+  (syntax-rules ()
+    ((_ (var clause ...) body1 body2 ...)
+     ((call/cc
+        (lambda (guard-k)
+          (with-exception-handler
+            (lambda (condition)
+              (let ((thunk (let ((var condition)) (guard-clauses clause ...))))
+                (if thunk
+                    (guard-k thunk)
+                    (raise-continuable condition))))
+            (lambda ()
+              (let ((result (let () body1 body2 ...)))
+                (guard-k (lambda () result)))))))))))
+
+; A thunk for the body of the first clause whose test is true, or #f.
+(define-syntax guard-clauses
+  (syntax-rules (else =>)
+    ((_) #f)
+    ((_ (else e1 e2 ...)) (lambda () e1 e2 ...))
+    ((_ (test => receiver) clause ...)
+     (let ((t test)) (if t (lambda () (receiver t)) (guard-clauses clause ...))))
+    ((_ (test) clause ...)
+     (let ((t test)) (if t (lambda () t) (guard-clauses clause ...))))
+    ((_ (test e1 e2 ...) clause ...)
+     (if test (lambda () e1 e2 ...) (guard-clauses clause ...)))))
+";
+
+// What to do with the value of the expression being evaluated. Pending work
+// is kept on Interpreter::stack instead of the Rust stack, and expressions in
+// tail position are evaluated without pushing anything. Sequences still to be
+// evaluated are kept as the compiled sequence and the index of the next
+// element. Each continuation is pushed along with the frame it was created
+// in, which is made current again before it resumes.
+#[derive(Clone)]
+enum Cont {
+    // (f a r g s): done holds the values of the operator and the operands
+    // evaluated so far
+    EvalArgs{name: Option<Symbol>, args: Rc<[Code]>, done: Vec<HeapObject>, location: Option<Location>},
+    // the forms left in a body, the last one is in tail position
+    Body(Rc<[Code]>, usize),
+    // leaving a lambda body, tail calls replace it
+    Return{traced: bool},
+    If{consequent: Code, alternate: Option<Code>},
+    DefineGlobal(Rc<Global>),
+    SetGlobal(Rc<Global>, Option<Location>),
+    SetLocal(usize, usize),
+    // vals holds the values of the inits evaluated so far
+    Let(Rc<LetCode>, Vec<HeapObject>),
+    // the index of the init being evaluated
+    Letrec(Rc<LetCode>, usize),
+    // the index of the clause whose test is being evaluated
+    Cond(Rc<[Clause]>, usize),
+    Case(Rc<[CaseClause]>),
+    ApplyTo(Vec<HeapObject>),
+    And(Rc<[Code]>, usize),
+    Or(Rc<[Code]>, usize),
+    WhileTest{test: Code, body: Rc<[Code]>, last: HeapObject},
+    WhileBody{test: Code, body: Rc<[Code]>},
+    // (dynamic-wind before thunk after) once before has returned
+    WindBefore{thunk: HeapObject, wind: HeapObject},
+    // the thunk of a dynamic-wind, parent is the winders outside it
+    WindBody{after: HeapObject, parent: HeapObject},
+    // discards the value it gets in favour of its own
+    Value(HeapObject),
+    // reinstalls the exception handlers outside a handler or a
+    // with-exception-handler thunk
+    Handlers(HeapObject),
+    // the handler of a raise of the object returned
+    HandlerReturned(HeapObject),
+    // f has been called for the index'th elements of seqs
+    Each{each: Each, f: HeapObject, seqs: Vec<HeapObject>, index: usize, results: Vec<HeapObject>},
+}
+
+// The builtins that call a procedure for each element of some sequences.
+// They run on the continuation stack rather than in a nested run, so that
+// continuations captured in the procedure can be resumed after it returns.
+#[derive(Clone, Copy)]
+pub enum Each {
+    VectorMap,
+    VectorForEach,
+    StringMap,
+    StringForEach,
+    // seqs holds a vector of the entries as pairs
+    HashTableWalk,
+    // results holds the value so far
+    HashTableFold,
+}
+
+impl Cont {
+    fn for_each_ref(&self, f: &mut dyn FnMut(&HeapObject)) {
+        match *self {
+            Cont::EvalArgs{ref done, ..} => done.iter().for_each(f),
+            Cont::Let(_, ref vals) | Cont::ApplyTo(ref vals) => vals.iter().for_each(f),
+            Cont::WhileTest{ref last, ..} | Cont::Value(ref last) | Cont::Handlers(ref last)
+            | Cont::HandlerReturned(ref last) => f(last),
+            Cont::WindBefore{ref thunk, ref wind} => {
+                f(thunk);
+                f(wind);
+            },
+            Cont::WindBody{ref after, ref parent} => {
+                f(after);
+                f(parent);
+            },
+            Cont::Each{f: ref proc, ref seqs, ref results, ..} => {
+                f(proc);
+                seqs.iter().for_each(&mut *f);
+                results.iter().for_each(f);
+            },
+            _ => {},
+        }
+    }
+}
+
+// The rest of a computation, captured by call/cc: the part of the
+// continuation stack above the run it was captured in, and the
+// dynamic-winds it is inside of.
+pub struct Continuation {
+    stack: RefCell<Vec<(Cont, HeapObject)>>,
+    fn_stack: Vec<Symbol>,
+    winders: RefCell<HeapObject>,
+    handlers: RefCell<HeapObject>,
+    base: usize,
+}
+
+impl Continuation {
+    pub fn size(&self) -> usize {
+        size_of::<Continuation>() + self.stack.borrow().len() * size_of::<(Cont, HeapObject)>()
+    }
+
+    pub fn for_each_ref(&self, f: &mut dyn FnMut(&HeapObject)) {
+        for (cont, env) in self.stack.borrow().iter() {
+            cont.for_each_ref(f);
+            f(env);
+        }
+        f(&self.winders.borrow());
+        f(&self.handlers.borrow());
+    }
+
+    pub fn clear(&self, nil: &HeapObject) {
+        self.stack.borrow_mut().clear();
+        *self.winders.borrow_mut() = nil.clone();
+        *self.handlers.borrow_mut() = nil.clone();
+    }
+}
+
+// Where the error in obj happened, if it is an error object the
+// interpreter made.
+fn error_location(obj: &HeapObject) -> Option<Location> {
+    match obj.object_type {
+        Type::Error(ref e) => e.location.clone(),
+        _ => Option::None,
+    }
+}
+
+fn as_continuation(obj: &HeapObject) -> &Continuation {
+    match obj.object_type {
+        Type::Procedure(ref p) => match **p {
+            Procedure::Continuation(ref k) => k,
+            _ => panic!("procedure is not a continuation"),
+        },
+        _ => panic!("object is not a continuation"),
+    }
+}
+
+// What a name is bound to at the top level.
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum BindingKind {
+    // a form the compiler implements
+    Syntax,
+    Macro,
+    Procedure,
+    Variable,
+}
+
+impl BindingKind {
+    pub fn name(self) -> &'static str {
+        match self {
+            BindingKind::Syntax => "syntax",
+            BindingKind::Macro => "macro",
+            BindingKind::Procedure => "procedure",
+            BindingKind::Variable => "variable",
+        }
+    }
+}
+
+pub enum Step {
+    Value(HeapObject),
+    Eval(Code),
+}
 
 pub struct Interpreter {
-    live_objects: Vec<HeapObject>,
-    fn_stack: Vec<Rc<String>>,
+    // the objects the GC tracks, each with the size counted in bytes_alloc
+    // when it was made, which is what freeing it takes off again. Some
+    // objects grow after that.
+    live_objects: Vec<(HeapObject, usize)>,
+    fn_stack: Vec<Symbol>,
     environment: Environment,
+    symbols: Interner,
+    // macros defined at the top level
+    syntax: Syntax,
+    stack: Vec<(Cont, HeapObject)>,
+    // stack depth the innermost run started at
+    base: usize,
+    // the dynamic-winds being evaluated, innermost first, as a list of
+    // (before . after) pairs
+    winders: HeapObject,
+    // the exception handlers installed, innermost first
+    handlers: HeapObject,
     nil: HeapObject,
     bool_true: HeapObject,
     bool_false: HeapObject,
     gc_disabled: bool,
     bytes_alloc: usize,
     gc_threshold: usize,
+    // what (command-line) returns, the program or script name first
+    command_line: Vec<String>,
+    // set from outside, by a signal handler say, to stop the evaluation in
+    // progress
+    interrupted: Arc<AtomicBool>,
+    // the files being loaded, innermost last, as canonical paths
+    loading: Vec<PathBuf>,
+}
+
+impl Default for Interpreter {
+    fn default() -> Self {
+        Self::new()
+    }
 }
 
 impl Interpreter {
     pub fn new() -> Self {
-        Interpreter{
+        let nil = Rc::new(Box::new(Object::new(Type::Nil)));
+        let mut i = Interpreter{
             live_objects: Vec::new(),
             fn_stack: Vec::new(),
             environment: Environment::new(),
-            nil: Rc::new(Box::new(Object::new(Type::Cons(Box::new(new_list()))))),
+            symbols: Interner::new(),
+            syntax: Syntax::new(),
+            stack: Vec::new(),
+            base: 0,
+            winders: nil.clone(),
+            handlers: nil.clone(),
+            nil,
             bool_true: Rc::new(Box::new(Object::new(Type::Bool(true)))),
             bool_false: Rc::new(Box::new(Object::new(Type::Bool(false)))),
             gc_disabled: false,
             bytes_alloc: 0,
             gc_threshold: 1000,
+            command_line: Vec::new(),
+            interrupted: Arc::new(AtomicBool::new(false)),
+            loading: Vec::new(),
+        };
+        i.register_primitives();
+        for datum in Reader::new(PRELUDE).read_all(&mut i).expect("prelude") {
+            i.eval(datum).expect("prelude");
+        }
+        // what the prelude defined lives as long as the interpreter, like
+        // the primitives
+        i.gc();
+        i.live_objects.clear();
+        i.bytes_alloc = 0;
+        i
+    }
+
+    // Primitives live as long as the interpreter, so they are not tracked
+    // in live_objects.
+    fn register_primitives(&mut self) {
+        for prim in PRIMITIVES {
+            let obj = Rc::new(Box::new(Object::new(
+                Type::Procedure(Box::new(Procedure::Primitive(prim))))));
+            let sym = self.intern(prim.0);
+            self.environment.insert_sym(sym, obj);
         }
+        for control in CONTROL {
+            let obj = Rc::new(Box::new(Object::new(
+                Type::Procedure(Box::new(Procedure::Control(control))))));
+            let sym = self.intern(control.0);
+            self.environment.insert_sym(sym, obj);
+        }
+    }
+
+    #[inline]
+    pub fn is_interned(&self, sym: &Symbol) -> bool {
+        self.symbols.is_interned(sym)
+    }
+
+    #[inline]
+    pub fn intern(&mut self, name: &str) -> Symbol {
+        self.symbols.intern(name)
     }
 
     #[inline]
@@ -38,22 +550,55 @@ impl Interpreter {
     pub fn new_true(&self) -> HeapObject {self.bool_true.clone()}
     #[inline]
     pub fn new_false(&self) -> HeapObject {self.bool_false.clone()}
+    #[inline]
+    pub fn new_bool(&self, b: bool) -> HeapObject {
+        if b {self.new_true()} else {self.new_false()}
+    }
+
+    #[inline]
+    pub fn cons(&mut self, car: HeapObject, cdr: HeapObject) -> HeapObject {
+        self.new_object(Type::Pair(Pair::new(car, cdr)))
+    }
+
+    #[inline]
+    pub fn new_number(&mut self, n: Number) -> HeapObject {
+        self.new_object(n.into_type())
+    }
+
+    pub fn vec_to_list(&mut self, elems: Vec<HeapObject>) -> HeapObject {
+        let mut list = self.new_nil();
+        for obj in elems.into_iter().rev() {
+            list = self.cons(obj, list);
+        }
+        list
+    }
 
     pub fn new_object(&mut self, t: Type) -> HeapObject {
-        self.bytes_alloc += t.size_of();
+        let size = t.size_of();
+        self.bytes_alloc += size;
         if self.bytes_alloc > self.gc_threshold {
-            self.gc_threshold = self.bytes_alloc/2;
             let n = self.gc();
+            self.gc_threshold = ::std::cmp::max(self.bytes_alloc*2, 1000);
             if cfg!(debug) {
                 println!("GC, freed {} items", n);
             }
         }
 
         let obj = Rc::new(Box::new(Object::new(t)));
-        self.live_objects.push(obj.clone());
+        self.live_objects.push((obj.clone(), size));
         obj
     }
 
+    pub fn set_command_line(&mut self, args: Vec<String>) {
+        self.command_line = args;
+    }
+
+    // A flag that, once set, makes the evaluation in progress fail with
+    // ErrType::Interrupted. It is cleared when that happens.
+    pub fn interrupt_flag(&self) -> Arc<AtomicBool> {
+        self.interrupted.clone()
+    }
+
     #[inline(always)]
     pub fn gc_disable(&mut self) {
         self.gc_disabled = true;
@@ -63,20 +608,54 @@ impl Interpreter {
         self.gc_disabled = false;
     }
 
-    fn gc(&mut self) -> usize {
+    // Objects referenced from outside the tracked heap (the environment,
+    // the continuation stack, primitives and Rust callers in progress) are
+    // roots: their reference count is higher than the number of tracked
+    // objects pointing at them. Everything reachable from a root survives,
+    // the rest has its mutable fields cleared so reference cycles among
+    // unreachable objects are freed.
+    pub fn gc(&mut self) -> usize {
         if self.gc_disabled {
             return 0
         }
+
+        let index: HashMap<*const Object, usize> = self.live_objects.iter().enumerate()
+            .map(|(i, (obj, _))| (&***obj as *const Object, i))
+            .collect();
+        let mut refs = vec![0; self.live_objects.len()];
+        for (obj, _) in &self.live_objects {
+            let mut count = |child: &HeapObject| {
+                if let Option::Some(&i) = index.get(&(&***child as *const Object)) {
+                    refs[i] += 1;
+                }
+            };
+            obj.for_each_ref(&mut count);
+            obj.for_each_weak_ref(&mut count);
+        }
+        for ((obj, _), refs) in self.live_objects.iter().zip(refs) {
+            // live_objects holds one reference itself
+            if Rc::strong_count(obj) > refs + 1 {
+                obj.mark();
+            }
+        }
+
+        // weak tables lose the entries of keys that are about to be freed
+        let live = |key: &HeapObject| key.marked.get() || !index.contains_key(&(&***key as *const Object));
+        for (obj, _) in &self.live_objects {
+            if let Type::HashTable(ref t) = obj.object_type {
+                if t.weak && obj.marked.get() {
+                    t.retain_keys(&live);
+                }
+            }
+        }
+
         let mut count = 0;
-        self.environment.mark_all();
         let mut indices = Vec::<usize>::new();
-
         for i in 0..self.live_objects.len() {
-            let ref obj = self.live_objects[i];
+            let (ref obj, size) = self.live_objects[i];
             if !obj.marked.get() {
-                self.bytes_alloc -= obj.object_type.size_of();
-                debug_assert_eq!(Rc::strong_count(obj), 1);
-                debug_assert_eq!(Rc::weak_count(obj), 0);
+                self.bytes_alloc -= size;
+                obj.clear_refs(&self.nil);
                 indices.push(i);
                 count += 1;
 
@@ -93,224 +672,1268 @@ impl Interpreter {
         count
     }
 
-    fn eval_lambda(&mut self, lambda: &Lambda, exp: List) -> Result<HeapObject, Err> {
-        let params = lambda.params.unwrap_list().clone();
-        if params.len() != exp.len() - 1 {
-            return Result::Err(Err::new(
-                ErrType::WrongArgsNum{wanted: params.len(), got: exp.len()-1},
-                self.fn_stack.clone()));
+    // Pushes cont, to be resumed in the current frame.
+    #[inline]
+    fn push_cont(&mut self, cont: Cont) {
+        let env = self.environment.current().clone();
+        self.stack.push((cont, env));
+    }
+
+    pub fn new_frame(&mut self, vars: Vec<HeapObject>, parent: HeapObject) -> HeapObject {
+        self.new_object(Type::Frame(Frame::new(vars, Option::Some(parent))))
+    }
+
+    // Makes a new child of the current frame holding vars current.
+    pub fn push_frame(&mut self, vars: Vec<HeapObject>) {
+        let parent = self.environment.current().clone();
+        let frame = self.new_frame(vars, parent);
+        self.environment.set_current(frame);
+    }
+
+    // The slot of the global variable name.
+    #[inline]
+    pub fn global(&mut self, name: Symbol) -> Rc<Global> {
+        self.environment.global(name)
+    }
+
+    // The value of the global variable name.
+    pub fn find_global(&self, name: Symbol) -> Result<HeapObject, Err> {
+        self.environment.find_sym(name).map_err(|e| Err::new(e, Vec::new()))
+    }
+
+    // Expands and compiles hobj at the top level, and evaluates it.
+    pub fn eval(&mut self, hobj: HeapObject) -> Result<HeapObject, Err> {
+        self.eval_from(hobj, Option::None)
+    }
+
+    // Like eval, for a datum read from source. Errors point at the
+    // expression in the source they happened in.
+    pub fn eval_source(&mut self, hobj: HeapObject, source: &Rc<Source>) -> Result<HeapObject, Err> {
+        self.eval_from(hobj, Option::Some(source.clone()))
+    }
+
+    fn eval_from(&mut self, hobj: HeapObject, source: Option<Rc<Source>>) -> Result<HeapObject, Err> {
+        let mut expander = Expander::new(self, source);
+        let expanded = expander.expand_toplevel(&hobj)?;
+        let sources = expander.into_sources();
+        let code = Compiler::new(self, sources).compile(&expanded)?;
+        let base = self.stack.len();
+        self.run(base, Step::Eval(code))
+    }
+
+    // Every name bound at the top level, sorted. Macros hide the syntax and
+    // variables of the same name, as they do when expanding.
+    pub fn bindings(&self) -> Vec<(String, BindingKind)> {
+        let mut bindings: HashMap<&str, BindingKind> = HashMap::new();
+        for (name, value) in self.environment.globals().filter(|&(name, _)| self.is_interned(name)) {
+            let kind = match value.object_type {
+                Type::Procedure(_) => BindingKind::Procedure,
+                _ => BindingKind::Variable,
+            };
+            bindings.insert(name.name(), kind);
         }
+        for &name in CORE_FORMS {
+            bindings.insert(name, BindingKind::Syntax);
+        }
+        for name in self.syntax.macro_names().filter(|name| self.is_interned(name)) {
+            bindings.insert(name.name(), BindingKind::Macro);
+        }
+        let mut bindings: Vec<(String, BindingKind)> = bindings.into_iter()
+            .map(|(name, kind)| (name.to_string(), kind))
+            .collect();
+        bindings.sort_by(|a, b| a.0.cmp(&b.0));
+        bindings
+    }
+
+    #[inline]
+    pub fn syntax(&mut self) -> &mut Syntax {
+        &mut self.syntax
+    }
+
+    #[inline]
+    pub fn loading(&mut self) -> &mut Vec<PathBuf> {
+        &mut self.loading
+    }
+
+    // Calls proc with already evaluated args.
+    pub fn apply(&mut self, proc: HeapObject, args: Vec<HeapObject>) -> Result<HeapObject, Err> {
+        let base = self.stack.len();
+        self.push_cont(Cont::ApplyTo(args));
+        self.run(base, Step::Value(proc))
+    }
+
+    // Steps until every continuation above base has been consumed.
+    // Primitives that call back into eval or apply start a nested run on
+    // top of the same stack.
+    fn run(&mut self, base: usize, mut step: Step) -> Result<HeapObject, Err> {
+        let outer_base = self.base;
+        let env = self.environment.current().clone();
+        let traced = self.fn_stack.len();
+        let winders = self.winders.clone();
+        let handlers = self.handlers.clone();
+        self.base = base;
 
-        self.environment.push();
-        if let Option::Some(ref env) = lambda.env {
-            self.environment.push();
-            for (sym, obj) in env.iter() {
-                self.environment.insert_sym(sym.clone(), obj.clone());
+        let res = loop {
+            let next = match step {
+                _ if self.interrupted.load(atomic::Ordering::Relaxed) => {
+                    self.interrupted.store(false, atomic::Ordering::Relaxed);
+                    Result::Err(Err::new(ErrType::Interrupted, self.fn_stack.clone()))
+                },
+                Step::Eval(code) => self.eval_step(code),
+                Step::Value(val) => {
+                    if self.stack.len() == base {
+                        break Result::Ok(val);
+                    }
+                    let (cont, env) = self.stack.pop().unwrap();
+                    self.environment.set_current(env);
+                    self.resume(cont, val)
+                },
+            };
+
+            let next = match next {
+                // Scheme code can handle the error if it installed a handler
+                Result::Err(e) if !self.handlers.is_nil() && !matches!(e.err_type, ErrType::Escape(..) | ErrType::Exit(_) | ErrType::Interrupted) => {
+                    let condition = self.condition(e);
+                    self.raise(condition, false)
+                },
+                next => next,
+            };
+            match next {
+                Result::Ok(next) => step = next,
+                Result::Err(e) => {
+                    self.stack.truncate(base);
+                    self.fn_stack.truncate(traced);
+                    // a continuation captured in this run, invoked from a
+                    // run nested in it
+                    if let ErrType::Escape(ref k, ref val) = e.err_type {
+                        if as_continuation(k).base == base {
+                            match self.throw(k.clone(), vec![val.clone()]) {
+                                Result::Ok(next) => {
+                                    step = next;
+                                    continue;
+                                },
+                                Result::Err(e) => break Result::Err(e),
+                            }
+                        }
+                    }
+                    // errors in after thunks are dropped in favour of the
+                    // one being reported
+                    let _ = self.rewind(winders);
+                    self.handlers = handlers;
+                    break Result::Err(e);
+                }
             }
+        };
+
+        self.environment.set_current(env);
+        self.base = outer_base;
+        res
+    }
+
+    fn eval_step(&mut self, code: Code) -> Result<Step, Err> {
+        match *code {
+            Node::Const(ref obj) => Result::Ok(Step::Value(obj.clone())),
+            Node::Local(depth, index) => Result::Ok(Step::Value(self.environment.local(depth, index))),
+            Node::Global(ref global, ref location) => match *global.value.borrow() {
+                Option::Some(ref val) => Result::Ok(Step::Value(val.clone())),
+                Option::None => Result::Err(self.unbound(global).at(location.clone())),
+            },
+            Node::SetLocal(depth, index, ref value) => {
+                self.push_cont(Cont::SetLocal(depth, index));
+                Result::Ok(Step::Eval(value.clone()))
+            },
+            Node::SetGlobal(ref global, ref value, ref location) => {
+                self.push_cont(Cont::SetGlobal(global.clone(), location.clone()));
+                Result::Ok(Step::Eval(value.clone()))
+            },
+            Node::DefineGlobal(ref global, ref value) => {
+                self.push_cont(Cont::DefineGlobal(global.clone()));
+                Result::Ok(Step::Eval(value.clone()))
+            },
+            Node::If(ref test, ref consequent, ref alternate) => {
+                self.push_cont(Cont::If{consequent: consequent.clone(), alternate: alternate.clone()});
+                Result::Ok(Step::Eval(test.clone()))
+            },
+            Node::Lambda(ref code) => {
+                let lambda = Lambda{env: self.environment.current().clone(), code: code.clone()};
+                Result::Ok(Step::Value(self.new_object(Type::Procedure(Box::new(Procedure::Lambda(lambda))))))
+            },
+            Node::Seq(ref body) => Result::Ok(self.eval_sequence(body.clone(), 0)),
+            Node::Call(ref name, ref proc, ref args, ref location) => {
+                self.push_cont(Cont::EvalArgs{name: name.clone(), args: args.clone(),
+                                              done: Vec::with_capacity(args.len() + 1),
+                                              location: location.clone()});
+                Result::Ok(Step::Eval(proc.clone()))
+            },
+            Node::Let(ref code) => match code.inits.first() {
+                Option::Some(init) => {
+                    self.push_cont(Cont::Let(code.clone(), Vec::with_capacity(code.frame_size)));
+                    Result::Ok(Step::Eval(init.clone()))
+                },
+                Option::None => Result::Ok(self.enter_let(code.clone(), Vec::new())),
+            },
+            Node::Letrec(ref code) => {
+                let vars = vec![self.new_nil(); code.frame_size];
+                self.push_frame(vars);
+                Result::Ok(self.bind_next(code.clone(), 0))
+            },
+            Node::Cond(ref clauses) => self.next_clause(clauses.clone(), 0),
+            Node::Case(ref key, ref clauses) => {
+                self.push_cont(Cont::Case(clauses.clone()));
+                Result::Ok(Step::Eval(key.clone()))
+            },
+            Node::And(ref exprs) => Result::Ok(self.eval_next(exprs.clone(), 0, Cont::And)),
+            Node::Or(ref exprs) => Result::Ok(self.eval_next(exprs.clone(), 0, Cont::Or)),
+            Node::While(ref test, ref body) => {
+                let last = self.new_nil();
+                self.push_cont(Cont::WhileTest{test: test.clone(), body: body.clone(), last});
+                Result::Ok(Step::Eval(test.clone()))
+            },
         }
+    }
 
-        //let mut last = self.nil.clone();
-        let mut last = Result::Ok(self.nil.clone());
+    fn resume(&mut self, cont: Cont, val: HeapObject) -> Result<Step, Err> {
+        match cont {
+            Cont::EvalArgs{name, args, mut done, location} => {
+                done.push(val);
+                match args.get(done.len() - 1) {
+                    Option::Some(next) => {
+                        let next = next.clone();
+                        self.push_cont(Cont::EvalArgs{name, args, done, location});
+                        Result::Ok(Step::Eval(next))
+                    },
+                    Option::None => {
+                        let proc = done.remove(0);
+                        self.apply_step(proc, done, name).map_err(|e| e.at(location))
+                    },
+                }
+            },
+            Cont::Body(body, next) => Result::Ok(self.eval_sequence(body, next)),
+            Cont::Return{traced} => {
+                if traced {
+                    self.fn_stack.pop();
+                }
+                Result::Ok(Step::Value(val))
+            },
+            Cont::If{consequent, alternate} => Result::Ok(if val.is_true() {
+                Step::Eval(consequent)
+            } else {
+                match alternate {
+                    Option::Some(alternate) => Step::Eval(alternate),
+                    Option::None => Step::Value(self.new_nil()),
+                }
+            }),
+            Cont::DefineGlobal(global) => {
+                *global.value.borrow_mut() = Option::Some(val);
+                Result::Ok(Step::Value(self.new_nil()))
+            },
+            Cont::SetGlobal(global, location) => {
+                if global.value.borrow().is_none() {
+                    return Result::Err(self.unbound(&global).at(location));
+                }
+                *global.value.borrow_mut() = Option::Some(val);
+                Result::Ok(Step::Value(self.new_nil()))
+            },
+            Cont::SetLocal(depth, index) => {
+                self.environment.set_local(depth, index, val);
+                Result::Ok(Step::Value(self.new_nil()))
+            },
+            Cont::Let(code, mut vals) => {
+                vals.push(val);
+                match code.inits.get(vals.len()) {
+                    Option::Some(init) => {
+                        let init = init.clone();
+                        self.push_cont(Cont::Let(code, vals));
+                        Result::Ok(Step::Eval(init))
+                    },
+                    Option::None => Result::Ok(self.enter_let(code, vals)),
+                }
+            },
+            Cont::Letrec(code, index) => {
+                self.environment.set_local(0, index, val);
+                Result::Ok(self.bind_next(code, index + 1))
+            },
+            Cont::Cond(clauses, index) => if val.is_true() {
+                Result::Ok(self.eval_clause(&clauses[index].body, val))
+            } else {
+                self.next_clause(clauses, index + 1)
+            },
+            Cont::Case(clauses) => {
+                for clause in clauses.iter() {
+                    let matched = match clause.data {
+                        Option::Some(ref data) => data.iter().any(|d| d.eqv(&val)),
+                        Option::None => true,
+                    };
+                    if matched {
+                        return Result::Ok(self.eval_clause(&clause.body, val));
+                    }
+                }
+                Result::Ok(Step::Value(self.new_nil()))
+            },
+            Cont::ApplyTo(args) => self.apply_step(val, args, Option::None),
+            Cont::And(exprs, next) => Result::Ok(if val.is_true() {
+                self.eval_next(exprs, next, Cont::And)
+            } else {
+                Step::Value(val)
+            }),
+            Cont::Or(exprs, next) => Result::Ok(if !val.is_true() {
+                self.eval_next(exprs, next, Cont::Or)
+            } else {
+                Step::Value(val)
+            }),
+            Cont::WhileTest{test, body, last} => if val.is_true() {
+                self.push_cont(Cont::WhileBody{test, body: body.clone()});
+                Result::Ok(self.eval_sequence(body, 0))
+            } else {
+                Result::Ok(Step::Value(last))
+            },
+            Cont::WhileBody{test, body} => {
+                self.push_cont(Cont::WhileTest{test: test.clone(), body, last: val});
+                Result::Ok(Step::Eval(test))
+            },
+            Cont::WindBefore{thunk, wind} => {
+                let parent = self.winders.clone();
+                let after = wind.cdr().unwrap();
+                self.winders = self.cons(wind, parent.clone());
+                self.push_cont(Cont::WindBody{after, parent});
+                self.apply_step(thunk, Vec::new(), Option::None)
+            },
+            Cont::WindBody{after, parent} => {
+                self.winders = parent;
+                self.push_cont(Cont::Value(val));
+                self.apply_step(after, Vec::new(), Option::None)
+            },
+            Cont::Value(val) => Result::Ok(Step::Value(val)),
+            Cont::Handlers(handlers) => {
+                self.handlers = handlers;
+                Result::Ok(Step::Value(val))
+            },
+            Cont::HandlerReturned(obj) => Result::Err(Err::new(ErrType::HandlerReturned, self.fn_stack.clone())
+                                                      .at(error_location(&obj))),
+            Cont::Each{each, f, seqs, index, mut results} => {
+                match each {
+                    Each::VectorMap => results.push(val),
+                    Each::StringMap => {
+                        self.as_char(&val)?;
+                        results.push(val);
+                    },
+                    Each::HashTableFold => results = vec![val],
+                    Each::VectorForEach | Each::StringForEach | Each::HashTableWalk => {},
+                }
+                self.each(each, f, seqs, index + 1, results)
+            },
+        }
+    }
 
-        /* (lambda-obj p a r a m s)
-         *              ^---------^
-         *              params_iter()
-         */
-        let mut param_syms_iter = exp.iter();
-        for supplied_param in params.iter() {
-            let param_sym = param_syms_iter.next().unwrap();
-            self.environment.insert_sym(param_sym.unwrap_sym(), supplied_param.clone());
+    // Calls f with the index'th elements of seqs, or finishes once one of
+    // them has run out.
+    pub fn each(&mut self, each: Each, f: HeapObject, seqs: Vec<HeapObject>, index: usize, mut results: Vec<HeapObject>)
+                -> Result<Step, Err> {
+        let args = match each {
+            Each::VectorMap | Each::VectorForEach => self.vector_args(&seqs, index)?,
+            Each::StringMap | Each::StringForEach => self.string_args(&seqs, index)?,
+            Each::HashTableWalk | Each::HashTableFold => self.vector_args(&seqs, index)?.map(|entry| {
+                let mut args = vec![entry[0].car().unwrap(), entry[0].cdr().unwrap()];
+                args.extend(results.first().cloned());
+                args
+            }),
+        };
+        match args {
+            Option::Some(args) => {
+                self.push_cont(Cont::Each{each, f: f.clone(), seqs, index, results});
+                self.apply_step(f, args, Option::None)
+            },
+            Option::None => Result::Ok(Step::Value(match each {
+                Each::VectorMap => self.new_vector(results),
+                Each::StringMap => {
+                    let s = results.iter().map(|c| self.as_char(c)).collect::<Result<String, Err>>()?;
+                    self.new_string(s)
+                },
+                Each::HashTableFold => results.pop().unwrap(),
+                Each::VectorForEach | Each::StringForEach | Each::HashTableWalk => self.new_nil(),
+            })),
         }
+    }
+
+    fn apply_step(&mut self, proc: HeapObject, mut args: Vec<HeapObject>, name: Option<Symbol>)
+                  -> Result<Step, Err> {
+        let lambda = match proc.object_type {
+            Type::Procedure(ref p) => match *p.as_ref() {
+                Procedure::Primitive(_) | Procedure::Record(_) => {
+                    if let Option::Some(ref name) = name {
+                        self.fn_stack.push(name.clone());
+                    }
+                    let res = match **p {
+                        Procedure::Primitive(prim) => {
+                            self.check_builtin_args(prim, args.len()).and_then(|()| (prim.1)(self, &args))
+                        },
+                        Procedure::Record(ref record) => self.call_record_proc(record, &args),
+                        _ => unreachable!(),
+                    };
+                    // the error's trace has the primitive, but a handler
+                    // of it continues outside it
+                    if name.is_some() {
+                        self.fn_stack.pop();
+                    }
+                    return res.map(Step::Value);
+                },
+                Procedure::Control(control) => {
+                    self.check_builtin_args(control, args.len())?;
+                    return (control.1)(self, args);
+                },
+                Procedure::Continuation(_) => return self.throw(proc.clone(), args),
+                Procedure::Lambda(ref lambda) => lambda,
+            },
+            _ => return Result::Err(Err::new(
+                ErrType::NotCallable(proc.get_type_string()),
+                self.fn_stack.clone()))
+        };
 
-        // (lambda (a r g s) body)
-        for obj in lambda.body.unwrap_list().iter() {
-            last = self.eval(obj.clone());
-            if let Result::Err(_) = last {
-                break
+        // A call in tail position replaces the Return of the lambda it
+        // returns from instead of stacking on top of it.
+        let tail = self.stack.len() > self.base
+            && matches!(self.stack.last(), Option::Some(&(Cont::Return{..}, _)));
+        if tail {
+            if let Option::Some((Cont::Return{traced: true}, _)) = self.stack.pop() {
+                self.fn_stack.pop();
             }
         }
 
-        if let Type::Procedure(ref p) = last.as_ref().unwrap().clone().object_type {
-            if let Procedure::Lambda(ref l) = *p.as_ref() {
-                let closure = Lambda{
-                        env: Option::Some(self.environment.cur_env_pop()),
-                        params: l.params.clone(),
-                        body: l.body.clone(),
-                };
-                last = Result::Ok(
-                    self.new_object(Type::Procedure(Box::new(Procedure::Lambda(closure))))
-                )
-            }
-        } else if let Option::Some(_) = lambda.env {
-            self.environment.pop();
+        // The parameters come first in the frame, a rest parameter gets the
+        // list of the remaining arguments.
+        let code = &lambda.code;
+        if code.rest {
+            self.check_min_args(code.required, args.len())?;
+            let rest = args.split_off(code.required);
+            let rest = self.vec_to_list(rest);
+            args.push(rest);
+        } else {
+            self.check_args(code.required, args.len())?;
         }
-        self.environment.pop();
+        args.resize(code.frame_size, self.nil.clone());
+        let frame = self.new_frame(args, lambda.env.clone());
+        self.environment.set_current(frame);
 
-        last
+        if let Option::Some(name) = name {
+            self.fn_stack.push(name);
+            self.push_cont(Cont::Return{traced: true});
+        } else {
+            self.push_cont(Cont::Return{traced: false});
+        }
+        Result::Ok(self.eval_sequence(code.body.clone(), 0))
     }
 
-    fn eval_cons(&mut self, c: &List) -> Result<HeapObject, Err> {
-        let frontopt = c.front();
-
-        if let Option::None = frontopt { //empty list
-            return Result::Ok(self.new_nil());
+    // Continues with the continuation k, passing it the value in args.
+    // Continuations resume the run they were captured in, so one captured
+    // outside the current run is passed down to it as an error.
+    fn throw(&mut self, k: HeapObject, mut args: Vec<HeapObject>) -> Result<Step, Err> {
+        let val = match args.len() {
+            0 => self.new_nil(),
+            1 => args.pop().unwrap(),
+            n => return Result::Err(Err::new(ErrType::WrongArgsNum{wanted: 1, got: n}, self.fn_stack.clone())),
+        };
+        if as_continuation(&k).base != self.base {
+            return Result::Err(Err::new(ErrType::Escape(k, val), self.fn_stack.clone()));
         }
 
-        let front = try!(self.eval(frontopt.unwrap().clone()));
-        frontopt.map(|f| {
-            if let Type::Symbol(ref s) = f.object_type {
-                if let Type::Procedure(_) = front.object_type {
-                    self.fn_stack.push(s.clone());
-                }
+        let winders = as_continuation(&k).winders.borrow().clone();
+        self.rewind(winders)?;
+        let k = as_continuation(&k);
+        self.handlers = k.handlers.borrow().clone();
+        self.stack.truncate(self.base);
+        self.stack.extend(k.stack.borrow().iter().cloned());
+        self.fn_stack = k.fn_stack.clone();
+        Result::Ok(Step::Value(val))
+    }
+
+    // Makes target the current winders, calling the after thunks of the
+    // winds being left, innermost first, then the before thunks of the
+    // ones being entered, outermost first.
+    fn rewind(&mut self, target: HeapObject) -> Result<(), Err> {
+        let mut from_len = ListIter::new(&self.winders).count();
+        let mut to_len = ListIter::new(&target).count();
+        let mut to = target;
+        let mut entering = Vec::new();
+        while to_len > from_len {
+            let parent = to.cdr().unwrap();
+            entering.push(to);
+            to = parent;
+            to_len -= 1;
+        }
+        while from_len > to_len || !Rc::ptr_eq(&self.winders, &to) {
+            let wind = self.winders.car().unwrap();
+            self.winders = self.winders.cdr().unwrap();
+            self.apply(wind.cdr().unwrap(), Vec::new())?;
+            if from_len == to_len {
+                let parent = to.cdr().unwrap();
+                entering.push(to);
+                to = parent;
+                to_len -= 1;
             }
-        });
+            from_len -= 1;
+        }
+        for winders in entering.into_iter().rev() {
+            let wind = winders.car().unwrap();
+            self.apply(wind.car().unwrap(), Vec::new())?;
+            self.winders = winders;
+        }
+        Result::Ok(())
+    }
 
-        let res = match front.object_type {
-            Type::Procedure(ref p) => match *p.as_ref() {
-                Procedure::Primitive(prim) => prim(c),
-                Procedure::Lambda(ref lambda) => self.eval_lambda(lambda, c.clone())
-            },
-            _ => Result::Err(Err::new(
-                ErrType::NotCallable(front.get_type_string()),
-                self.fn_stack.clone()))
+    // Calls the current handler with obj, with the handlers outside it
+    // installed. Once a handler of a non-continuable raise returns, the
+    // raise fails again.
+    fn raise(&mut self, obj: HeapObject, continuable: bool) -> Result<Step, Err> {
+        let handler = match self.handlers.car() {
+            Option::Some(handler) => handler,
+            Option::None => return Result::Err(Err::new(ErrType::Raised(obj.clone()), self.fn_stack.clone())
+                                               .at(error_location(&obj))),
         };
+        let outer = self.handlers.cdr().unwrap();
+        self.push_cont(Cont::Handlers(self.handlers.clone()));
+        if !continuable {
+            self.push_cont(Cont::HandlerReturned(obj.clone()));
+        }
+        self.handlers = outer;
+        self.apply_step(handler, vec![obj], Option::None)
+    }
 
-        let _ = res.as_ref().map(|_| {self.fn_stack.pop()});
-        res
+    // The object a handler gets for e: what was raised, or an error
+    // object describing it.
+    fn condition(&mut self, e: Err) -> HeapObject {
+        let kind = match e.err_type {
+            ErrType::Raised(obj) => return obj,
+            ErrType::Read(_) => ErrorKind::Read,
+            ErrType::File(_) => ErrorKind::File,
+            _ => ErrorKind::Error,
+        };
+        let error = ErrorObject{
+            kind,
+            message: e.err_type.to_string(),
+            irritants: self.new_nil(),
+            location: e.location().cloned(),
+        };
+        self.new_object(Type::Error(Box::new(error)))
+    }
+
+    // Evaluates body from its next'th form.
+    fn eval_sequence(&mut self, body: Rc<[Code]>, next: usize) -> Step {
+        match body.get(next) {
+            Option::Some(code) => {
+                let code = code.clone();
+                if next + 1 < body.len() {
+                    self.push_cont(Cont::Body(body, next + 1));
+                }
+                Step::Eval(code)
+            },
+            Option::None => Step::Value(self.new_nil()),
+        }
     }
 
-    fn eval_body(&mut self, body: &List) -> Result<HeapObject, Err> {
-        let mut last = Result::Ok(self.new_nil());
-        for obj in body {
-            last = Result::Ok(try!(self.eval(obj.clone())));
+    // Evaluates exprs[next], leaving the ones after it to cont.
+    fn eval_next(&mut self, exprs: Rc<[Code]>, next: usize, cont: fn(Rc<[Code]>, usize) -> Cont) -> Step {
+        let code = exprs[next].clone();
+        if next + 1 < exprs.len() {
+            self.push_cont(cont(exprs, next + 1));
         }
+        Step::Eval(code)
+    }
 
-        last
+    fn enter_let(&mut self, code: Rc<LetCode>, mut vals: Vec<HeapObject>) -> Step {
+        vals.resize(code.frame_size, self.nil.clone());
+        self.push_frame(vals);
+        self.eval_sequence(code.body.clone(), 0)
     }
 
-    pub fn eval(&mut self, hobj: HeapObject) -> Result<HeapObject, Err> {
-        match hobj.object_type {
-            Type::Cons(ref c) => self.eval_cons(c),
-            Type::Symbol(ref sym) => {
-                let res = self.environment.find_sym(sym.clone());
-                match res {
-                    Result::Ok(val) => Result::Ok(val.clone()),
-                    Result::Err(errt) => Result::Err(Err::new(errt, self.fn_stack.clone()))
-                }
+    // Evaluates the next letrec init, or the body once they are all bound.
+    fn bind_next(&mut self, code: Rc<LetCode>, index: usize) -> Step {
+        match code.inits.get(index) {
+            Option::Some(init) => {
+                let init = init.clone();
+                self.push_cont(Cont::Letrec(code, index));
+                Step::Eval(init)
+            },
+            Option::None => self.eval_sequence(code.body.clone(), 0),
+        }
+    }
+
+    // Evaluates the body of a cond or case clause whose test produced val.
+    fn eval_clause(&mut self, body: &ClauseBody, val: HeapObject) -> Step {
+        match *body {
+            ClauseBody::Test => Step::Value(val),
+            ClauseBody::Seq(ref body) => self.eval_sequence(body.clone(), 0),
+            ClauseBody::Receiver(ref receiver) => {
+                self.push_cont(Cont::ApplyTo(vec![val]));
+                Step::Eval(receiver.clone())
+            },
+        }
+    }
+
+    fn next_clause(&mut self, clauses: Rc<[Clause]>, index: usize) -> Result<Step, Err> {
+        match clauses.get(index) {
+            Option::Some(clause) => {
+                let test = clause.test.clone();
+                self.push_cont(Cont::Cond(clauses, index));
+                Result::Ok(Step::Eval(test))
             },
-            _ => Result::Ok(hobj.clone()),
+            Option::None => Result::Ok(Step::Value(self.new_nil())),
         }
     }
 
     #[inline]
-    fn check_args(&mut self, needed: usize, got: usize) -> Result<(), Err> {
+    pub fn check_args(&mut self, needed: usize, got: usize) -> Result<(), Err> {
         if needed != got {
-            Result::Err(Err::new(ErrType::WrongArgsNum{wanted: needed, got: got}, self.fn_stack.clone()))
+            Result::Err(Err::new(ErrType::WrongArgsNum{wanted: needed, got}, self.fn_stack.clone()))
         } else {
             Result::Ok(())
         }
     }
 
     #[inline]
-    fn check_min_args(&mut self, min: usize, got: usize) -> Result<(), Err> {
+    pub fn check_min_args(&mut self, min: usize, got: usize) -> Result<(), Err> {
         if min > got {
-            Result::Err(Err::new(ErrType::WrongMinArgsNum{min: 1, got: 0}, self.fn_stack.clone()))
+            Result::Err(Err::new(ErrType::WrongMinArgsNum{min, got}, self.fn_stack.clone()))
         } else {
             Result::Ok(())
         }
     }
 
     #[inline]
-    fn get_sym(&mut self, obj: HeapObject) -> Result<Rc<String>, Err> {
-        if let Type::Symbol(ref s) = obj.as_ref().object_type {
-            Result::Ok(s.clone())
-        } else {
-            Result::Err(Err::new(
-                ErrType::WrongType{wanted: "symbolp",
-                                   got: obj.clone().get_type_string()},
-                                 self.fn_stack.clone()))
+    pub fn bad_syntax(&self, form: &'static str) -> Err {
+        Err::new(ErrType::BadSyntax(form), self.fn_stack.clone())
+    }
+
+    #[inline]
+    fn unbound(&self, global: &Global) -> Err {
+        Err::new(ErrType::SymbolNotFound(global.name.clone()), self.fn_stack.clone())
+    }
+
+    #[inline]
+    pub fn wrong_type<S: Into<Cow<'static, str>>>(&self, wanted: S, obj: &HeapObject) -> Err {
+        Err::new(ErrType::WrongType{wanted: wanted.into(), got: obj.get_type_string()},
+                 self.fn_stack.clone())
+    }
+
+    #[inline]
+    pub fn out_of_range(&self, index: i64, len: usize) -> Err {
+        Err::new(ErrType::IndexOutOfRange{index, len}, self.fn_stack.clone())
+    }
+
+    #[inline]
+    pub fn file_error(&self, message: String) -> Err {
+        Err::new(ErrType::File(message), self.fn_stack.clone())
+    }
+
+    #[inline]
+    pub fn key_not_found(&self, key: &HeapObject) -> Err {
+        Err::new(ErrType::KeyNotFound(key.to_string()), self.fn_stack.clone())
+    }
+
+    // The index in obj into a sequence of length len. inclusive allows len
+    // itself, for the end of a range.
+    pub fn index(&self, obj: &HeapObject, len: usize, inclusive: bool) -> Result<usize, Err> {
+        let index = match obj.object_type {
+            Type::Integer(i) => i,
+            Type::BigInt(_) => i64::MAX,
+            _ => return Result::Err(self.wrong_type("exact integer", obj)),
+        };
+        if index < 0 || index as u64 > len as u64 || (index as u64 == len as u64 && !inclusive) {
+            return Result::Err(self.out_of_range(index, len));
         }
+        Result::Ok(index as usize)
     }
 
-    //builtins
-    pub fn print(&mut self, args: &List) -> Result<HeapObject, Err> {
-        try!(self.check_min_args(1, args.len()));
-        for obj in args {
-            if let Type::Symbol(_) = obj.clone().object_type {
-                print!("{} ", try!(self.eval(obj.clone())));
-            }
-            print!("{} ", try!(self.eval(obj.clone())));
+    // The length in obj of a sequence to be made. Callers still have to
+    // check they can allocate it.
+    pub fn length(&self, obj: &HeapObject) -> Result<usize, Err> {
+        match obj.object_type {
+            Type::Integer(n) if n >= 0 => Result::Ok(n as usize),
+            Type::Integer(_) | Type::BigInt(_) => {
+                let err_type = if Number::of(&obj.object_type).is_some_and(|n| n.is_negative()) {
+                    ErrType::NegativeLength(obj.to_string())
+                } else {
+                    ErrType::TooLong(obj.to_string())
+                };
+                Result::Err(Err::new(err_type, self.fn_stack.clone()))
+            },
+            _ => Result::Err(self.wrong_type("exact integer", obj)),
         }
-        Result::Ok(self.new_nil())
     }
 
-    pub fn define(&mut self, args: &List) -> Result<HeapObject, Err> {
-        try!(self.check_args(2, args.len()));
-        let sym = try!(self.get_sym(args.front().unwrap().clone()));
-        let val = try!(self.eval(args.iter().next().unwrap().clone()));
-        self.environment.insert_sym(sym, val);
-        Result::Ok(self.new_nil())
+    #[inline]
+    pub fn too_long(&self, len: usize) -> Err {
+        Err::new(ErrType::TooLong(len.to_string()), self.fn_stack.clone())
     }
 
-    pub fn add(&mut self, args: &List) -> Result<HeapObject, Err> {
-        let res = Object::add_list(args);
-        match res {
-            Result::Ok(obj) => Result::Ok(self.new_object(obj.object_type)),
-            Result::Err(e) => Result::Err(Err::new(e, self.fn_stack.clone())),
+    // The optional start and end arguments from args[at], defaulting to
+    // the whole of a sequence of length len.
+    pub fn range(&self, args: &[HeapObject], at: usize, len: usize) -> Result<(usize, usize), Err> {
+        let start = match args.get(at) {
+            Option::Some(obj) => self.index(obj, len, true)?,
+            Option::None => 0,
+        };
+        let end = match args.get(at + 1) {
+            Option::Some(obj) => self.index(obj, len, true)?,
+            Option::None => len,
+        };
+        if start > end {
+            return Result::Err(self.out_of_range(start as i64, end));
         }
+        Result::Ok((start, end))
     }
 
-    pub fn sub(&mut self, args: &List) -> Result<HeapObject, Err> {
-        let res = Object::sub_list(args);
-        match res {
-            Result::Ok(obj) => Result::Ok(self.new_object(obj.object_type)),
-            Result::Err(e) => Result::Err(Err::new(e, self.fn_stack.clone())),
+    // Fails unless builtin is given as many arguments as its entry in the
+    // builtin tables says it takes. The builtins rely on it.
+    fn check_builtin_args<F>(&mut self, builtin: &Builtin<F>, got: usize) -> Result<(), Err> {
+        match builtin.3 {
+            Option::Some(max) if max == builtin.2 => self.check_args(max, got),
+            Option::Some(max) if got > max => {
+                Result::Err(Err::new(ErrType::WrongArgsNum{wanted: max, got}, self.fn_stack.clone()))
+            },
+            _ => self.check_min_args(builtin.2, got),
         }
     }
 
-    pub fn mul(&mut self, args: &List) -> Result<HeapObject, Err> {
-        let res = Object::mul_list(args);
-        match res {
-            Result::Ok(obj) => Result::Ok(self.new_object(obj.object_type)),
-            Result::Err(e) => Result::Err(Err::new(e, self.fn_stack.clone())),
-        }
+    fn number(&self, obj: &HeapObject) -> Result<Number, Err> {
+        Number::of(&obj.object_type).ok_or_else(|| self.wrong_type("number", obj))
     }
 
-    pub fn div(&mut self, args: &List) -> Result<HeapObject, Err> {
-        let res = Object::div_list(args);
+    fn numbers(&self, args: &[HeapObject]) -> Result<Vec<Number>, Err> {
+        args.iter().map(|obj| self.number(obj)).collect()
+    }
+
+    fn number_result(&mut self, res: Result<Number, ErrType>) -> Result<HeapObject, Err> {
         match res {
-            Result::Ok(obj) => Result::Ok(self.new_object(obj.object_type)),
+            Result::Ok(n) => Result::Ok(self.new_number(n)),
             Result::Err(e) => Result::Err(Err::new(e, self.fn_stack.clone())),
         }
     }
 
-    pub fn refcount(&mut self, args: &List) -> Result<HeapObject, Err> {
-        try!(self.check_args(1, args.len()));
-        let obj: &HeapObject = args.front().unwrap();
-        Result::Ok(self.new_object(Type::Integer(Rc::strong_count(obj) as i64)))
+    // Whether each argument is ordered relative to the next as ok wants.
+    // Comparisons with NaN are always false.
+    fn compare_chain(&mut self, args: &[HeapObject], ok: fn(Ordering) -> bool) -> Result<HeapObject, Err> {
+        let nums = self.numbers(args)?;
+        let res = nums.windows(2).all(|w| w[0].compare(&w[1]).is_some_and(ok));
+        Result::Ok(self.new_bool(res))
     }
 
-    pub fn eval_pub(&mut self, args: &List) -> Result<HeapObject, Err> {
-        try!(self.check_args(1, args.len()));
-        self.eval(args.front().unwrap().clone())
+    fn unary(&mut self, args: &[HeapObject], f: fn(&Number) -> Number) -> Result<HeapObject, Err> {
+        let n = f(&self.number(&args[0])?);
+        Result::Ok(self.new_number(n))
     }
 
-    pub fn while_loop(&mut self, args: &List) -> Result<HeapObject, Err> {
-        try!(self.check_args(2, args.len()));
-        let mut last = Result::Ok(self.new_nil());
-        let body = args.iter().next().unwrap();
+    fn binary(&mut self, args: &[HeapObject], f: fn(&Number, &Number) -> Result<Number, ErrType>)
+              -> Result<HeapObject, Err> {
+        let (a, b) = (self.number(&args[0])?, self.number(&args[1])?);
+        self.number_result(f(&a, &b))
+    }
 
-        while try!(self.eval(args.front().unwrap().clone())).is_true() {
-            if let Type::Cons(ref c) = body.clone().object_type {
-                if let Type::Cons(_) = c.front().unwrap().object_type {
-                    last = Result::Ok(try!(self.eval_body(body.unwrap_list())));
-                }
-            } else {
-                last = Result::Ok(try!(self.eval(body.clone())));
+    // Transcendental functions, which are always inexact.
+    fn float_fn(&mut self, args: &[HeapObject], f: fn(f64) -> f64) -> Result<HeapObject, Err> {
+        let x = self.number(&args[0])?.to_f64();
+        Result::Ok(self.new_number(Number::Real(f(x))))
+    }
+
+    fn predicate(&mut self, args: &[HeapObject], f: fn(&Number) -> bool) -> Result<HeapObject, Err> {
+        let res = f(&self.number(&args[0])?);
+        Result::Ok(self.new_bool(res))
+    }
+
+    fn integer_predicate(&mut self, args: &[HeapObject], f: fn(&Number) -> bool) -> Result<HeapObject, Err> {
+        let n = self.number(&args[0])?;
+        if !n.is_integer() {
+            return Result::Err(self.wrong_type("integer", &args[0]));
+        }
+        Result::Ok(self.new_bool(f(&n)))
+    }
+
+    //builtins
+    pub fn print(&mut self, args: &[HeapObject]) -> Result<HeapObject, Err> {
+        for obj in args {
+            print!("{} ", obj);
+        }
+        Result::Ok(self.new_nil())
+    }
+
+    pub fn add(&mut self, args: &[HeapObject]) -> Result<HeapObject, Err> {
+        let res = Object::add_list(args);
+        match res {
+            Result::Ok(obj) => Result::Ok(self.new_object(obj.object_type)),
+            Result::Err(e) => Result::Err(Err::new(e, self.fn_stack.clone())),
+        }
+    }
+
+    pub fn sub(&mut self, args: &[HeapObject]) -> Result<HeapObject, Err> {
+        let res = Object::sub_list(args);
+        match res {
+            Result::Ok(obj) => Result::Ok(self.new_object(obj.object_type)),
+            Result::Err(e) => Result::Err(Err::new(e, self.fn_stack.clone())),
+        }
+    }
+
+    pub fn mul(&mut self, args: &[HeapObject]) -> Result<HeapObject, Err> {
+        let res = Object::mul_list(args);
+        match res {
+            Result::Ok(obj) => Result::Ok(self.new_object(obj.object_type)),
+            Result::Err(e) => Result::Err(Err::new(e, self.fn_stack.clone())),
+        }
+    }
+
+    pub fn div(&mut self, args: &[HeapObject]) -> Result<HeapObject, Err> {
+        let res = Object::div_list(args);
+        match res {
+            Result::Ok(obj) => Result::Ok(self.new_object(obj.object_type)),
+            Result::Err(e) => Result::Err(Err::new(e, self.fn_stack.clone())),
+        }
+    }
+
+    pub fn num_eq(&mut self, args: &[HeapObject]) -> Result<HeapObject, Err> {
+        self.compare_chain(args, |o| o == Ordering::Equal)
+    }
+
+    pub fn num_lt(&mut self, args: &[HeapObject]) -> Result<HeapObject, Err> {
+        self.compare_chain(args, |o| o == Ordering::Less)
+    }
+
+    pub fn num_gt(&mut self, args: &[HeapObject]) -> Result<HeapObject, Err> {
+        self.compare_chain(args, |o| o == Ordering::Greater)
+    }
+
+    pub fn num_le(&mut self, args: &[HeapObject]) -> Result<HeapObject, Err> {
+        self.compare_chain(args, |o| o != Ordering::Greater)
+    }
+
+    pub fn num_ge(&mut self, args: &[HeapObject]) -> Result<HeapObject, Err> {
+        self.compare_chain(args, |o| o != Ordering::Less)
+    }
+
+    pub fn quotient(&mut self, args: &[HeapObject]) -> Result<HeapObject, Err> {
+        self.binary(args, Number::quotient)
+    }
+
+    pub fn remainder(&mut self, args: &[HeapObject]) -> Result<HeapObject, Err> {
+        self.binary(args, Number::remainder)
+    }
+
+    pub fn modulo(&mut self, args: &[HeapObject]) -> Result<HeapObject, Err> {
+        self.binary(args, Number::modulo)
+    }
+
+    pub fn abs(&mut self, args: &[HeapObject]) -> Result<HeapObject, Err> {
+        self.unary(args, Number::abs)
+    }
+
+    // min and max are inexact if any argument is.
+    fn extremum(&mut self, args: &[HeapObject], wanted: Ordering) -> Result<HeapObject, Err> {
+        let nums = self.numbers(args)?;
+        let exact = nums.iter().all(Number::is_exact);
+        let mut best = nums[0].clone();
+        for n in &nums[1..] {
+            if n.compare(&best) == Option::Some(wanted) {
+                best = n.clone();
             }
         }
+        Result::Ok(self.new_number(if exact {best} else {best.inexact()}))
+    }
+
+    pub fn min(&mut self, args: &[HeapObject]) -> Result<HeapObject, Err> {
+        self.extremum(args, Ordering::Less)
+    }
+
+    pub fn max(&mut self, args: &[HeapObject]) -> Result<HeapObject, Err> {
+        self.extremum(args, Ordering::Greater)
+    }
+
+    fn fold_integers(&mut self, args: &[HeapObject], identity: i64,
+                     f: fn(&Number, &Number) -> Result<Number, ErrType>) -> Result<HeapObject, Err> {
+        let nums = self.numbers(args)?;
+        let res = nums.iter().try_fold(Number::Fixnum(identity), |acc, n| f(&acc, n));
+        self.number_result(res)
+    }
+
+    pub fn gcd(&mut self, args: &[HeapObject]) -> Result<HeapObject, Err> {
+        self.fold_integers(args, 0, Number::gcd)
+    }
+
+    pub fn lcm(&mut self, args: &[HeapObject]) -> Result<HeapObject, Err> {
+        self.fold_integers(args, 1, Number::lcm)
+    }
+
+    pub fn floor(&mut self, args: &[HeapObject]) -> Result<HeapObject, Err> {
+        self.unary(args, Number::floor)
+    }
+
+    pub fn ceiling(&mut self, args: &[HeapObject]) -> Result<HeapObject, Err> {
+        self.unary(args, Number::ceiling)
+    }
+
+    pub fn round(&mut self, args: &[HeapObject]) -> Result<HeapObject, Err> {
+        self.unary(args, Number::round)
+    }
+
+    pub fn truncate(&mut self, args: &[HeapObject]) -> Result<HeapObject, Err> {
+        self.unary(args, Number::truncate)
+    }
+
+    pub fn exp(&mut self, args: &[HeapObject]) -> Result<HeapObject, Err> {
+        self.float_fn(args, f64::exp)
+    }
+
+    // (log z) or (log z base)
+    pub fn log(&mut self, args: &[HeapObject]) -> Result<HeapObject, Err> {
+        if args.len() == 2 {
+            let (z, base) = (self.number(&args[0])?.to_f64(), self.number(&args[1])?.to_f64());
+            return Result::Ok(self.new_number(Number::Real(z.ln() / base.ln())));
+        }
+        self.float_fn(args, f64::ln)
+    }
+
+    pub fn sin(&mut self, args: &[HeapObject]) -> Result<HeapObject, Err> {
+        self.float_fn(args, f64::sin)
+    }
+
+    pub fn cos(&mut self, args: &[HeapObject]) -> Result<HeapObject, Err> {
+        self.float_fn(args, f64::cos)
+    }
+
+    pub fn tan(&mut self, args: &[HeapObject]) -> Result<HeapObject, Err> {
+        self.float_fn(args, f64::tan)
+    }
+
+    pub fn asin(&mut self, args: &[HeapObject]) -> Result<HeapObject, Err> {
+        self.float_fn(args, f64::asin)
+    }
+
+    pub fn acos(&mut self, args: &[HeapObject]) -> Result<HeapObject, Err> {
+        self.float_fn(args, f64::acos)
+    }
+
+    // (atan z) or (atan y x)
+    pub fn atan(&mut self, args: &[HeapObject]) -> Result<HeapObject, Err> {
+        if args.len() == 2 {
+            let (y, x) = (self.number(&args[0])?.to_f64(), self.number(&args[1])?.to_f64());
+            return Result::Ok(self.new_number(Number::Real(y.atan2(x))));
+        }
+        self.float_fn(args, f64::atan)
+    }
+
+    pub fn sqrt(&mut self, args: &[HeapObject]) -> Result<HeapObject, Err> {
+        self.unary(args, Number::sqrt)
+    }
+
+    // There are no multiple values, so the root and the remainder are
+    // returned as a list of two.
+    pub fn exact_integer_sqrt(&mut self, args: &[HeapObject]) -> Result<HeapObject, Err> {
+        match self.number(&args[0])?.exact_integer_sqrt() {
+            Result::Ok((s, r)) => {
+                let vals = vec![self.new_number(s), self.new_number(r)];
+                Result::Ok(self.vec_to_list(vals))
+            },
+            Result::Err(e) => Result::Err(Err::new(e, self.fn_stack.clone())),
+        }
+    }
+
+    pub fn expt(&mut self, args: &[HeapObject]) -> Result<HeapObject, Err> {
+        self.binary(args, Number::expt)
+    }
+
+    fn radix(&self, args: &[HeapObject]) -> Result<u32, Err> {
+        match args.get(1).map(|obj| &obj.object_type) {
+            Option::None => Result::Ok(10),
+            Option::Some(&Type::Integer(r)) if r == 2 || r == 8 || r == 10 || r == 16 => Result::Ok(r as u32),
+            Option::Some(_) => Result::Err(self.wrong_type("radix 2, 8, 10 or 16", &args[1])),
+        }
+    }
+
+    // (number->string z [radix])
+    pub fn number_to_string(&mut self, args: &[HeapObject]) -> Result<HeapObject, Err> {
+        let n = self.number(&args[0])?;
+        let radix = self.radix(args)?;
+        if radix != 10 && !n.is_exact() {
+            return Result::Err(self.wrong_type("exact number", &args[0]));
+        }
+        Result::Ok(self.new_string(n.to_string_radix(radix)))
+    }
+
+    // (string->number string [radix]), #f if string isn't a number
+    pub fn string_to_number(&mut self, args: &[HeapObject]) -> Result<HeapObject, Err> {
+        let radix = self.radix(args)?;
+        let text = match args[0].object_type {
+            Type::String(ref s) => s.borrow().clone(),
+            _ => return Result::Err(self.wrong_type("string", &args[0])),
+        };
+        let prefix = match radix {
+            2 => "#b",
+            8 => "#o",
+            16 => "#x",
+            _ => "",
+        };
+        match number::parse(&format!("{}{}", prefix, text)) {
+            Option::Some(n) => Result::Ok(self.new_number(n)),
+            Option::None => Result::Ok(self.new_false()),
+        }
+    }
+
+    pub fn integer_p(&mut self, args: &[HeapObject]) -> Result<HeapObject, Err> {
+        let res = Number::of(&args[0].object_type).is_some_and(|n| n.is_integer());
+        Result::Ok(self.new_bool(res))
+    }
+
+    pub fn zero_p(&mut self, args: &[HeapObject]) -> Result<HeapObject, Err> {
+        self.predicate(args, Number::is_zero)
+    }
+
+    pub fn positive_p(&mut self, args: &[HeapObject]) -> Result<HeapObject, Err> {
+        self.predicate(args, |n| n.compare(&Number::Fixnum(0)) == Option::Some(Ordering::Greater))
+    }
+
+    pub fn negative_p(&mut self, args: &[HeapObject]) -> Result<HeapObject, Err> {
+        self.predicate(args, Number::is_negative)
+    }
+
+    pub fn odd_p(&mut self, args: &[HeapObject]) -> Result<HeapObject, Err> {
+        self.integer_predicate(args, |n| !n.modulo(&Number::Fixnum(2)).is_ok_and(|r| r.is_zero()))
+    }
+
+    pub fn even_p(&mut self, args: &[HeapObject]) -> Result<HeapObject, Err> {
+        self.integer_predicate(args, |n| n.modulo(&Number::Fixnum(2)).is_ok_and(|r| r.is_zero()))
+    }
+
+    pub fn number_p(&mut self, args: &[HeapObject]) -> Result<HeapObject, Err> {
+        Result::Ok(self.new_bool(Number::of(&args[0].object_type).is_some()))
+    }
+
+    pub fn exact_p(&mut self, args: &[HeapObject]) -> Result<HeapObject, Err> {
+        let n = self.number(&args[0])?;
+        Result::Ok(self.new_bool(n.is_exact()))
+    }
 
-        last
+    pub fn inexact_p(&mut self, args: &[HeapObject]) -> Result<HeapObject, Err> {
+        let n = self.number(&args[0])?;
+        Result::Ok(self.new_bool(!n.is_exact()))
+    }
+
+    pub fn exact(&mut self, args: &[HeapObject]) -> Result<HeapObject, Err> {
+        match self.number(&args[0])?.exact() {
+            Result::Ok(n) => Result::Ok(self.new_number(n)),
+            Result::Err(e) => Result::Err(Err::new(e, self.fn_stack.clone())),
+        }
+    }
+
+    pub fn inexact(&mut self, args: &[HeapObject]) -> Result<HeapObject, Err> {
+        let n = self.number(&args[0])?.inexact();
+        Result::Ok(self.new_number(n))
+    }
+
+    pub fn refcount(&mut self, args: &[HeapObject]) -> Result<HeapObject, Err> {
+        let obj: &HeapObject = &args[0];
+        Result::Ok(self.new_object(Type::Integer(Rc::strong_count(obj) as i64)))
+    }
+
+    pub fn eval_pub(&mut self, args: &[HeapObject]) -> Result<HeapObject, Err> {
+        self.eval(args[0].clone())
+    }
+
+    pub fn cons_pub(&mut self, args: &[HeapObject]) -> Result<HeapObject, Err> {
+        Result::Ok(self.cons(args[0].clone(), args[1].clone()))
+    }
+
+    pub fn car(&mut self, args: &[HeapObject]) -> Result<HeapObject, Err> {
+        args[0].car().ok_or_else(|| self.wrong_type("pair", &args[0]))
+    }
+
+    pub fn cdr(&mut self, args: &[HeapObject]) -> Result<HeapObject, Err> {
+        args[0].cdr().ok_or_else(|| self.wrong_type("pair", &args[0]))
+    }
+
+    pub fn set_car(&mut self, args: &[HeapObject]) -> Result<HeapObject, Err> {
+        match args[0].object_type {
+            Type::Pair(ref p) => *p.car.borrow_mut() = args[1].clone(),
+            _ => return Result::Err(self.wrong_type("pair", &args[0])),
+        }
+        Result::Ok(self.new_nil())
+    }
+
+    pub fn set_cdr(&mut self, args: &[HeapObject]) -> Result<HeapObject, Err> {
+        match args[0].object_type {
+            Type::Pair(ref p) => *p.cdr.borrow_mut() = args[1].clone(),
+            _ => return Result::Err(self.wrong_type("pair", &args[0])),
+        }
+        Result::Ok(self.new_nil())
+    }
+
+    pub fn list(&mut self, args: &[HeapObject]) -> Result<HeapObject, Err> {
+        Result::Ok(self.vec_to_list(args.to_vec()))
+    }
+
+    pub fn pair_p(&mut self, args: &[HeapObject]) -> Result<HeapObject, Err> {
+        Result::Ok(self.new_bool(matches!(args[0].object_type, Type::Pair(_))))
+    }
+
+    pub fn null_p(&mut self, args: &[HeapObject]) -> Result<HeapObject, Err> {
+        Result::Ok(self.new_bool(args[0].is_nil()))
+    }
+
+    pub fn list_p(&mut self, args: &[HeapObject]) -> Result<HeapObject, Err> {
+        Result::Ok(self.new_bool(Object::is_list(&args[0])))
+    }
+
+    pub fn eq_p(&mut self, args: &[HeapObject]) -> Result<HeapObject, Err> {
+        Result::Ok(self.new_bool(args[0].is_eq(&args[1])))
+    }
+
+    pub fn eqv_p(&mut self, args: &[HeapObject]) -> Result<HeapObject, Err> {
+        Result::Ok(self.new_bool(args[0].eqv(&args[1])))
+    }
+
+    pub fn equal_p(&mut self, args: &[HeapObject]) -> Result<HeapObject, Err> {
+        Result::Ok(self.new_bool(Object::equal(&args[0], &args[1])))
+    }
+
+    pub fn symbol_p(&mut self, args: &[HeapObject]) -> Result<HeapObject, Err> {
+        Result::Ok(self.new_bool(matches!(args[0].object_type, Type::Symbol(_))))
+    }
+
+    pub fn string_to_symbol(&mut self, args: &[HeapObject]) -> Result<HeapObject, Err> {
+        match args[0].object_type {
+            Type::String(ref s) => {
+                let sym = self.intern(&s.borrow());
+                Result::Ok(self.new_object(Type::Symbol(sym)))
+            },
+            _ => Result::Err(self.wrong_type("string", &args[0])),
+        }
+    }
+
+    pub fn symbol_to_string(&mut self, args: &[HeapObject]) -> Result<HeapObject, Err> {
+        match args[0].object_type {
+            Type::Symbol(ref s) => Result::Ok(self.new_string(s.name().to_string())),
+            _ => Result::Err(self.wrong_type("symbol", &args[0])),
+        }
+    }
+
+    // (gensym [prefix]), a symbol that isn't eq? to any other
+    pub fn gensym(&mut self, args: &[HeapObject]) -> Result<HeapObject, Err> {
+        let sym = match args.first().map(|a| &a.object_type) {
+            Option::None => self.symbols.gensym("g"),
+            Option::Some(Type::String(s)) => self.symbols.gensym(&s.borrow()),
+            Option::Some(Type::Symbol(s)) => self.symbols.gensym(s.name()),
+            Option::Some(_) => return Result::Err(self.wrong_type("string", &args[0])),
+        };
+        Result::Ok(self.new_object(Type::Symbol(sym)))
+    }
+
+    // (call/cc proc) calls proc with the continuation of the call.
+    pub fn call_cc(&mut self, args: Vec<HeapObject>) -> Result<Step, Err> {
+        let k = Continuation{
+            stack: RefCell::new(self.stack[self.base..].to_vec()),
+            fn_stack: self.fn_stack.clone(),
+            winders: RefCell::new(self.winders.clone()),
+            handlers: RefCell::new(self.handlers.clone()),
+            base: self.base,
+        };
+        let k = self.new_object(Type::Procedure(Box::new(Procedure::Continuation(k))));
+        self.apply_step(args[0].clone(), vec![k], Option::None)
+    }
+
+    // (dynamic-wind before thunk after) calls thunk, with before called
+    // whenever its extent is entered and after whenever it is left, by
+    // returning or by a continuation.
+    pub fn dynamic_wind(&mut self, args: Vec<HeapObject>) -> Result<Step, Err> {
+        let wind = self.cons(args[0].clone(), args[2].clone());
+        self.push_cont(Cont::WindBefore{thunk: args[1].clone(), wind});
+        self.apply_step(args[0].clone(), Vec::new(), Option::None)
+    }
+
+    // (with-exception-handler handler thunk) calls thunk with handler
+    // installed.
+    pub fn with_exception_handler(&mut self, args: Vec<HeapObject>) -> Result<Step, Err> {
+        let handlers = self.handlers.clone();
+        self.push_cont(Cont::Handlers(handlers.clone()));
+        self.handlers = self.cons(args[0].clone(), handlers);
+        self.apply_step(args[1].clone(), Vec::new(), Option::None)
+    }
+
+    pub fn raise_pub(&mut self, args: Vec<HeapObject>) -> Result<Step, Err> {
+        self.raise(args[0].clone(), false)
+    }
+
+    // (raise-continuable obj) returns what the handler returns.
+    pub fn raise_continuable(&mut self, args: Vec<HeapObject>) -> Result<Step, Err> {
+        self.raise(args[0].clone(), true)
+    }
+
+    // (error message irritant ...) raises a new error object.
+    pub fn error(&mut self, mut args: Vec<HeapObject>) -> Result<Step, Err> {
+        let irritants = args.split_off(1);
+        let message = match args[0].object_type {
+            Type::String(ref s) => s.borrow().clone(),
+            _ => args[0].to_string(),
+        };
+        let error = ErrorObject{kind: ErrorKind::Error, message, irritants: self.vec_to_list(irritants), location: Option::None};
+        let obj = self.new_object(Type::Error(Box::new(error)));
+        self.raise(obj, false)
+    }
+
+    pub fn macroexpand(&mut self, args: &[HeapObject]) -> Result<HeapObject, Err> {
+        Expander::new(self, Option::None).expand_toplevel(&args[0])
+    }
+
+    fn error_object<'a>(&self, obj: &'a HeapObject) -> Result<&'a ErrorObject, Err> {
+        match obj.object_type {
+            Type::Error(ref e) => Result::Ok(e),
+            _ => Result::Err(self.wrong_type("error-object", obj)),
+        }
+    }
+
+    pub fn error_object_p(&mut self, args: &[HeapObject]) -> Result<HeapObject, Err> {
+        Result::Ok(self.new_bool(matches!(args[0].object_type, Type::Error(_))))
+    }
+
+    pub fn error_object_message(&mut self, args: &[HeapObject]) -> Result<HeapObject, Err> {
+        let message = self.error_object(&args[0])?.message.clone();
+        Result::Ok(self.new_string(message))
+    }
+
+    pub fn error_object_irritants(&mut self, args: &[HeapObject]) -> Result<HeapObject, Err> {
+        self.error_object(&args[0]).map(|e| e.irritants.clone())
+    }
+
+    pub fn read_error_p(&mut self, args: &[HeapObject]) -> Result<HeapObject, Err> {
+        let res = matches!(args[0].object_type, Type::Error(ref e) if e.kind == ErrorKind::Read);
+        Result::Ok(self.new_bool(res))
+    }
+
+    pub fn file_error_p(&mut self, args: &[HeapObject]) -> Result<HeapObject, Err> {
+        let res = matches!(args[0].object_type, Type::Error(ref e) if e.kind == ErrorKind::File);
+        Result::Ok(self.new_bool(res))
+    }
+
+    pub fn command_line(&mut self, _args: &[HeapObject]) -> Result<HeapObject, Err> {
+        let args = self.command_line.clone().into_iter().map(|arg| self.new_string(arg)).collect();
+        Result::Ok(self.vec_to_list(args))
+    }
+
+    // (exit [obj]) leaves the program, after the after thunks of the
+    // dynamic-winds it is inside of. #t or no obj is success, #f failure
+    // and an integer the exit status, which has to fit in 32 bits. Any
+    // other obj is failure.
+    pub fn exit(&mut self, args: &[HeapObject]) -> Result<HeapObject, Err> {
+        let status = match args.first().map(|obj| &obj.object_type) {
+            Option::None | Option::Some(&Type::Bool(true)) => 0,
+            Option::Some(&Type::Integer(n)) if n >= i32::MIN as i64 && n <= i32::MAX as i64 => n as i32,
+            Option::Some(&Type::Integer(_)) | Option::Some(&Type::BigInt(_)) => {
+                return Result::Err(self.wrong_type("32-bit exit status", &args[0]));
+            },
+            Option::Some(_) => 1,
+        };
+        Result::Err(Err::new(ErrType::Exit(status), Vec::new()))
+    }
+
+    pub fn symbol_interned_p(&mut self, args: &[HeapObject]) -> Result<HeapObject, Err> {
+        match args[0].object_type {
+            Type::Symbol(ref s) => Result::Ok(self.new_bool(self.symbols.is_interned(s))),
+            _ => Result::Err(self.wrong_type("symbol", &args[0])),
+        }
     }
 }
 
@@ -321,23 +1944,26 @@ mod test {
     use types::Type;
     use std::rc::Rc;
     use std::string::ToString;
+    use parse::Reader;
+    use test_util::{eval_str, assert_eval};
 
     #[test]
     fn test_gc() {
         let mut interpreter = Interpreter::new();
 
-        let obj = interpreter.new_object(Type::String(Rc::new("foobar".to_string())));
-        interpreter.environment.push();
-        interpreter.environment.insert_sym(Rc::new("test".to_string()), obj);
+        let obj = interpreter.new_object(Type::String(RefCell::new("foobar".to_string())));
+        let global = interpreter.environment.current().clone();
+        interpreter.push_frame(vec![obj]);
         assert_eq!(interpreter.gc(), 0);
-        assert_eq!(interpreter.live_objects.len(), 1);
-        interpreter.environment.pop();
-        assert_eq!(interpreter.gc(), 1);
+        // the string and the frame holding it
+        assert_eq!(interpreter.live_objects.len(), 2);
+        interpreter.environment.set_current(global);
+        assert_eq!(interpreter.gc(), 2);
         assert_eq!(interpreter.live_objects.len(), 0);
 
         interpreter.gc_disable();
         for _ in 0..10 {
-            interpreter.new_object(Type::String(Rc::new("foobar".to_string())));
+            interpreter.new_object(Type::String(RefCell::new("foobar".to_string())));
         }
         interpreter.gc_enable();
 
@@ -348,15 +1974,679 @@ mod test {
     #[test]
     fn test_sym_found() {
         let mut interpreter = Interpreter::new();
-        let obj = interpreter.new_object(Type::String(Rc::new("foobar".to_string())));
-        interpreter.environment.insert_sym(Rc::new("test".to_string()), obj);
-        interpreter.environment.find_sym(Rc::new("test".to_string())).expect("");
+        let obj = interpreter.new_object(Type::String(RefCell::new("foobar".to_string())));
+        let sym = interpreter.intern("test");
+        interpreter.environment.insert_sym(sym.clone(), obj);
+        interpreter.environment.find_sym(sym).expect("");
     }
 
     #[should_panic]
     #[test]
     fn test_sym_not_found() {
-        let interpreter = Interpreter::new();
-        interpreter.environment.find_sym(Rc::new("abcd".to_string())).expect("");
+        let mut interpreter = Interpreter::new();
+        let sym = interpreter.intern("abcd");
+        interpreter.environment.find_sym(sym).expect("");
+    }
+
+    #[test]
+    fn test_primitives_bound() {
+        let mut interpreter = Interpreter::new();
+        for name in &["+", "-", "*", "/", "print", "eval", "refcount"] {
+            let sym = interpreter.intern(name);
+            interpreter.environment.find_sym(sym).expect(name);
+        }
+        // define and while started out as primitives, and are now forms the
+        // compiler implements, bound at the top level as syntax
+        let bindings = interpreter.bindings();
+        for name in &["define", "while"] {
+            let kind = bindings.iter().find(|b| b.0 == *name).map(|b| b.1);
+            assert_eq!(kind, Option::Some(BindingKind::Syntax), "{}", name);
+        }
+        eval_str(&mut interpreter, "(define n 0)").unwrap();
+        eval_str(&mut interpreter, "(while (< n 3) (set! n (+ n 1)))").unwrap();
+        assert_eval(&mut interpreter, "n", "3");
+    }
+
+    #[test]
+    fn test_call_primitive() {
+        let mut interpreter = Interpreter::new();
+        let res = eval_str(&mut interpreter, "(+ 1 2)").unwrap();
+        assert_eq!(format!("{}", res), "3");
+
+        eval_str(&mut interpreter, "(define x 40)").unwrap();
+        let res = eval_str(&mut interpreter, "(+ x (+ 1 1))").unwrap();
+        assert_eq!(format!("{}", res), "42");
+    }
+
+    #[test]
+    fn test_builtin_arity() {
+        let mut i = Interpreter::new();
+        let entries = PRIMITIVES.iter().map(|&(name, _, min, max)| (name, min, max))
+            .chain(CONTROL.iter().map(|&(name, _, min, max)| (name, min, max)));
+        for (name, min, max) in entries {
+            // one argument too few and one too many
+            let counts = min.checked_sub(1).into_iter().chain(max.map(|max| max + 1));
+            for n in counts {
+                let call = format!("({}{})", name, " 1".repeat(n));
+                match eval_str(&mut i, &call) {
+                    Result::Err(Err{err_type: ErrType::WrongArgsNum{..}, ..}) |
+                    Result::Err(Err{err_type: ErrType::WrongMinArgsNum{..}, ..}) => {},
+                    _ => panic!("{}", call),
+                }
+            }
+        }
+        let err = eval_str(&mut i, "(vector-copy #(1) 0 1 2)").err().unwrap();
+        assert_eq!(err.err_type.to_string(), "Wrong number of arguments, wanted: 3, got: 4");
+        let err = eval_str(&mut i, "(vector-map car)").err().unwrap();
+        assert_eq!(err.err_type.to_string(), "Wanted minimum 2 args, got: 1");
+    }
+
+    #[test]
+    fn test_numeric_tower() {
+        let mut i = Interpreter::new();
+        assert_eval(&mut i, "(+ 9223372036854775807 1)", "9223372036854775808");
+        assert_eval(&mut i, "(+ 99999999999999999999 -99999999999999999998)", "1");
+        assert_eval(&mut i, "(+ 1/3 1/6)", "1/2");
+        assert_eval(&mut i, "(+ 1/2 0.25)", "0.75");
+        assert_eval(&mut i, "(+ #xff #b1 #e1.5)", "515/2");
+        assert_eval(&mut i, "(exact? 1/3)", "#t");
+        assert_eval(&mut i, "(exact? #i1/4)", "#f");
+        assert_eval(&mut i, "(inexact? 1.0)", "#t");
+        assert_eval(&mut i, "(number? (quote a))", "#f");
+        assert_eval(&mut i, "(exact 2.5)", "5/2");
+        assert_eval(&mut i, "(inexact->exact 4.0)", "4");
+        assert_eval(&mut i, "(exact->inexact 1/8)", "0.125");
+        assert_eval(&mut i, "(inexact 12345678901234567890)", "12345678901234567000.0");
+
+        match eval_str(&mut i, "(/ 1 0)").map_err(|e| e.err_type) {
+            Result::Err(ErrType::DivisionByZero) => {},
+            _ => panic!("(/ 1 0) should be a division by zero"),
+        }
+        assert!(eval_str(&mut i, "(exact +nan.0)").is_err());
+    }
+
+    #[test]
+    fn test_arithmetic() {
+        let mut i = Interpreter::new();
+        let cases = [
+            ("(+ 3 4)", "7"),
+            ("(+ 3)", "3"),
+            ("(+)", "0"),
+            ("(* 4)", "4"),
+            ("(*)", "1"),
+            ("(* 2 3 4)", "24"),
+            ("(- 3 4)", "-1"),
+            ("(- 3 4 5)", "-6"),
+            ("(- 3)", "-3"),
+            ("(- 5 2)", "3"),
+            ("(/ 3 4 5)", "3/20"),
+            ("(/ 3)", "1/3"),
+            ("(/ 6 3)", "2"),
+            ("(/ 1.0 4)", "0.25"),
+            ("(abs -7)", "7"),
+            ("(abs -1/2)", "1/2"),
+            ("(max 3 4)", "4"),
+            ("(max 3.9 4)", "4.0"),
+            ("(min 1 2.0)", "1.0"),
+            ("(quotient 13 4)", "3"),
+            ("(quotient -13 4)", "-3"),
+            ("(remainder 13 4)", "1"),
+            ("(remainder -13 4)", "-1"),
+            ("(remainder 13 -4)", "1"),
+            ("(modulo 13 4)", "1"),
+            ("(modulo -13 4)", "3"),
+            ("(modulo 13 -4)", "-3"),
+            ("(modulo -13 -4)", "-1"),
+            ("(remainder -13 -4.0)", "-1.0"),
+            ("(quotient -9223372036854775808 -1)", "9223372036854775808"),
+            ("(gcd 32 -36)", "4"),
+            ("(gcd)", "0"),
+            ("(lcm 32 -36)", "288"),
+            ("(lcm 32.0 -36)", "288.0"),
+            ("(lcm)", "1"),
+            ("(floor -4.3)", "-5.0"),
+            ("(ceiling -4.3)", "-4.0"),
+            ("(truncate -4.3)", "-4.0"),
+            ("(round -4.3)", "-4.0"),
+            ("(floor 3.5)", "3.0"),
+            ("(ceiling 3.5)", "4.0"),
+            ("(truncate 3.5)", "3.0"),
+            ("(round 3.5)", "4.0"),
+            ("(round 7/2)", "4"),
+            ("(round 5/2)", "2"),
+            ("(round -5/2)", "-2"),
+            ("(round 2.5)", "2.0"),
+            ("(floor -7/2)", "-4"),
+            ("(round 7)", "7"),
+            ("(sqrt 9)", "3"),
+            ("(sqrt 1/4)", "1/2"),
+            ("(sqrt 2.25)", "1.5"),
+            ("(sqrt 2)", "1.4142135623730951"),
+            ("(exact-integer-sqrt 4)", "(2 0)"),
+            ("(exact-integer-sqrt 5)", "(2 1)"),
+            ("(expt 2 10)", "1024"),
+            ("(expt 2 100)", "1267650600228229401496703205376"),
+            ("(expt 2/3 -2)", "9/4"),
+            ("(expt 0 0)", "1"),
+            ("(expt 0.0 0)", "1.0"),
+            ("(expt 4 1/2)", "2.0"),
+            ("(expt 2.0 3)", "8.0"),
+            ("(exp 0)", "1.0"),
+            ("(log 1)", "0.0"),
+            ("(log 8 2)", "3.0"),
+            ("(sin 0)", "0.0"),
+            ("(cos 0)", "1.0"),
+            ("(tan 0)", "0.0"),
+            ("(asin 0)", "0.0"),
+            ("(acos 1)", "0.0"),
+            ("(atan 0)", "0.0"),
+            ("(atan 1 0)", "1.5707963267948966"),
+            ("(number->string 255 16)", "\"ff\""),
+            ("(number->string -5 2)", "\"-101\""),
+            ("(number->string 1/3)", "\"1/3\""),
+            ("(number->string 2.0)", "\"2.0\""),
+            ("(string->number \"100\")", "100"),
+            ("(string->number \"100\" 16)", "256"),
+            ("(string->number \"1e2\")", "100.0"),
+            ("(string->number \"#xff\")", "255"),
+            ("(string->number \"abc\")", "#f"),
+        ];
+        for &(src, expected) in cases.iter() {
+            assert_eval(&mut i, src, expected);
+        }
+
+        for src in &["(-)", "(/ 1 0)", "(quotient 1 0)", "(modulo 1.5 1)", "(expt 0 -1)",
+                     "(exact-integer-sqrt -1)", "(number->string 1.5 2)", "(+ 1 (quote a))"] {
+            assert!(eval_str(&mut i, src).is_err(), "{} should fail", src);
+        }
+    }
+
+    #[test]
+    fn test_comparison() {
+        let mut i = Interpreter::new();
+        let cases = [
+            ("(= 1 1 1)", "#t"),
+            ("(= 1 1.0 1/1)", "#t"),
+            ("(= 1 2)", "#f"),
+            ("(< 1 2 3)", "#t"),
+            ("(< 1 3 2)", "#f"),
+            ("(< 1/3 0.34)", "#t"),
+            ("(> 3 2 1)", "#t"),
+            ("(> 3 3)", "#f"),
+            ("(<= 1 1 2)", "#t"),
+            ("(>= 2 2 3)", "#f"),
+            ("(< 9223372036854775807 9223372036854775808)", "#t"),
+            ("(= +nan.0 +nan.0)", "#f"),
+            ("(< -inf.0 -1 +inf.0)", "#t"),
+            ("(zero? 0.0)", "#t"),
+            ("(positive? 1/2)", "#t"),
+            ("(negative? -0.5)", "#t"),
+            ("(odd? 3)", "#t"),
+            ("(even? -4)", "#t"),
+            ("(even? 0)", "#t"),
+            ("(odd? 12345678901234567891)", "#t"),
+            ("(integer? 3.0)", "#t"),
+            ("(integer? 3/2)", "#f"),
+            ("(integer? (quote a))", "#f"),
+        ];
+        for &(src, expected) in cases.iter() {
+            assert_eval(&mut i, src, expected);
+        }
+        assert!(eval_str(&mut i, "(< 1 (quote a))").is_err());
+        assert!(eval_str(&mut i, "(odd? 1.5)").is_err());
+    }
+
+    #[test]
+    fn test_call_cc() {
+        let mut i = Interpreter::new();
+        assert_eval(&mut i, "(+ 1 (call/cc (lambda (k) (+ 10 (k 2)))))", "3");
+        assert_eval(&mut i, "(call-with-current-continuation (lambda (k) 5))", "5");
+        assert_eval(&mut i, "(let loop ((l (quote (1 2 x 4))) (n 0)) \
+                                 (call/cc (lambda (exit) \
+                                   (if (symbol? (car l)) (exit n) (loop (cdr l) (+ n 1))))))", "2");
+
+        // re-entering a continuation runs the rest of the let body again
+        assert_eval(&mut i, "(let ((count 0) (k #f)) \
+                               (let ((v (call/cc (lambda (c) (set! k c) 0)))) \
+                                 (set! count (+ count 1)) \
+                                 (if (< v 3) (k (+ v 1)) (list v count))))", "(3 4)");
+
+        eval_str(&mut i, "(define (make-gen lst) \
+                            (define return #f) \
+                            (define resume #f) \
+                            (define (start) \
+                              (let loop ((l lst)) \
+                                (if (null? l) \
+                                    (return (quote done)) \
+                                    (begin (call/cc (lambda (k) (set! resume k) (return (car l)))) \
+                                           (loop (cdr l)))))) \
+                            (lambda () \
+                              (call/cc (lambda (r) \
+                                (set! return r) \
+                                (if resume (resume #f) (start))))))").unwrap();
+        eval_str(&mut i, "(define g (make-gen (quote (a b c))))").unwrap();
+        assert_eval(&mut i, "(list (g) (g) (g) (g) (g))", "(a b c done done)");
+
+        // the same over a vector, resumed inside vector-for-each
+        eval_str(&mut i, "(define (make-vector-gen v) \
+                            (define return #f) \
+                            (define resume #f) \
+                            (define (start) \
+                              (vector-for-each \
+                                (lambda (x) (call/cc (lambda (k) (set! resume k) (return x)))) \
+                                v) \
+                              (return (quote done))) \
+                            (lambda () \
+                              (call/cc (lambda (r) \
+                                (set! return r) \
+                                (if resume (resume #f) (start))))))").unwrap();
+        eval_str(&mut i, "(define vg (make-vector-gen (vector 1 2 3)))").unwrap();
+        assert_eval(&mut i, "(list (vg) (vg) (vg) (vg))", "(1 2 3 done)");
+        // each re-entry of a vector-map gets a vector of its own
+        assert_eval(&mut i, "(let* ((k #f) (n 0) \
+                                    (v (vector-map (lambda (x) (call/cc (lambda (c) (if (= x 2) (set! k c)) x))) \
+                                                   (vector 1 2 3)))) \
+                               (set! n (+ n 1)) \
+                               (if (< n 3) (k (* n 10)) v))", "#(1 20 3)");
+        assert_eval(&mut i, "(string-map (lambda (c) (call/cc (lambda (k) (k (char-upcase c))))) \"ab\")", "\"AB\"");
+        assert_eval(&mut i, "(let ((t (make-hash-table))) \
+                               (hash-table-set! t 1 10) \
+                               (hash-table-set! t 2 20) \
+                               (hash-table-fold t (lambda (k v acc) (+ k v acc)) 0))", "33");
+
+        // re-entering a top level form after it has finished
+        eval_str(&mut i, "(define k #f)").unwrap();
+        eval_str(&mut i, "(define n (+ 100 (call/cc (lambda (c) (set! k c) 1))))").unwrap();
+        eval_str(&mut i, "(k 2)").unwrap();
+        assert_eval(&mut i, "n", "102");
+
+        // escaping from evaluation nested in a primitive call
+        assert_eval(&mut i, "(+ 1 (call/cc (lambda (c) (set! k c) (eval (quote (k 5))))))", "6");
+        assert!(i.stack.is_empty());
+        assert!(eval_str(&mut i, "(call/cc (lambda (c) (c 1 2)))").is_err());
+    }
+
+    #[test]
+    fn test_dynamic_wind() {
+        let mut i = Interpreter::new();
+        eval_str(&mut i, "(define path (quote ()))").unwrap();
+        eval_str(&mut i, "(define (add s) (set! path (cons s path)))").unwrap();
+        assert_eval(&mut i, "(dynamic-wind (lambda () (add 1)) (lambda () (add 2) 3) (lambda () (add 4)))", "3");
+        assert_eval(&mut i, "path", "(4 2 1)");
+
+        // leaving and re-entering the extent of the thunk
+        eval_str(&mut i, "(set! path (quote ()))").unwrap();
+        eval_str(&mut i, "(let ((c #f) (n 0)) \
+                            (dynamic-wind \
+                              (lambda () (add (quote connect))) \
+                              (lambda () (add (call/cc (lambda (c0) (set! c c0) (quote talk1))))) \
+                              (lambda () (add (quote disconnect)))) \
+                            (set! n (+ n 1)) \
+                            (if (< n 2) (c (quote talk2)) n))").unwrap();
+        assert_eval(&mut i, "path", "(disconnect talk2 connect disconnect talk1 connect)");
+
+        eval_str(&mut i, "(set! path (quote ()))").unwrap();
+        assert_eval(&mut i, "(call/cc (lambda (k) \
+                               (dynamic-wind (lambda () (add (quote in))) \
+                                             (lambda () (k (quote out)) (add (quote never))) \
+                                             (lambda () (add (quote after))))))", "out");
+        assert_eval(&mut i, "path", "(after in)");
+
+        // errors leave the extent too
+        eval_str(&mut i, "(set! path (quote ()))").unwrap();
+        assert!(eval_str(&mut i, "(dynamic-wind (lambda () 0) (lambda () (car 1)) (lambda () (add (quote cleanup))))").is_err());
+        assert_eval(&mut i, "path", "(cleanup)");
+        assert!(i.winders.is_nil());
+    }
+
+    #[test]
+    fn test_exceptions() {
+        let mut i = Interpreter::new();
+        let cases = [
+            ("(with-exception-handler (lambda (e) 42) (lambda () (+ (raise-continuable (quote oops)) 1)))", "43"),
+            ("(call/cc (lambda (k) (with-exception-handler (lambda (e) (k (list (quote caught) e))) \
+                                                             (lambda () (raise (quote boom))))))", "(caught boom)"),
+            ("(guard (e (#t (list (quote caught) e))) (raise 1))", "(caught 1)"),
+            ("(guard (e ((pair? e) e) ((symbol? e) (list e))) (raise (quote sym)))", "(sym)"),
+            ("(guard (e ((and (pair? e) (car e)) => (lambda (x) (+ x 1)))) (raise (list 41)))", "42"),
+            ("(guard (e ((symbol? e) 0) (else (quote other))) (raise 5))", "other"),
+            ("(guard (e (#f 0)) (+ 1 2))", "3"),
+            ("(guard (e ((error-object? e) (error-object-message e))) (error \"bad thing\" 1 2))", "\"bad thing\""),
+            ("(guard (e ((error-object? e) (error-object-irritants e))) (error \"bad thing\" 1 (quote x)))", "(1 x)"),
+            // errors found by the interpreter are error objects too
+            ("(guard (e ((error-object? e) (error-object-message e))) (car 1))",
+             "\"Wrong argument type, wanted: pair, got: integer\""),
+            ("(guard (e ((error-object? e) (error-object-message e))) (undefined-variable))",
+             "\"Couldn't find symbol undefined-variable\""),
+            ("(guard (e (#t (list (read-error? e) (file-error? e)))) (/ 1 0))", "(#f #f)"),
+            // an inner guard with no matching clause passes it on
+            ("(guard (e ((symbol? e) (quote outer))) (guard (e2 ((number? e2) (quote inner))) (raise (quote s))))", "outer"),
+            ("(with-exception-handler (lambda (e) 10) \
+                (lambda () (guard (e ((pair? e) 0)) (+ 1 (raise-continuable (quote c))))))", "11"),
+            // also when it was raised in a run nested in a primitive
+            ("(guard (e (#t (list (quote outer) e))) \
+                (guard (e ((string? e) (quote str))) (vector-for-each (lambda (x) (raise 42)) (vector 1))))", "(outer 42)"),
+            ("(guard (e (#t (list (quote outer) e))) (guard (e ((string? e) (quote str))) (eval (quote (raise 42)))))",
+             "(outer 42)"),
+        ];
+        for &(src, expected) in cases.iter() {
+            assert_eval(&mut i, src, expected);
+        }
+
+        // handlers run outside their own extent and after thunks run when
+        // guard leaves the body
+        eval_str(&mut i, "(define log (quote ()))").unwrap();
+        assert_eval(&mut i, "(guard (e (#t (set! log (cons e log)) log)) \
+                               (dynamic-wind (lambda () #f) \
+                                             (lambda () (raise (quote x))) \
+                                             (lambda () (set! log (cons (quote after) log)))))", "(x after)");
+        assert!(i.handlers.is_nil() && i.winders.is_nil());
+
+        match eval_str(&mut i, "(with-exception-handler (lambda (e) 0) (lambda () (raise (quote oops))))") {
+            Result::Err(e) => assert!(matches!(e.err_type, ErrType::HandlerReturned), "{}", e),
+            Result::Ok(_) => panic!("handler returned from raise"),
+        }
+        let err = eval_str(&mut i, "(error \"went wrong:\" 42)").err().unwrap();
+        assert_eq!(err.to_string(), "went wrong: 42");
+        // and reaches the top level as it was
+        for src in ["(guard (e ((string? e) (quote str))) (vector-map (lambda (x) (car x)) (vector 1)))",
+                    "(guard (e ((string? e) (quote str))) (eval (quote (car 1))))"] {
+            let err = eval_str(&mut i, src).err().unwrap();
+            assert_eq!(err.err_type.to_string(), "Wrong argument type, wanted: pair, got: integer", "{}", src);
+        }
+        let err = eval_str(&mut i, "(raise (quote up))").err().unwrap();
+        assert_eq!(err.to_string(), "Uncaught exception: up");
+        assert!(i.handlers.is_nil());
+    }
+
+    #[test]
+    fn test_exit() {
+        let mut i = Interpreter::new();
+        i.set_command_line(vec!["script.scm".to_string(), "arg".to_string()]);
+        assert_eval(&mut i, "(command-line)", "(\"script.scm\" \"arg\")");
+        eval_str(&mut i, "(define log (quote ()))").unwrap();
+        // handlers don't catch it, after thunks run
+        let err = eval_str(&mut i, "(guard (e (#t 0)) \
+                                      (dynamic-wind (lambda () #f) \
+                                                    (lambda () (exit 3)) \
+                                                    (lambda () (set! log (quote after)))))").err().unwrap();
+        assert!(matches!(err.err_type, ErrType::Exit(3)), "{}", err);
+        assert_eval(&mut i, "log", "after");
+        for &(src, status) in [("(exit)", 0), ("(exit #t)", 0), ("(exit #f)", 1), ("(exit -7)", -7),
+                               ("(exit 2147483647)", i32::MAX), ("(exit \"bye\")", 1)].iter() {
+            assert!(matches!(eval_str(&mut i, src).err().unwrap().err_type, ErrType::Exit(s) if s == status), "{}", src);
+        }
+        // statuses that don't fit aren't cut down to ones that do
+        for src in ["(exit 4294967297)", "(exit -2147483649)", "(exit 100000000000000000000)"] {
+            let err = eval_str(&mut i, src).err().unwrap();
+            assert!(matches!(err.err_type, ErrType::WrongType{..}), "{}: {}", src, err);
+        }
+    }
+
+    #[test]
+    fn test_interrupt() {
+        let mut i = Interpreter::new();
+        let flag = i.interrupt_flag();
+        let interrupter = ::std::thread::spawn(move || {
+            ::std::thread::sleep(::std::time::Duration::from_millis(50));
+            flag.store(true, atomic::Ordering::Relaxed);
+        });
+        let err = eval_str(&mut i, "(guard (e (#t 0)) (let loop () (loop)))").err().unwrap();
+        interrupter.join().unwrap();
+        assert!(matches!(err.err_type, ErrType::Interrupted), "{}", err);
+        assert!(i.handlers.is_nil());
+        assert_eval(&mut i, "(+ 1 2)", "3");
+    }
+
+    #[test]
+    fn test_gc_continuations() {
+        let mut i = Interpreter::new();
+        // the continuation refers to the frame holding it
+        let k = eval_str(&mut i, "(let ((k #f)) (call/cc (lambda (c) (set! k c))) k)").unwrap();
+        let weak = Rc::downgrade(&k);
+        i.gc();
+        assert!(weak.upgrade().is_some());
+        drop(k);
+        i.gc();
+        assert!(weak.upgrade().is_none());
+    }
+
+    #[test]
+    fn test_special_forms() {
+        let mut i = Interpreter::new();
+        assert_eval(&mut i, "(quote foo)", "foo");
+        assert_eval(&mut i, "(if #t 1 2)", "1");
+        assert_eval(&mut i, "(if #f 1 2)", "2");
+        assert_eval(&mut i, "(begin 1 2 3)", "3");
+        assert_eval(&mut i, "((lambda (x y) (+ x y)) 1 2)", "3");
+        assert_eval(&mut i, "(and 1 #f 2)", "#f");
+        assert_eval(&mut i, "(and 1 2)", "2");
+        assert_eval(&mut i, "(or #f 3)", "3");
+        assert_eval(&mut i, "(or)", "#f");
+
+        eval_str(&mut i, "(define x 1)").unwrap();
+        eval_str(&mut i, "(set! x 10)").unwrap();
+        assert_eval(&mut i, "x", "10");
+
+        eval_str(&mut i, "(define (add3 a b c) (+ a (+ b c)))").unwrap();
+        assert_eval(&mut i, "(add3 1 2 3)", "6");
+    }
+
+    #[test]
+    fn test_binding_forms() {
+        let mut i = Interpreter::new();
+        eval_str(&mut i, "(define x 1)").unwrap();
+        assert_eval(&mut i, "(let ((x 2) (y x)) y)", "1");
+        assert_eval(&mut i, "(let* ((x 2) (y x)) y)", "2");
+        assert_eval(&mut i, "(letrec ((f (lambda (n) (case n ((0) 0) (else (g n))))) (g (lambda (n) (f 0)))) (f 5))", "0");
+        assert_eval(&mut i, "(let loop ((n 3) (acc 0)) (case n ((0) acc) (else (loop (+ n -1) (+ acc n)))))", "6");
+    }
+
+    #[test]
+    fn test_conditionals() {
+        let mut i = Interpreter::new();
+        assert_eval(&mut i, "(cond (#f 1) (2 3))", "3");
+        assert_eval(&mut i, "(cond (#f 1) (else 4))", "4");
+        assert_eval(&mut i, "(cond (5))", "5");
+        assert_eval(&mut i, "(cond (1 => (lambda (x) (+ x 1))))", "2");
+        assert_eval(&mut i, "(case 3 ((1 2) 0) ((3 4) 1) (else 2))", "1");
+        assert_eval(&mut i, "(case (quote z) ((a) 0) (else 2))", "2");
+    }
+
+    #[test]
+    fn test_bad_syntax() {
+        let mut i = Interpreter::new();
+        for s in &["(if)", "(lambda)", "(lambda (1) 2)", "(let ((x)) x)", "(cond 1)", "(quote)", "(set! 1 2)"] {
+            match eval_str(&mut i, s).map_err(|e| e.err_type) {
+                Result::Err(ErrType::BadSyntax(_)) => {},
+                _ => panic!("{} should be bad syntax", s),
+            }
+        }
+    }
+
+    #[test]
+    fn test_tail_calls() {
+        let mut i = Interpreter::new();
+        eval_str(&mut i, "(define (count n acc) (case n ((0) acc) (else (count (+ n -1) (+ acc 1)))))").unwrap();
+        assert_eval(&mut i, "(count 1000000 0)", "1000000");
+        assert_eq!(i.stack.len(), 0);
+        assert!(i.environment.is_global());
+
+        assert_eval(&mut i, "(let loop ((n 100000)) (if (case n ((0) #t) (else #f)) (quote done) (loop (+ n -1))))", "done");
+        assert!(i.environment.is_global());
+    }
+
+    #[test]
+    fn test_error_unwinds() {
+        let mut i = Interpreter::new();
+        eval_str(&mut i, "(define (f x) (let ((y x)) (g y)))").unwrap();
+        assert!(eval_str(&mut i, "(f 1)").is_err());
+        assert_eq!(i.stack.len(), 0);
+        assert!(i.environment.is_global());
+        assert!(i.fn_stack.is_empty());
+    }
+
+    #[test]
+    fn test_error_locations() {
+        let mut i = Interpreter::new();
+        let text = "(define (f x)\n  (car x))\n(f\t(+ 1 2))\n(g 1)\n(if)";
+        let source = Rc::new(Source::new("test.scm", text.to_string()));
+        let data = Reader::new(text).read_all(&mut i).unwrap();
+        let errs: Vec<Err> = data.into_iter().filter_map(|obj| i.eval_source(obj, &source).err()).collect();
+        let spans: Vec<(u32, u32)> = errs.iter().map(|e| {
+            let span = e.location().unwrap().span;
+            (span.line, span.column)
+        }).collect();
+        assert_eq!(spans, vec![(2, 3), (4, 2), (5, 1)]);
+
+        assert_eq!(errs[0].to_string(), "Wrong argument type, wanted: pair, got: integer\n \
+                                         --> test.scm:2:3\n  |\n2 |   (car x))\n  |   ^^^^^^^\n\
+                                         in function:\n0: f\n1: car");
+        assert!(errs[1].to_string().ends_with("4 | (g 1)\n  |  ^"));
+
+        let err = match Reader::new("(a\n  b ]").read_all(&mut i) {
+            Result::Err(err) => Err::read(err, &source),
+            Result::Ok(_) => panic!("read invalid syntax"),
+        };
+        assert_eq!(err.location().unwrap().span.line, 2);
+    }
+
+    #[test]
+    fn test_pairs() {
+        let mut i = Interpreter::new();
+        assert_eval(&mut i, "(cons 1 2)", "(1 . 2)");
+        assert_eval(&mut i, "(cons 1 (cons 2 (quote ())))", "(1 2)");
+        assert_eval(&mut i, "(quote (1 2 . 3))", "(1 2 . 3)");
+        assert_eval(&mut i, "(car (quote (a . b)))", "a");
+        assert_eval(&mut i, "(cdr (quote (a . b)))", "b");
+        assert_eval(&mut i, "(list)", "()");
+        assert_eval(&mut i, "(pair? (quote ()))", "#f");
+        assert_eval(&mut i, "(null? (quote ()))", "#t");
+        assert_eval(&mut i, "(list? (quote (1 . 2)))", "#f");
+
+        eval_str(&mut i, "(define x (list 1 2 3))").unwrap();
+        eval_str(&mut i, "(define y (cdr x))").unwrap();
+        eval_str(&mut i, "(set-car! y 20)").unwrap();
+        assert_eval(&mut i, "x", "(1 20 3)");
+        eval_str(&mut i, "(set-cdr! (cdr y) x)").unwrap();
+        assert_eval(&mut i, "(list? x)", "#f");
+        assert_eval(&mut i, "(car (cdr (cdr (cdr x))))", "1");
+
+        assert!(eval_str(&mut i, "(car 1)").is_err());
+    }
+
+    #[test]
+    fn test_rest_params() {
+        let mut i = Interpreter::new();
+        assert_eval(&mut i, "((lambda args args) 1 2)", "(1 2)");
+        assert_eval(&mut i, "((lambda (a . rest) rest) 1 2 3)", "(2 3)");
+        eval_str(&mut i, "(define (f a b . c) c)").unwrap();
+        assert_eval(&mut i, "(f 1 2)", "()");
+        assert!(eval_str(&mut i, "(f 1)").is_err());
+    }
+
+    #[test]
+    fn test_mark_long_list() {
+        let mut i = Interpreter::new();
+        eval_str(&mut i, "(define l (quote ()))").unwrap();
+        eval_str(&mut i, "(let loop ((n 200000)) (if (case n ((0) #t) (else #f)) l (begin (set! l (cons n l)) (loop (+ n -1)))))").unwrap();
+        i.gc();
+        assert_eval(&mut i, "(car l)", "1");
+    }
+
+    #[test]
+    fn test_closures() {
+        let mut i = Interpreter::new();
+        eval_str(&mut i, "(define (make-counter) (let ((n 0)) (lambda () (set! n (+ n 1)) n)))").unwrap();
+        eval_str(&mut i, "(define c1 (make-counter))").unwrap();
+        eval_str(&mut i, "(define c2 (make-counter))").unwrap();
+        eval_str(&mut i, "(c1)").unwrap();
+        assert_eval(&mut i, "(c1)", "2");
+        assert_eval(&mut i, "(c2)", "1");
+
+        // closures over the same frame see each other's set!
+        eval_str(&mut i, "(define (make-box v) (list (lambda () v) (lambda (x) (set! v x))))").unwrap();
+        eval_str(&mut i, "(define b (make-box 1))").unwrap();
+        eval_str(&mut i, "((car (cdr b)) 5)").unwrap();
+        assert_eval(&mut i, "((car b))", "5");
+
+        // a closure created outside tail position keeps its environment
+        assert_eval(&mut i, "(let ((f ((lambda (x) (let ((g (lambda () x))) g)) 7))) (f))", "7");
+        assert_eval(&mut i, "(((lambda (x) (lambda (y) (cons x y))) 1) 2)", "(1 . 2)");
+    }
+
+    #[test]
+    fn test_mutual_recursion() {
+        let mut i = Interpreter::new();
+        eval_str(&mut i, "(define (parity n) \
+                            (define (ev? n) (case n ((0) #t) (else (od? (+ n -1))))) \
+                            (define (od? n) (case n ((0) #f) (else (ev? (+ n -1))))) \
+                            (ev? n))").unwrap();
+        assert_eval(&mut i, "(parity 10001)", "#f");
+        assert_eval(&mut i, "(letrec ((ev? (lambda (n) (if (null? n) #t (od? (cdr n))))) (od? (lambda (n) (if (null? n) #f (ev? (cdr n)))))) (ev? (list 1 2 3 4)))", "#t");
+    }
+
+    #[test]
+    fn test_escaping_closures() {
+        let mut i = Interpreter::new();
+        eval_str(&mut i, "(define fs (let loop ((n 3) (acc (quote ()))) (if (case n ((0) #t) (else #f)) acc (loop (+ n -1) (cons (lambda () n) acc)))))").unwrap();
+        assert_eval(&mut i, "(list ((car fs)) ((car (cdr fs))) ((car (cdr (cdr fs)))))", "(1 2 3)");
+
+        eval_str(&mut i, "(define cell (cons 0 0))").unwrap();
+        eval_str(&mut i, "(let ((secret 42)) (set-car! cell (lambda () secret)))").unwrap();
+        i.gc();
+        assert_eval(&mut i, "((car cell))", "42");
+    }
+
+    #[test]
+    fn test_gc_frame_cycles() {
+        let mut i = Interpreter::new();
+        // the frame of a named let and the loop procedure refer to each other
+        let proc = eval_str(&mut i, "(let loop ((n 0)) loop)").unwrap();
+        let frame = match proc.object_type {
+            Type::Procedure(ref p) => match **p {
+                Procedure::Lambda(ref l) => Rc::downgrade(&l.env),
+                _ => panic!("not a lambda"),
+            },
+            _ => panic!("not a procedure"),
+        };
+        i.gc();
+        assert!(frame.upgrade().is_some());
+        drop(proc);
+        i.gc();
+        assert!(frame.upgrade().is_none());
+    }
+
+    #[test]
+    fn test_scoping() {
+        let mut i = Interpreter::new();
+        assert_eval(&mut i, "(let ((x 1)) (let ((y 2)) (let ((x 3)) (cons x y))))", "(3 . 2)");
+        assert_eval(&mut i, "(let ((x 1)) (let ((y 2)) x))", "1");
+        assert_eval(&mut i, "((lambda (x) (define y (+ x 1)) (set! x y) (cons x y)) 1)", "(2 . 2)");
+        assert_eval(&mut i, "(let* ((x 1) (f (lambda () x)) (x 2)) (cons (f) x))", "(1 . 2)");
+
+        // globals can be referred to before they are defined
+        eval_str(&mut i, "(define (get) later)").unwrap();
+        assert!(eval_str(&mut i, "(get)").is_err());
+        eval_str(&mut i, "(define later 5)").unwrap();
+        assert_eval(&mut i, "(get)", "5");
+        assert!(eval_str(&mut i, "(set! never-defined 1)").is_err());
+    }
+
+    #[test]
+    fn test_symbols() {
+        let mut i = Interpreter::new();
+        assert_eval(&mut i, "(eq? (quote abc) (quote abc))", "#t");
+        assert_eval(&mut i, "(eq? (quote abc) (string->symbol \"abc\"))", "#t");
+        assert_eval(&mut i, "(eq? (quote abc) (quote abd))", "#f");
+        assert_eval(&mut i, "(symbol->string (quote abc))", "\"abc\"");
+        assert_eval(&mut i, "(symbol-interned? (quote abc))", "#t");
+
+        eval_str(&mut i, "(define g (gensym))").unwrap();
+        assert_eval(&mut i, "(eq? g g)", "#t");
+        assert_eval(&mut i, "(symbol-interned? g)", "#f");
+        assert_eval(&mut i, "(eq? g (string->symbol (symbol->string g)))", "#f");
+        assert_eval(&mut i, "(eq? (gensym \"x\") (gensym \"x\"))", "#f");
+        assert!(eval_str(&mut i, "(symbol->string 1)").is_err());
     }
 }
@@ -0,0 +1,137 @@
+use interpreter::Interpreter;
+use types::{Object, Type, HeapObject, Procedure};
+use record::RecordProc;
+use error::Err;
+use std::option::Option;
+use std::result::Result;
+
+// The fewest and most arguments a procedure takes, with None for no limit.
+fn arity(procedure: &Procedure) -> (usize, Option<usize>) {
+    match *procedure {
+        Procedure::Lambda(ref lambda) => {
+            let required = lambda.code.required;
+            (required, if lambda.code.rest {Option::None} else {Option::Some(required)})
+        },
+        Procedure::Primitive(&(_, _, min, max)) |
+        Procedure::Control(&(_, _, min, max)) => (min, max),
+        Procedure::Continuation(_) => (1, Option::Some(1)),
+        Procedure::Record(ref proc) => {
+            let n = match *proc {
+                RecordProc::Constructor(_, ref fields) => fields.len(),
+                RecordProc::Predicate(_) | RecordProc::Accessor(..) => 1,
+                RecordProc::Modifier(..) => 2,
+            };
+            (n, Option::Some(n))
+        },
+    }
+}
+
+fn arguments(n: usize) -> String {
+    format!("{} argument{}", n, if n == 1 {""} else {"s"})
+}
+
+fn article(noun: &str) -> &'static str {
+    if noun.starts_with(['a', 'e', 'i', 'o', 'u']) {"an"} else {"a"}
+}
+
+// What describe prints about obj: its type, and for procedures what they
+// take and their docstring.
+pub fn description(obj: &Object) -> String {
+    let procedure = match obj.object_type {
+        Type::Procedure(ref procedure) => procedure,
+        _ => {
+            let kind = obj.get_type_string();
+            return format!("{} is {} {}", obj, article(&kind), kind);
+        },
+    };
+    // builtins go by the name they are bound to
+    let (name, kind) = match **procedure {
+        Procedure::Primitive(&(name, ..)) |
+        Procedure::Control(&(name, ..)) => (name.to_string(), "builtin procedure"),
+        Procedure::Continuation(_) => (obj.to_string(), "continuation"),
+        Procedure::Lambda(_) | Procedure::Record(_) => (obj.to_string(), "procedure"),
+    };
+    let mut description = format!("{} is {} {} taking ", name, article(kind), kind);
+    description.push_str(&match arity(procedure) {
+        (0, Option::None) => "any number of arguments".to_string(),
+        (min, Option::None) => format!("at least {}", arguments(min)),
+        (min, Option::Some(max)) if min == max => arguments(min),
+        (min, Option::Some(max)) => format!("{} to {} arguments", min, max),
+    });
+    if let Procedure::Lambda(ref lambda) = **procedure {
+        if let Option::Some(ref doc) = lambda.code.doc {
+            description.push('\n');
+            description.push_str(doc);
+        }
+    }
+    description
+}
+
+// apropos and describe, for finding your way around at the REPL.
+impl Interpreter {
+    // (apropos str) prints the names bound at the top level that contain
+    // str, with what they are bound to.
+    pub fn apropos(&mut self, args: &[HeapObject]) -> Result<HeapObject, Err> {
+        let part = match args[0].object_type {
+            Type::String(ref s) => s.borrow().clone(),
+            Type::Symbol(ref s) => s.name().to_string(),
+            _ => return Result::Err(self.wrong_type("string", &args[0])),
+        };
+        for (name, kind) in self.bindings().into_iter().filter(|(name, _)| name.contains(&part)) {
+            println!("{} ({})", name, kind.name());
+        }
+        Result::Ok(self.new_nil())
+    }
+
+    pub fn describe(&mut self, args: &[HeapObject]) -> Result<HeapObject, Err> {
+        println!("{}", description(&args[0]));
+        Result::Ok(self.new_nil())
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use interpreter::{Interpreter, BindingKind};
+    use test_util::{eval_obj, eval_str};
+    use super::description;
+
+    #[test]
+    fn test_describe() {
+        let mut i = Interpreter::new();
+        let cases = [
+            ("5", "5 is an integer"),
+            ("(list 1 2)", "(1 2) is a pair"),
+            ("car", "car is a builtin procedure taking 1 argument"),
+            ("+", "+ is a builtin procedure taking any number of arguments"),
+            ("vector-copy", "vector-copy is a builtin procedure taking 1 to 3 arguments"),
+            ("vector-map", "vector-map is a builtin procedure taking at least 2 arguments"),
+            ("command-line", "command-line is a builtin procedure taking 0 arguments"),
+            ("inexact->exact", "inexact->exact is a builtin procedure taking 1 argument"),
+            ("(call/cc (lambda (k) k))", "#<procedure> is a continuation taking 1 argument"),
+            ("(lambda (x . rest) \"Adds things up.\" x)",
+             "#<procedure> is a procedure taking at least 1 argument\nAdds things up."),
+            // a string alone is the body, not a docstring
+            ("(lambda (x y) \"result\")", "#<procedure> is a procedure taking 2 arguments"),
+            ("(let () (define-record-type point (make-point x y) point? (x px) (y py)) make-point)",
+             "#<procedure> is a procedure taking 2 arguments"),
+        ];
+        for &(src, expected) in cases.iter() {
+            let obj = eval_obj(&mut i, src);
+            assert_eq!(description(&obj), expected, "{}", src);
+        }
+    }
+
+    #[test]
+    fn test_bindings() {
+        let mut i = Interpreter::new();
+        eval_str(&mut i, "(define answer 42)").unwrap();
+        let bindings = i.bindings();
+        let kind = |name: &str| bindings.iter().find(|b| b.0 == name).map(|b| b.1);
+        assert_eq!(kind("answer"), Option::Some(BindingKind::Variable));
+        assert_eq!(kind("car"), Option::Some(BindingKind::Procedure));
+        assert_eq!(kind("lambda"), Option::Some(BindingKind::Syntax));
+        assert_eq!(kind("define-record-type"), Option::Some(BindingKind::Macro));
+        assert_eq!(kind("no-such-name"), Option::None);
+        assert!(bindings.windows(2).all(|w| w[0].0 < w[1].0));
+    }
+}
@@ -1,6 +1,24 @@
-#![feature(rc_counts)]
+extern crate num_bigint;
+extern crate num_rational;
+extern crate num_integer;
+extern crate num_traits;
+
 pub mod types;
 pub mod interpreter;
 pub mod error;
+pub mod number;
+pub mod hashtable;
 mod environment;
+mod compile;
+mod expand;
+mod vector;
+mod string;
+pub mod record;
+pub mod printer;
+pub mod format;
+pub mod introspect;
+mod load;
 pub mod parse;
+pub mod symbol;
+#[cfg(test)]
+mod test_util;
@@ -0,0 +1,177 @@
+use interpreter::Interpreter;
+use types::{Type, HeapObject};
+use parse::{Reader, shebang_len};
+use error::{Err, Source};
+use std::fs;
+use std::io;
+use std::path::{Path, PathBuf};
+use std::rc::Rc;
+use std::option::Option;
+use std::result::Result;
+
+// name relative to the directory of the file at base, if there is one.
+pub fn resolve(base: Option<&Path>, name: &str) -> PathBuf {
+    match base.and_then(Path::parent) {
+        Option::Some(dir) if Path::new(name).is_relative() => dir.join(name),
+        _ => PathBuf::from(name),
+    }
+}
+
+// Loading source files, and reading the ones include takes its data from.
+impl Interpreter {
+    fn io_error(&self, path: &Path, e: io::Error) -> Err {
+        self.file_error(format!("{}: {}", path.display(), e))
+    }
+
+    // The canonical form of path, unless it is one of the files in chain,
+    // each of which is being loaded or included by the one before it.
+    pub fn check_cycle(&self, path: &Path, chain: &[PathBuf]) -> Result<PathBuf, Err> {
+        let canonical = fs::canonicalize(path).map_err(|e| self.io_error(path, e))?;
+        match chain.iter().position(|p| *p == canonical) {
+            Option::Some(i) => {
+                let cycle: Vec<String> = chain[i..].iter().chain(Option::Some(&canonical))
+                    .map(|p| p.display().to_string())
+                    .collect();
+                let message = format!("Include cycle: {}", cycle.join(" -> "));
+                Result::Err(self.file_error(message))
+            },
+            Option::None => Result::Ok(canonical),
+        }
+    }
+
+    // Every datum in the file at path.
+    pub fn read_file(&mut self, path: &Path, fold_case: bool) -> Result<(Vec<HeapObject>, Rc<Source>), Err> {
+        let text = fs::read_to_string(path).map_err(|e| self.io_error(path, e))?;
+        let source = Rc::new(Source::new(&path.display().to_string(), text));
+        let mut reader = Reader::new(&source.text);
+        reader.set_fold_case(fold_case);
+        self.gc_disable();
+        let data = reader.read_all(self).map_err(|e| Err::read(e, &source));
+        self.gc_enable();
+        Result::Ok((data?, source))
+    }
+
+    // Reads and evaluates the data in text in order, up to the first
+    // error. A #! line at the start is skipped, so scripts can be made
+    // executable.
+    pub fn eval_all(&mut self, name: &str, mut text: String) -> Result<(), Err> {
+        // the line break stays so lines are numbered as in the file
+        text.replace_range(..shebang_len(&text), "");
+        let source = Rc::new(Source::new(name, text));
+        let mut reader = Reader::new(&source.text);
+        loop {
+            self.gc_disable();
+            let datum = reader.read(self).map_err(|e| Err::read(e, &source));
+            self.gc_enable();
+            match datum? {
+                Option::Some(datum) => self.eval_source(datum, &source)?,
+                Option::None => return Result::Ok(()),
+            };
+        }
+    }
+
+    // Evaluates the file at path, which is relative to the file being
+    // loaded if there is one. Errors in it are reported against it.
+    pub fn load_file(&mut self, path: &str) -> Result<(), Err> {
+        let path = resolve(self.loading().last().map(PathBuf::as_path), path);
+        let loading = self.loading().clone();
+        let canonical = self.check_cycle(&path, &loading)?;
+        let text = fs::read_to_string(&path).map_err(|e| self.io_error(&path, e))?;
+        self.loading().push(canonical);
+        let res = self.eval_all(&path.display().to_string(), text);
+        self.loading().pop();
+        res
+    }
+
+    // (load path)
+    pub fn load(&mut self, args: &[HeapObject]) -> Result<HeapObject, Err> {
+        let path = match args[0].object_type {
+            Type::String(ref s) => s.borrow().clone(),
+            _ => return Result::Err(self.wrong_type("string", &args[0])),
+        };
+        self.load_file(&path)?;
+        Result::Ok(self.new_nil())
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use interpreter::Interpreter;
+    use error::ErrType;
+    use test_util::{eval_str, assert_eval};
+    use std::env;
+    use std::fs;
+    use std::path::PathBuf;
+    use std::process;
+
+    // A directory of its own for each test, with files in it.
+    fn dir(name: &str, files: &[(&str, &str)]) -> PathBuf {
+        let dir = env::temp_dir().join(format!("skeem-{}-{}", name, process::id()));
+        let _ = fs::remove_dir_all(&dir);
+        fs::create_dir_all(dir.join("lib")).unwrap();
+        for &(file, text) in files {
+            fs::write(dir.join(file), text).unwrap();
+        }
+        dir
+    }
+
+    #[test]
+    fn test_load() {
+        let dir = dir("load", &[
+            ("main.scm", "(define x 1)\n(load \"lib/util.scm\")\n(define z (+ y 1))"),
+            // relative to lib, where util.scm is
+            ("lib/util.scm", "(load \"more.scm\")"),
+            ("lib/more.scm", "#!/usr/bin/env skeem\n(define y (+ x 1))"),
+            ("bad.scm", "(define a 1)\n(car a)"),
+            ("loop.scm", "(load \"loop.scm\")"),
+        ]);
+        let mut i = Interpreter::new();
+        let main = dir.join("main.scm").display().to_string();
+        eval_str(&mut i, &format!("(load {:?})", main)).unwrap();
+        assert_eval(&mut i, "(list x y z)", "(1 2 3)");
+
+        let bad = dir.join("bad.scm").display().to_string();
+        let err = eval_str(&mut i, &format!("(load {:?})", bad)).err().unwrap();
+        assert!(err.to_string().contains(&format!("--> {}:2:1", bad)), "{}", err);
+        assert_eval(&mut i, "a", "1");
+
+        let err = i.load_file(&dir.join("loop.scm").display().to_string()).err().unwrap();
+        assert!(err.to_string().starts_with("Include cycle:"), "{}", err);
+        assert_eval(&mut i, "(guard (e ((file-error? e) 'missing)) (load \"/no/such/file.scm\"))", "missing");
+        fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn test_include() {
+        let dir = dir("include", &[
+            ("main.scm", "(define (f) (include \"lib/body.scm\") (g))"),
+            ("lib/body.scm", "(define (g) (list (h) (hh)))\n(include \"defs.scm\")"),
+            ("lib/defs.scm", "(define (h) 'h)\n(include-ci \"ci.scm\")"),
+            ("lib/ci.scm", "(DEFINE (HH) 'HH)"),
+            ("a.scm", "(include \"b.scm\")"),
+            ("b.scm", "(include \"a.scm\")"),
+            ("errors.scm", "(define (e) (include \"lib/e.scm\"))\n(include \"lib/bad.scm\")"),
+            ("lib/e.scm", "(define x 1)\n(car x)"),
+            ("lib/bad.scm", "(define y 2)\n  (if)"),
+        ]);
+        let mut i = Interpreter::new();
+        i.load_file(&dir.join("main.scm").display().to_string()).unwrap();
+        assert_eval(&mut i, "(f)", "(h hh)");
+
+        let err = i.load_file(&dir.join("a.scm").display().to_string()).err().unwrap();
+        match err.err_type {
+            ErrType::File(ref message) => assert!(message.starts_with("Include cycle:")
+                                                  && message.ends_with("a.scm"), "{}", message),
+            _ => panic!("{}", err),
+        }
+
+        // errors in included files point at the text in them
+        let err = i.load_file(&dir.join("errors.scm").display().to_string()).err().unwrap();
+        let bad = dir.join("lib/bad.scm").display().to_string();
+        assert!(err.to_string().contains(&format!("--> {}:2:3\n  |\n2 |   (if)\n  |   ^", bad)), "{}", err);
+        let err = eval_str(&mut i, "(e)").err().unwrap();
+        let e = dir.join("lib/e.scm").display().to_string();
+        assert!(err.to_string().contains(&format!("--> {}:2:1\n  |\n2 | (car x)\n  | ^", e)), "{}", err);
+        fs::remove_dir_all(&dir).unwrap();
+    }
+}
@@ -1,61 +1,159 @@
 extern crate skeem;
+extern crate rustyline;
+extern crate ctrlc;
+
+mod repl;
 
 use skeem::interpreter::Interpreter;
-use skeem::parse::{Scanner, parse_sexp};
-use skeem::error::Err;
-use skeem::types::HeapObject;
+use skeem::error::{Err, ErrType};
+use skeem::printer;
+use skeem::format;
+use std::env;
+use std::fs;
 use std::io;
-use std::io::Write;
+use std::io::{Read, Write};
+use std::process;
 use std::string::String;
 use std::result::Result;
 
-fn print_result(res: Result<HeapObject, Err>) {
-    match res {
-        Result::Ok(obj) => println!("=> {}", *(obj.as_ref())),
-        Result::Err(err) => println!("error: {}", err),
+const FMT_USAGE: &str = "usage: skeem fmt [--check] [--width N] [FILE]...";
+
+// skeem fmt: rewrites the files given in place, or formats standard input
+// to standard output. With --check nothing is written, and the exit status
+// is 1 if anything would change.
+fn fmt(args: &[String]) -> i32 {
+    let mut check = false;
+    let mut width = printer::WIDTH;
+    let mut paths = Vec::new();
+    let mut args = args.iter();
+    while let Option::Some(arg) = args.next() {
+        match arg.as_str() {
+            "--check" => check = true,
+            "--width" => match args.next().and_then(|n| n.parse().ok()) {
+                Option::Some(n) => width = n,
+                Option::None => {
+                    eprintln!("{}", FMT_USAGE);
+                    return 2;
+                },
+            },
+            _ if arg.starts_with('-') => {
+                eprintln!("{}", FMT_USAGE);
+                return 2;
+            },
+            _ => paths.push(arg.as_str()),
+        }
+    }
+
+    if paths.is_empty() {
+        let mut src = String::new();
+        if let Result::Err(e) = io::stdin().read_to_string(&mut src) {
+            eprintln!("<stdin>: {}", e);
+            return 1;
+        }
+        return match format::format(&src, width) {
+            Result::Ok(formatted) if check => (formatted != src) as i32,
+            Result::Ok(formatted) => {
+                print!("{}", formatted);
+                0
+            },
+            Result::Err(e) => {
+                eprintln!("<stdin>:{}", e);
+                1
+            },
+        };
+    }
+
+    let mut status = 0;
+    for path in paths {
+        let formatted = fs::read_to_string(path).map_err(|e| e.to_string()).and_then(|src| {
+            let formatted = format::format(&src, width).map_err(|e| e.to_string())?;
+            Result::Ok(if formatted == src {Option::None} else {Option::Some(formatted)})
+        });
+        match formatted {
+            Result::Ok(Option::None) => {},
+            Result::Ok(Option::Some(_)) if check => {
+                println!("{} would be reformatted", path);
+                status = 1;
+            },
+            Result::Ok(Option::Some(formatted)) => if let Result::Err(e) = fs::write(path, formatted) {
+                eprintln!("{}: {}", path, e);
+                status = 1;
+            },
+            Result::Err(e) => {
+                eprintln!("{}:{}", path, e);
+                status = 1;
+            },
+        }
     }
+    status
 }
 
-fn main() {
+const USAGE: &str = "usage: skeem [FILE | - | -e EXPR | -- FILE] [ARG]...
+       skeem fmt [--check] [--width N] [FILE]...
+After --, FILE is a script even if it is named fmt or starts with -.";
+
+// Runs a script, -e expressions or standard input, with the rest of args
+// as its command line. Errors go to standard error.
+fn script(args: &[String]) -> i32 {
     let mut i = Interpreter::new();
-    let mut scanner = Scanner::new();
-    let stdin = io::stdin();
+    let (args, file) = match args.split_first() {
+        Option::Some((first, rest)) if first == "--" && !rest.is_empty() => (rest, true),
+        _ => (args, false),
+    };
+    let expr = !file && args[0] == "-e" && args.len() > 1;
+    let text = match args[0].as_str() {
+        _ if file => Result::Ok(Option::None),
+        _ if expr => Result::Ok(Option::Some(args[1].clone())),
+        "-" => {
+            let mut text = String::new();
+            io::stdin().read_to_string(&mut text).map(|_| Option::Some(text))
+        },
+        path if !path.starts_with('-') => Result::Ok(Option::None),
+        _ => {
+            eprintln!("{}", USAGE);
+            return 2;
+        },
+    };
+    let text = match text {
+        Result::Ok(text) => text,
+        Result::Err(e) => {
+            eprintln!("<stdin>: {}", e);
+            return 1;
+        },
+    };
+    // -e takes its expression off the command line
+    let rest = if expr {&args[2..]} else {&args[1..]};
+    let mut command_line = vec![if expr {env::args().next().unwrap_or_default()} else {args[0].clone()}];
+    command_line.extend(rest.iter().cloned());
+    i.set_command_line(command_line);
 
-    print!("LISP> ");
+    let res = match text {
+        Option::Some(text) => i.eval_all(if expr {"<command line>"} else {"<stdin>"}, text),
+        // a file is loaded, so what it loads is relative to it
+        Option::None => i.load_file(&args[0]),
+    };
+    let status = match res {
+        Result::Ok(()) => 0,
+        Result::Err(Err{err_type: ErrType::Exit(status), ..}) => status,
+        Result::Err(e) => {
+            eprintln!("error: {}", e);
+            1
+        },
+    };
     io::stdout().flush().unwrap();
-    loop {
-        i.gc_disable();
-        let mut line = String::new();
-        if let Result::Err(e) = stdin.read_line(&mut line) {
-            println!("{}", e);
-            return;
-        };
+    status
+}
 
-        if line.chars().nth(0).unwrap() == '\n' {
-            print!("LISP> ");
-            io::stdout().flush().unwrap();
-            continue;
-        }
-        let opt = scanner.scan(line);
-
-        if let Option::Some(res) = opt {
-            match res {
-                Result::Ok(tokens) => {
-                    let res = parse_sexp(tokens.as_ref(), &mut i);
-                    match res {
-                        Result::Ok(obj) => {i.gc_enable(); print_result(i.eval(obj))},
-                        Result::Err(err) => println!("error: {}", err),
-                    }
-                },
-                Result::Err(err) => {
-                    println!("error: {}", err);
-                }
-            }
-            print!("LISP> ");
-            io::stdout().flush().unwrap();
-        } else {
-            print!("> ");
-            io::stdout().flush().unwrap();
-        }
+fn main() {
+    let args: Vec<String> = env::args().skip(1).collect();
+    match args.first().map(String::as_str) {
+        Option::Some("fmt") => process::exit(fmt(&args[1..])),
+        Option::Some("-h") | Option::Some("--help") => return println!("{}", USAGE),
+        Option::Some(_) => process::exit(script(&args)),
+        Option::None => {},
     }
+
+    let mut i = Interpreter::new();
+    i.set_command_line(env::args().take(1).collect());
+    process::exit(repl::repl(&mut i));
 }
@@ -0,0 +1,545 @@
+use num_bigint::BigInt;
+use num_rational::BigRational;
+use num_integer::Integer;
+use num_traits::{Zero, One, Signed, ToPrimitive, Num, Pow};
+use types::Type;
+use error::ErrType;
+use std::cmp::Ordering;
+use std::option::Option;
+use std::result::Result;
+use std::str::FromStr;
+
+// A number taken out of an object for arithmetic. Exact integers that fit
+// in an i64 are always Fixnum, and ratios are always in lowest terms with
+// a denominator other than 1, so each exact value has one representation.
+#[derive(Clone, Debug, PartialEq)]
+pub enum Number {
+    Fixnum(i64),
+    Big(BigInt),
+    Ratio(BigRational),
+    Real(f64),
+}
+
+impl Number {
+    pub fn of(t: &Type) -> Option<Number> {
+        match *t {
+            Type::Integer(n) => Option::Some(Number::Fixnum(n)),
+            Type::BigInt(ref n) => Option::Some(Number::Big(n.clone())),
+            Type::Rational(ref r) => Option::Some(Number::Ratio((**r).clone())),
+            Type::Float(f) => Option::Some(Number::Real(f)),
+            _ => Option::None,
+        }
+    }
+
+    pub fn into_type(self) -> Type {
+        match self.normalize() {
+            Number::Fixnum(n) => Type::Integer(n),
+            Number::Big(n) => Type::BigInt(n),
+            Number::Ratio(r) => Type::Rational(Box::new(r)),
+            Number::Real(f) => Type::Float(f),
+        }
+    }
+
+    fn normalize(self) -> Number {
+        match self {
+            Number::Big(n) => match n.to_i64() {
+                Option::Some(n) => Number::Fixnum(n),
+                Option::None => Number::Big(n),
+            },
+            Number::Ratio(r) => {
+                if r.denom().is_one() {
+                    Number::Big(r.to_integer()).normalize()
+                } else {
+                    Number::Ratio(r)
+                }
+            },
+            n => n,
+        }
+    }
+
+    #[inline]
+    pub fn is_exact(&self) -> bool {
+        !matches!(*self, Number::Real(_))
+    }
+
+    pub fn is_zero(&self) -> bool {
+        match *self {
+            Number::Fixnum(n) => n == 0,
+            Number::Big(ref n) => n.is_zero(),
+            Number::Ratio(ref r) => r.is_zero(),
+            Number::Real(f) => f == 0.0,
+        }
+    }
+
+    pub fn to_f64(&self) -> f64 {
+        match *self {
+            Number::Fixnum(n) => n as f64,
+            Number::Big(ref n) => n.to_f64().unwrap_or(f64::NAN),
+            Number::Ratio(ref r) => r.to_f64().unwrap_or(f64::NAN),
+            Number::Real(f) => f,
+        }
+    }
+
+    fn to_big(&self) -> BigInt {
+        match *self {
+            Number::Fixnum(n) => BigInt::from(n),
+            Number::Big(ref n) => n.clone(),
+            _ => unreachable!("not an exact integer"),
+        }
+    }
+
+    fn to_ratio(&self) -> BigRational {
+        match *self {
+            Number::Ratio(ref r) => r.clone(),
+            _ => BigRational::from_integer(self.to_big()),
+        }
+    }
+
+    pub fn exact(&self) -> Result<Number, ErrType> {
+        match *self {
+            Number::Real(f) => match BigRational::from_float(f) {
+                Option::Some(r) => Result::Ok(Number::Ratio(r).normalize()),
+                Option::None => Result::Err(ErrType::WrongType{wanted: "finite number".into(), got: "float".into()}),
+            },
+            ref n => Result::Ok(n.clone()),
+        }
+    }
+
+    #[inline]
+    pub fn inexact(&self) -> Number {
+        Number::Real(self.to_f64())
+    }
+
+    // Applies the operation for the representation both operands fit in:
+    // inexact if either is, otherwise the wider of the two.
+    fn combine(&self, other: &Number, fixnum: fn(i64, i64) -> Option<i64>,
+               big: fn(BigInt, BigInt) -> BigInt, ratio: fn(BigRational, BigRational) -> BigRational,
+               real: fn(f64, f64) -> f64) -> Number {
+        match (self, other) {
+            (&Number::Real(_), _) | (_, &Number::Real(_)) => Number::Real(real(self.to_f64(), other.to_f64())),
+            (&Number::Ratio(_), _) | (_, &Number::Ratio(_)) => Number::Ratio(ratio(self.to_ratio(), other.to_ratio())).normalize(),
+            (&Number::Fixnum(a), &Number::Fixnum(b)) => match fixnum(a, b) {
+                Option::Some(n) => Number::Fixnum(n),
+                Option::None => Number::Big(big(BigInt::from(a), BigInt::from(b))).normalize(),
+            },
+            _ => Number::Big(big(self.to_big(), other.to_big())).normalize(),
+        }
+    }
+
+    pub fn add(&self, other: &Number) -> Number {
+        self.combine(other, i64::checked_add, |a, b| a + b, |a, b| a + b, |a, b| a + b)
+    }
+
+    pub fn sub(&self, other: &Number) -> Number {
+        self.combine(other, i64::checked_sub, |a, b| a - b, |a, b| a - b, |a, b| a - b)
+    }
+
+    pub fn mul(&self, other: &Number) -> Number {
+        self.combine(other, i64::checked_mul, |a, b| a * b, |a, b| a * b, |a, b| a * b)
+    }
+
+    // Exact division gives a ratio unless it comes out even, and is an
+    // error by exact zero.
+    pub fn div(&self, other: &Number) -> Result<Number, ErrType> {
+        if other.is_exact() && other.is_zero() {
+            return Result::Err(ErrType::DivisionByZero);
+        }
+        Result::Ok(match (self, other) {
+            (&Number::Real(_), _) | (_, &Number::Real(_)) => Number::Real(self.to_f64() / other.to_f64()),
+            _ => Number::Ratio(self.to_ratio() / other.to_ratio()).normalize(),
+        })
+    }
+
+    pub fn neg(&self) -> Number {
+        match *self {
+            // 0 - 0.0 would lose the sign
+            Number::Real(x) => Number::Real(-x),
+            _ => Number::Fixnum(0).sub(self),
+        }
+    }
+
+    // Exact numbers compare exactly, with each other and with floats.
+    pub fn compare(&self, other: &Number) -> Option<Ordering> {
+        match (self, other) {
+            (&Number::Fixnum(a), &Number::Fixnum(b)) => Option::Some(a.cmp(&b)),
+            (&Number::Real(a), &Number::Real(b)) => a.partial_cmp(&b),
+            (&Number::Real(a), b) => match Number::Real(a).exact() {
+                Result::Ok(a) => Option::Some(a.to_ratio().cmp(&b.to_ratio())),
+                Result::Err(_) => a.partial_cmp(&0.0),
+            },
+            (a, &Number::Real(_)) => other.compare(a).map(Ordering::reverse),
+            _ => Option::Some(self.to_ratio().cmp(&other.to_ratio())),
+        }
+    }
+
+    pub fn is_integer(&self) -> bool {
+        match *self {
+            Number::Fixnum(_) | Number::Big(_) => true,
+            Number::Ratio(_) => false,
+            Number::Real(f) => f.is_finite() && f.fract() == 0.0,
+        }
+    }
+
+    pub fn type_name(&self) -> &'static str {
+        match *self {
+            Number::Fixnum(_) | Number::Big(_) => "integer",
+            Number::Ratio(_) => "rational",
+            Number::Real(_) => "float",
+        }
+    }
+
+    pub fn is_negative(&self) -> bool {
+        match *self {
+            Number::Fixnum(n) => n < 0,
+            Number::Big(ref n) => n.is_negative(),
+            Number::Ratio(ref r) => r.is_negative(),
+            Number::Real(f) => f < 0.0,
+        }
+    }
+
+    pub fn abs(&self) -> Number {
+        if self.is_negative() {self.neg()} else {self.clone()}
+    }
+
+    // Applies an operation on integers, which may be inexact ones.
+    fn integer_op(&self, other: &Number, fixnum: fn(i64, i64) -> Option<i64>,
+                  big: fn(&BigInt, &BigInt) -> BigInt, real: fn(f64, f64) -> f64) -> Result<Number, ErrType> {
+        for n in [self, other].iter() {
+            if !n.is_integer() {
+                return Result::Err(ErrType::WrongType{wanted: "integer".into(), got: n.type_name().into()});
+            }
+        }
+        if other.is_zero() {
+            return Result::Err(ErrType::DivisionByZero);
+        }
+        Result::Ok(match (self, other) {
+            (&Number::Real(_), _) | (_, &Number::Real(_)) => Number::Real(real(self.to_f64(), other.to_f64())),
+            (&Number::Fixnum(a), &Number::Fixnum(b)) => match fixnum(a, b) {
+                Option::Some(n) => Number::Fixnum(n),
+                Option::None => Number::Big(big(&BigInt::from(a), &BigInt::from(b))).normalize(),
+            },
+            _ => Number::Big(big(&self.to_big(), &other.to_big())).normalize(),
+        })
+    }
+
+    // Division rounding toward zero.
+    pub fn quotient(&self, other: &Number) -> Result<Number, ErrType> {
+        self.integer_op(other, i64::checked_div, |a, b| a / b, |a, b| (a / b).trunc())
+    }
+
+    // Has the sign of the dividend.
+    pub fn remainder(&self, other: &Number) -> Result<Number, ErrType> {
+        self.integer_op(other, i64::checked_rem, |a, b| a % b, |a, b| a % b)
+    }
+
+    // Has the sign of the divisor.
+    pub fn modulo(&self, other: &Number) -> Result<Number, ErrType> {
+        self.integer_op(other, |a, b| a.checked_rem(b).map(|r| if r != 0 && (r < 0) != (b < 0) {r + b} else {r}),
+                        |a, b| a.mod_floor(b),
+                        |a, b| {
+                            let r = a % b;
+                            if r != 0.0 && (r < 0.0) != (b < 0.0) {r + b} else {r}
+                        })
+    }
+
+    // gcd and lcm are always non-negative, and inexact if either argument
+    // is.
+    fn gcd_lcm(&self, other: &Number, f: fn(&BigInt, &BigInt) -> BigInt) -> Result<Number, ErrType> {
+        let mut exact = Vec::new();
+        for n in [self, other].iter() {
+            if !n.is_integer() {
+                return Result::Err(ErrType::WrongType{wanted: "integer".into(), got: n.type_name().into()});
+            }
+            exact.push(n.exact()?.to_big());
+        }
+        let result = Number::Big(f(&exact[0], &exact[1])).normalize();
+        Result::Ok(if self.is_exact() && other.is_exact() {result} else {result.inexact()})
+    }
+
+    pub fn gcd(&self, other: &Number) -> Result<Number, ErrType> {
+        self.gcd_lcm(other, |a, b| a.gcd(b))
+    }
+
+    pub fn lcm(&self, other: &Number) -> Result<Number, ErrType> {
+        self.gcd_lcm(other, |a, b| a.lcm(b))
+    }
+
+    fn round_with(&self, ratio: fn(&BigRational) -> BigRational, real: fn(f64) -> f64) -> Number {
+        match *self {
+            Number::Ratio(ref r) => Number::Ratio(ratio(r)).normalize(),
+            Number::Real(f) => Number::Real(real(f)),
+            ref n => n.clone(),
+        }
+    }
+
+    pub fn floor(&self) -> Number {
+        self.round_with(BigRational::floor, f64::floor)
+    }
+
+    pub fn ceiling(&self) -> Number {
+        self.round_with(BigRational::ceil, f64::ceil)
+    }
+
+    pub fn truncate(&self) -> Number {
+        self.round_with(BigRational::trunc, f64::trunc)
+    }
+
+    // To the nearest integer, and to the even one on a tie.
+    pub fn round(&self) -> Number {
+        self.round_with(|r| {
+            let floor = r.floor();
+            let diff = r - &floor;
+            let half = BigRational::new(BigInt::one(), BigInt::from(2));
+            if diff > half || (diff == half && floor.to_integer().is_odd()) {
+                floor + BigRational::one()
+            } else {
+                floor
+            }
+        }, f64::round_ties_even)
+    }
+
+    // The exact root of an exact number whose root is exact. Complex
+    // numbers aren't supported, so the root of a negative number is NaN.
+    pub fn sqrt(&self) -> Number {
+        let root = |n: &BigInt| {
+            let s = n.sqrt();
+            if &s * &s == *n {Option::Some(s)} else {Option::None}
+        };
+        if !self.is_negative() {
+            match *self {
+                Number::Fixnum(_) | Number::Big(_) => if let Option::Some(s) = root(&self.to_big()) {
+                    return Number::Big(s).normalize();
+                },
+                Number::Ratio(ref r) => if let (Option::Some(n), Option::Some(d)) = (root(r.numer()), root(r.denom())) {
+                    return Number::Ratio(BigRational::new(n, d)).normalize();
+                },
+                Number::Real(_) => {},
+            }
+        }
+        Number::Real(self.to_f64().sqrt())
+    }
+
+    // s and r with s*s + r = self, for an exact non-negative integer.
+    pub fn exact_integer_sqrt(&self) -> Result<(Number, Number), ErrType> {
+        match *self {
+            Number::Fixnum(_) | Number::Big(_) if !self.is_negative() => {
+                let n = self.to_big();
+                let s = n.sqrt();
+                let r = &n - &s * &s;
+                Result::Ok((Number::Big(s).normalize(), Number::Big(r).normalize()))
+            },
+            _ => Result::Err(ErrType::WrongType{wanted: "exact non-negative integer".into(), got: self.type_name().into()}),
+        }
+    }
+
+    // Exact when both are exact and the power is an integer.
+    pub fn expt(&self, power: &Number) -> Result<Number, ErrType> {
+        let exponent = match *power {
+            Number::Fixnum(n) if self.is_exact() => n,
+            // only 0, 1 and -1 have powers this large that fit in memory
+            Number::Big(ref p) if self.is_exact() => return match *self {
+                Number::Fixnum(0) if p.is_negative() => Result::Err(ErrType::DivisionByZero),
+                Number::Fixnum(0) | Number::Fixnum(1) => Result::Ok(self.clone()),
+                Number::Fixnum(-1) => Result::Ok(Number::Fixnum(if p.is_even() {1} else {-1})),
+                _ => Result::Ok(Number::Real(self.to_f64().powf(power.to_f64()))),
+            },
+            Number::Fixnum(0) => return Result::Ok(Number::Real(1.0)),
+            _ => return Result::Ok(Number::Real(self.to_f64().powf(power.to_f64()))),
+        };
+        let base = self.to_ratio();
+        if exponent < 0 && base.is_zero() {
+            return Result::Err(ErrType::DivisionByZero);
+        }
+        let magnitude = match exponent.unsigned_abs().to_u32() {
+            Option::Some(e) => base.pow(e),
+            Option::None => return Result::Ok(Number::Real(self.to_f64().powf(exponent as f64))),
+        };
+        Result::Ok(Number::Ratio(if exponent < 0 {magnitude.recip()} else {magnitude}).normalize())
+    }
+
+    // The text of the number in radix, which is 10 for inexact numbers.
+    pub fn to_string_radix(&self, radix: u32) -> String {
+        match *self {
+            Number::Fixnum(n) => BigInt::from(n).to_str_radix(radix),
+            Number::Big(ref n) => n.to_str_radix(radix),
+            Number::Ratio(ref r) => format!("{}/{}", r.numer().to_str_radix(radix), r.denom().to_str_radix(radix)),
+            Number::Real(f) => format_real(f),
+        }
+    }
+}
+
+// Floats always show that they are inexact.
+pub fn format_real(f: f64) -> String {
+    if f.is_nan() {
+        "+nan.0".to_string()
+    } else if f.is_infinite() {
+        if f > 0.0 {"+inf.0"} else {"-inf.0"}.to_string()
+    } else {
+        let s = format!("{}", f);
+        if s.contains('.') {s} else {s + ".0"}
+    }
+}
+
+// Parses the R7RS syntax for a real number, with any #e #i #x #b #o #d
+// prefixes. None if text isn't a number.
+pub fn parse(text: &str) -> Option<Number> {
+    let mut rest = text;
+    let mut radix = Option::None;
+    let mut exactness = Option::None;
+    while rest.starts_with('#') {
+        let flag = rest[1..].chars().next()?.to_ascii_lowercase();
+        match flag {
+            'e' | 'i' if exactness.is_none() => exactness = Option::Some(flag == 'e'),
+            'x' | 'b' | 'o' | 'd' if radix.is_none() => radix = Option::Some(match flag {
+                'x' => 16,
+                'b' => 2,
+                'o' => 8,
+                _ => 10,
+            }),
+            _ => return Option::None,
+        }
+        rest = &rest[2..];
+    }
+
+    let number = parse_real(rest, radix.unwrap_or(10))?;
+    match exactness {
+        Option::Some(true) => number.exact().ok(),
+        Option::Some(false) => Option::Some(number.inexact()),
+        Option::None => Option::Some(number),
+    }
+}
+
+fn parse_real(text: &str, radix: u32) -> Option<Number> {
+    match text.to_ascii_lowercase().as_str() {
+        "+inf.0" => return Option::Some(Number::Real(f64::INFINITY)),
+        "-inf.0" => return Option::Some(Number::Real(f64::NEG_INFINITY)),
+        "+nan.0" | "-nan.0" => return Option::Some(Number::Real(f64::NAN)),
+        _ => {},
+    }
+
+    let (negative, unsigned) = match text.as_bytes().first() {
+        Option::Some(b'+') => (false, &text[1..]),
+        Option::Some(b'-') => (true, &text[1..]),
+        _ => (false, text),
+    };
+    let is_digit = |c: char| c.is_digit(radix);
+    let uinteger = |s: &str| !s.is_empty() && s.chars().all(is_digit);
+
+    let number = if let Option::Some(slash) = unsigned.find('/') {
+        let (numer, denom) = (&unsigned[..slash], &unsigned[slash + 1..]);
+        if !uinteger(numer) || !uinteger(denom) {
+            return Option::None;
+        }
+        let denom = BigInt::from_str_radix(denom, radix).ok()?;
+        if denom.is_zero() {
+            return Option::None;
+        }
+        Number::Ratio(BigRational::new(BigInt::from_str_radix(numer, radix).ok()?, denom)).normalize()
+    } else if uinteger(unsigned) {
+        Number::Big(BigInt::from_str_radix(unsigned, radix).ok()?).normalize()
+    } else if radix == 10 {
+        parse_decimal(unsigned)?
+    } else {
+        return Option::None;
+    };
+    Option::Some(if negative {number.neg()} else {number})
+}
+
+// digits [. digits] [e [+-] digits] with at least one digit before the
+// exponent, read as a float.
+fn parse_decimal(text: &str) -> Option<Number> {
+    let bytes = text.as_bytes();
+    let mut i = 0;
+    let digits = |i: &mut usize| {
+        let start = *i;
+        while *i < bytes.len() && bytes[*i].is_ascii_digit() {
+            *i += 1;
+        }
+        *i - start
+    };
+    let mut mantissa = digits(&mut i);
+    if i < bytes.len() && bytes[i] == b'.' {
+        i += 1;
+        mantissa += digits(&mut i);
+    }
+    if mantissa == 0 {
+        return Option::None;
+    }
+    if i < bytes.len() && (bytes[i] == b'e' || bytes[i] == b'E') {
+        i += 1;
+        if i < bytes.len() && (bytes[i] == b'+' || bytes[i] == b'-') {
+            i += 1;
+        }
+        if digits(&mut i) == 0 {
+            return Option::None;
+        }
+    }
+    if i != bytes.len() {
+        return Option::None;
+    }
+    f64::from_str(text).ok().map(Number::Real)
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    fn num(s: &str) -> Number {
+        parse(s).unwrap_or_else(|| panic!("{} is not a number", s))
+    }
+
+    #[test]
+    fn test_ops() {
+        assert_eq!(Number::Fixnum(1).add(&Number::Real(2.0)), Number::Real(3.0));
+        assert_eq!(Number::Fixnum(2).mul(&Number::Real(2.0)), Number::Real(4.0));
+        assert_eq!(num("9223372036854775807").add(&num("1")), num("9223372036854775808"));
+        assert_eq!(num("9223372036854775808").sub(&num("1")), Number::Fixnum(i64::MAX));
+        assert_eq!(num("-9223372036854775808").neg(), num("9223372036854775808"));
+        assert_eq!(num("1/3").add(&num("1/6")), num("1/2"));
+        assert_eq!(num("1/2").add(&num("1/2")), Number::Fixnum(1));
+        assert_eq!(num("1").div(&num("3")).unwrap(), num("1/3"));
+        assert_eq!(num("6").div(&num("3")).unwrap(), Number::Fixnum(2));
+        assert!(matches!(num("1").div(&num("0")), Result::Err(ErrType::DivisionByZero)));
+        assert_eq!(num("1").div(&num("0.0")).unwrap(), Number::Real(f64::INFINITY));
+        assert_eq!(num("1/3").compare(&num("0.3")), Option::Some(Ordering::Greater));
+    }
+
+    #[test]
+    fn test_parse() {
+        let cases = [
+            ("42", Number::Fixnum(42)),
+            ("-17", Number::Fixnum(-17)),
+            ("#x-1F", Number::Fixnum(-31)),
+            ("#b101", Number::Fixnum(5)),
+            ("#o17", Number::Fixnum(15)),
+            ("#d10", Number::Fixnum(10)),
+            ("#e1.25", num("5/4")),
+            ("#e1e3", Number::Fixnum(1000)),
+            ("#i1/4", Number::Real(0.25)),
+            ("#x#i10", Number::Real(16.0)),
+            ("#i#xff", Number::Real(255.0)),
+            ("6/4", num("3/2")),
+            ("-6/3", Number::Fixnum(-2)),
+            (".5", Number::Real(0.5)),
+            ("1e2", Number::Real(100.0)),
+            ("-inf.0", Number::Real(f64::NEG_INFINITY)),
+        ];
+        for &(text, ref expected) in cases.iter() {
+            assert_eq!(&num(text), expected, "{}", text);
+        }
+        for text in ["1/0", "#x1.5", "1/2/3", "#e#e1", "#e+inf.0", "1.5/2", "#q1", "--1", "#x"].iter() {
+            assert!(parse(text).is_none(), "{}", text);
+        }
+    }
+
+    #[test]
+    fn test_exactness() {
+        assert_eq!(num("0.5").exact().unwrap(), num("1/2"));
+        assert_eq!(num("1/8").inexact(), Number::Real(0.125));
+        assert!(Number::Real(f64::NAN).exact().is_err());
+        assert_eq!(num("1/3").to_string_radix(10), "1/3");
+        assert_eq!(num("255").to_string_radix(16), "ff");
+        assert_eq!(format_real(2.0), "2.0");
+        assert_eq!(format_real(0.1), "0.1");
+    }
+}
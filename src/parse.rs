@@ -1,271 +1,701 @@
 use std::result::Result;
-use std::boxed::Box;
-use std::vec::Vec;
-use std::clone::Clone;
 use std::option::Option;
+use std::collections::{HashMap, HashSet};
 use std::fmt;
-use std::str::FromStr;
-use std::fmt::Write;
 use std::rc::Rc;
+use std::cell::RefCell;
 use interpreter::Interpreter;
-use types::{Type, new_list, HeapObject};
+use types::{Object, Type, Pair, HeapObject};
+use number::{self, Number};
 
-pub enum Token {
+// Where a token or datum starts in the source. line and column count from
+// 1, column and offset in chars and bytes respectively.
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub struct Span {
+    pub line: u32,
+    pub column: u32,
+    pub offset: u32,
+}
+
+pub enum TokenKind {
     ParenOpen,
+    VectorOpen,
+    BytevectorOpen,
     ParenClose,
+    Dot,
+    Quote,
+    Quasiquote,
+    Unquote,
+    UnquoteSplicing,
+    // #; comments out the datum after it
+    DatumComment,
+    // #n= and #n#
+    Label(u64),
+    LabelRef(u64),
+    // ; and #| |# comments, with their delimiters
+    Comment(String),
 
-    Symbol(Rc<String>),
-    String(Rc<String>),
+    Symbol(String),
+    String(String),
     Character(char),
-    Integer(i64),
-    Float(f64),
+    Boolean(bool),
+    Number(Number),
+}
+
+pub struct Token {
+    pub kind: TokenKind,
+    pub span: Span,
 }
 
-// impl Clone for Token {
-//     fn clone(&self) -> Token {
-//         match self {
-//             &Token::ParenOpen => Token::ParenOpen,
-//             &Token::ParenClose => Token::ParenClose,
-//             &Token::String(ref s) => Token::String(s.clone()),
-//             &Token::Symbol(ref s) => Token::Symbol(s.clone()),
-//             &Token::Character(c) => Token::Character(c),
-//             &Token::Integer(i) => Token::Integer(i),
-//             &Token::Float(f) => Token::Float(f),
-//         }
-//     }
-// }
-
-pub enum ScanError {
-    UnmatchedParen,
-    InvalidChar,
+pub struct ReadError {
+    pub message: &'static str,
+    pub span: Span,
+    // the input ended in the middle of a datum, more of it may complete it
+    pub incomplete: bool,
 }
 
-impl fmt::Display for ScanError {
+impl ReadError {
+    pub fn new(message: &'static str, span: Span) -> ReadError {
+        ReadError{message, span, incomplete: false}
+    }
+
+    fn incomplete(message: &'static str, span: Span) -> ReadError {
+        ReadError{message, span, incomplete: true}
+    }
+}
+
+impl fmt::Display for ReadError {
     fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
-        match *self {
-            ScanError::UnmatchedParen => write!(f, "Unmatched Parenthesis"),
-            ScanError::InvalidChar => write!(f, "Invalid character syntax"),
-        }
+        write!(f, "{}:{}: {}", self.span.line, self.span.column, self.message)
     }
 }
 
-pub struct Scanner {
-    scanning_string: bool,
-    scanning_char: bool,
-    scanning_num: bool,
-    scanning_float: bool,
-    scanning_list_depth: usize,
-    incomplete_str: Option<String>,
+impl fmt::Debug for ReadError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{}", self)
+    }
 }
 
-#[inline(always)]
-fn is_terminating_char(ch: char) -> bool {
-    ch == ' ' || ch == '(' || ch == ')'
+#[inline]
+fn is_delimiter(ch: char) -> bool {
+    ch.is_whitespace() || matches!(ch, '(' | ')' | '"' | ';' | '|')
 }
 
-impl Scanner{
-    pub fn new() -> Scanner {
-        Scanner {
-            scanning_string: false,
-            scanning_char: false,
-            scanning_num: false,
-            scanning_float: false,
-            scanning_list_depth: 0,
-            incomplete_str: Option::None,
-        }
+// Splits source text into tokens, comments included.
+pub struct Lexer<'a> {
+    src: &'a str,
+    pos: usize,
+    line: u32,
+    column: u32,
+    fold_case: bool,
+}
+
+impl<'a> Lexer<'a> {
+    pub fn new(src: &'a str) -> Lexer<'a> {
+        Lexer{src, pos: 0, line: 1, column: 1, fold_case: false}
+    }
+
+    // Whether identifiers and character names are read case-insensitively,
+    // as after #!fold-case.
+    pub fn set_fold_case(&mut self, fold_case: bool) {
+        self.fold_case = fold_case;
+    }
+
+    #[inline]
+    pub fn span(&self) -> Span {
+        Span{line: self.line, column: self.column, offset: self.pos as u32}
+    }
+
+    #[inline]
+    fn peek(&self) -> Option<char> {
+        self.src[self.pos..].chars().next()
     }
 
-    #[inline(always)]
-    fn scanning_incomplete(&self) -> bool {
-        self.scanning_char || self.scanning_list_depth != 0 || self.scanning_string
+    fn bump(&mut self) -> Option<char> {
+        let ch = self.peek()?;
+        self.pos += ch.len_utf8();
+        if ch == '\n' {
+            self.line += 1;
+            self.column = 1;
+        } else {
+            self.column += 1;
+        }
+        Option::Some(ch)
     }
 
-    fn get_token(&mut self, word: &String) -> Option<Token> {
-        //println!("{} {}", word, word.len());
-        if word.len() == 0 {
-            return Option::None
+    // Reads up to the next delimiter.
+    fn word(&mut self, mut word: String) -> String {
+        while let Option::Some(ch) = self.peek() {
+            if is_delimiter(ch) {
+                break;
+            }
+            word.push(ch);
+            self.bump();
         }
+        word
+    }
 
-        if self.scanning_num && !(word == "+" || word == "-") {
-            if self.scanning_float {
-                let f = f64::from_str(word.clone().as_str()).unwrap();
-                return Option::Some(Token::Float(f));
+    pub fn next_token(&mut self) -> Result<Option<Token>, ReadError> {
+        loop {
+            while self.peek().is_some_and(char::is_whitespace) {
+                self.bump();
             }
 
-            let n = i64::from_str(word.clone().as_str()).unwrap();
-            return Option::Some(Token::Integer(n));
+            let span = self.span();
+            let ch = match self.bump() {
+                Option::Some(ch) => ch,
+                Option::None => return Result::Ok(Option::None),
+            };
+            let kind = match ch {
+                '(' => TokenKind::ParenOpen,
+                ')' => TokenKind::ParenClose,
+                '\'' => TokenKind::Quote,
+                '`' => TokenKind::Quasiquote,
+                ',' => if self.peek() == Option::Some('@') {
+                    self.bump();
+                    TokenKind::UnquoteSplicing
+                } else {
+                    TokenKind::Unquote
+                },
+                ';' => {
+                    let start = self.pos - 1;
+                    while self.peek().is_some_and(|ch| ch != '\n') {
+                        self.bump();
+                    }
+                    TokenKind::Comment(self.src[start..self.pos].to_string())
+                },
+                '"' => TokenKind::String(self.delimited('"', span)?),
+                '|' => TokenKind::Symbol(self.delimited('|', span)?),
+                '#' => match self.hash(span)? {
+                    Option::Some(kind) => kind,
+                    Option::None => continue,
+                },
+                '[' | ']' | '{' | '}' => return Result::Err(ReadError::new("reserved character", span)),
+                _ => {
+                    let mut word = String::new();
+                    word.push(ch);
+                    let word = self.word(word);
+                    self.atom(word, span)?
+                },
+            };
+            return Result::Ok(Option::Some(Token{kind, span}));
         }
+    }
 
-        return Option::Some(if self.scanning_string {
-            Token::String(Rc::new(word.clone()))
-        } else {
-            Token::Symbol(Rc::new(word.clone()))
-        })
+    // After a #. None for directives, which produce no token.
+    fn hash(&mut self, span: Span) -> Result<Option<TokenKind>, ReadError> {
+        let ch = match self.peek() {
+            Option::Some(ch) => ch,
+            Option::None => return Result::Err(ReadError::incomplete("unexpected end of input after #", span)),
+        };
+        let kind = match ch {
+            '(' => {
+                self.bump();
+                TokenKind::VectorOpen
+            },
+            ';' => {
+                self.bump();
+                TokenKind::DatumComment
+            },
+            '|' => {
+                self.bump();
+                TokenKind::Comment(self.block_comment(span)?)
+            },
+            '\\' => {
+                self.bump();
+                TokenKind::Character(self.character(span)?)
+            },
+            '!' => {
+                self.bump();
+                match self.word(String::new()).as_str() {
+                    "fold-case" => self.fold_case = true,
+                    "no-fold-case" => self.fold_case = false,
+                    _ => return Result::Err(ReadError::new("unknown directive", span)),
+                }
+                return Result::Ok(Option::None);
+            },
+            '0'..='9' => {
+                let mut n: u64 = 0;
+                while let Option::Some(d) = self.peek().and_then(|ch| ch.to_digit(10)) {
+                    n = match n.checked_mul(10).and_then(|n| n.checked_add(d as u64)) {
+                        Option::Some(n) => n,
+                        Option::None => return Result::Err(ReadError::new("label too large", span)),
+                    };
+                    self.bump();
+                }
+                match self.bump() {
+                    Option::Some('=') => TokenKind::Label(n),
+                    Option::Some('#') => TokenKind::LabelRef(n),
+                    Option::None => return Result::Err(ReadError::incomplete("unexpected end of input in label", span)),
+                    _ => return Result::Err(ReadError::new("bad label syntax", span)),
+                }
+            },
+            _ => {
+                let word = self.word(String::new());
+                match word.as_str() {
+                    "t" | "true" => TokenKind::Boolean(true),
+                    "f" | "false" => TokenKind::Boolean(false),
+                    "u8" if self.peek() == Option::Some('(') => {
+                        self.bump();
+                        TokenKind::BytevectorOpen
+                    },
+                    // #x10, #e1.5, #i#b101...
+                    _ if "eixbodEIXBOD".contains(ch) => match number::parse(&format!("#{}", word)) {
+                        Option::Some(n) => TokenKind::Number(n),
+                        Option::None => return Result::Err(ReadError::new("bad number syntax", span)),
+                    },
+                    _ => return Result::Err(ReadError::new("unknown # syntax", span)),
+                }
+            },
+        };
+        Result::Ok(Option::Some(kind))
     }
 
-    //Option::Some represents a completed scan
-    //Option::None represents an incomplete scan
-    pub fn scan(&mut self, line: String) -> Option<Result<Box<Vec<Token>>, ScanError>> {
-        let mut tokens = Vec::new();
-        let mut word = String::new();
+    // #| ... |#, which nest
+    fn block_comment(&mut self, span: Span) -> Result<String, ReadError> {
+        let start = self.pos - 2;
+        let mut depth = 1;
+        while depth > 0 {
+            match self.bump() {
+                Option::Some('|') if self.peek() == Option::Some('#') => {
+                    self.bump();
+                    depth -= 1;
+                },
+                Option::Some('#') if self.peek() == Option::Some('|') => {
+                    self.bump();
+                    depth += 1;
+                },
+                Option::Some(_) => {},
+                Option::None => return Result::Err(ReadError::incomplete("unterminated block comment", span)),
+            }
+        }
+        Result::Ok(self.src[start..self.pos].to_string())
+    }
 
-        let actual_line = if let Option::Some(ref s) = self.incomplete_str {
-            let mut s = s.clone();
-            s.write_str(line.as_str()).expect("");
-            let (f, _) = s.split_at(s.len()-1);
-            String::from_str(f).unwrap()
-        } else {
-            let (f, _) = line.split_at(line.len()-1);
-            String::from_str(f).unwrap()
+    // After #\: a single character, a character name or a hex scalar value.
+    fn character(&mut self, span: Span) -> Result<char, ReadError> {
+        let ch = match self.bump() {
+            Option::Some(ch) => ch,
+            Option::None => return Result::Err(ReadError::incomplete("unexpected end of input in character", span)),
         };
+        if is_delimiter(ch) || self.peek().is_none_or(is_delimiter) {
+            return Result::Ok(ch);
+        }
 
-        //println!("{}", actual_line);
+        let mut name = String::new();
+        name.push(ch);
+        let mut name = self.word(name);
+        if self.fold_case {
+            name = name.to_lowercase();
+        }
+        let named = match name.as_str() {
+            "alarm" => '\x07',
+            "backspace" => '\x08',
+            "delete" => '\x7f',
+            "escape" => '\x1b',
+            "newline" => '\n',
+            "null" => '\0',
+            "return" => '\r',
+            "space" => ' ',
+            "tab" => '\t',
+            _ => {
+                let hex = if name.starts_with('x') || name.starts_with('X') {
+                    u32::from_str_radix(&name[1..], 16).ok().and_then(char::from_u32)
+                } else {
+                    Option::None
+                };
+                return hex.ok_or_else(|| ReadError::new("unknown character name", span));
+            },
+        };
+        Result::Ok(named)
+    }
 
-        for (i, ch) in actual_line.chars().enumerate() {
-            let mut push_ch = false;
-            match ch {
-                '\"' => {
-                    if self.scanning_string {
-                        tokens.push(Token::String(Rc::new(word.clone())));
-                        word.clear();
-                    }
+    // The contents of a string or |symbol|, up to the unescaped end
+    // delimiter.
+    fn delimited(&mut self, end: char, span: Span) -> Result<String, ReadError> {
+        let mut s = String::new();
+        loop {
+            let ch = match self.bump() {
+                Option::Some(ch) => ch,
+                Option::None => return Result::Err(ReadError::incomplete(
+                    if end == '"' {"unterminated string"} else {"unterminated |symbol|"}, span)),
+            };
+            if ch == end {
+                return Result::Ok(s);
+            }
+            if ch != '\\' {
+                s.push(ch);
+                continue;
+            }
 
-                    self.scanning_string = !self.scanning_string;
-                },
-                '?' => self.scanning_char = true,
-                '0'...'9' => {self.scanning_num |= word.len() == 0; push_ch = true;},
-                '.' => {self.scanning_float = self.scanning_num; push_ch = true;},
-                '-' | '+' => {self.scanning_num |= word.len() == 0; push_ch = true;},
-                '(' => {
-                    self.get_token(&word).map(|t| {tokens.push(t)});
-                    tokens.push(Token::ParenOpen);
-                    self.scanning_list_depth += 1;
-                    word.clear();
-                },
-                ')' => {
-                    if !self.scanning_list_depth == 0 {
-                        return Option::Some(Result::Err(ScanError::UnmatchedParen));
+            let escape_span = self.span();
+            let escaped = match self.bump() {
+                Option::Some('a') => '\x07',
+                Option::Some('b') => '\x08',
+                Option::Some('t') => '\t',
+                Option::Some('n') => '\n',
+                Option::Some('r') => '\r',
+                Option::Some(ch @ ('"' | '\\' | '|')) => ch,
+                Option::Some('x') | Option::Some('X') => {
+                    let mut hex = String::new();
+                    while let Option::Some(ch) = self.bump() {
+                        if ch == ';' {
+                            break;
+                        }
+                        hex.push(ch);
+                    }
+                    match u32::from_str_radix(&hex, 16).ok().and_then(char::from_u32) {
+                        Option::Some(ch) => ch,
+                        Option::None => return Result::Err(ReadError::new("bad \\x escape", escape_span)),
                     }
-                    self.get_token(&word).map(|t| {tokens.push(t)});
-                    tokens.push(Token::ParenClose);
-                    self.scanning_list_depth -= 1;
-                    word.clear();
-                },
-                ' ' => {
-                    self.get_token(&word).map(|t| {tokens.push(t);});
-                    word.clear();
-                    self.scanning_num = false;
                 },
-                _ => {
-                    if self.scanning_char {
-                        if i == line.len() - 2  {
-                            return Option::Some(Result::Err(ScanError::InvalidChar));
+                // \ at the end of a line joins it with the next, leaving
+                // out the whitespace around the line break
+                Option::Some(ch) if ch == ' ' || ch == '\t' || ch == '\n' || ch == '\r' => {
+                    let mut ch = ch;
+                    while ch != '\n' {
+                        match self.bump() {
+                            Option::Some(' ') | Option::Some('\t') | Option::Some('\r') => {},
+                            Option::Some('\n') => ch = '\n',
+                            _ => return Result::Err(ReadError::new("bad escape in string", escape_span)),
                         }
-
-                        self.scanning_char = false;
-                        tokens.push(Token::Character(ch));
-                        continue;
                     }
-                    self.scanning_num = false;
-                    push_ch = true;
+                    while self.peek().is_some_and(|ch| ch == ' ' || ch == '\t') {
+                        self.bump();
+                    }
+                    continue;
                 },
+                Option::Some(_) => return Result::Err(ReadError::new("bad escape in string", escape_span)),
+                Option::None => continue,
             };
+            s.push(escaped);
+        }
+    }
+
+    // A number, identifier or the . of a dotted list.
+    fn atom(&self, word: String, span: Span) -> Result<TokenKind, ReadError> {
+        if word == "." {
+            return Result::Ok(TokenKind::Dot);
+        }
+        if let Option::Some(number) = number::parse(&word) {
+            return Result::Ok(TokenKind::Number(number));
+        }
+
+        let mut chars = word.chars();
+        let first = chars.next().unwrap();
+        let second = chars.next();
+        let numeric = first.is_ascii_digit()
+            || ((first == '+' || first == '-' || first == '.') && second.is_some_and(|c| c.is_ascii_digit()));
+        if numeric {
+            return Result::Err(ReadError::new("bad number syntax", span));
+        }
+
+        Result::Ok(TokenKind::Symbol(if self.fold_case {word.to_lowercase()} else {word}))
+    }
+}
 
-            if push_ch {
-                word.push(ch);
+// Nesting deeper than this is an error rather than a stack overflow.
+pub const MAX_DEPTH: usize = 1000;
+
+// A datum the reader has started but not finished.
+enum Open {
+    // the elements so far, and the dotted tail once it has been read
+    List{span: Span, elems: Vec<HeapObject>, dot: Dot},
+    Vector{span: Span, elems: Vec<HeapObject>},
+    Bytevector{span: Span, bytes: Vec<u8>},
+    // (quote datum) and friends
+    Quote{span: Span, name: &'static str},
+    Label{span: Span, n: u64, placeholder: HeapObject},
+    DatumComment{span: Span},
+}
+
+enum Dot {
+    None,
+    // after the ., before the tail
+    Tail,
+    // after the tail, before the )
+    Close(HeapObject),
+}
+
+// Reads data from source text, skipping comments. Nesting is kept on an
+// explicit stack rather than the Rust stack.
+pub struct Reader<'a> {
+    lexer: Lexer<'a>,
+    // values of the #n= labels of the datum being read
+    labels: HashMap<u64, HeapObject>,
+    open: Vec<Open>,
+}
+
+impl<'a> Reader<'a> {
+    pub fn new(src: &'a str) -> Reader<'a> {
+        Reader{lexer: Lexer::new(src), labels: HashMap::new(), open: Vec::new()}
+    }
+
+    pub fn set_fold_case(&mut self, fold_case: bool) {
+        self.lexer.set_fold_case(fold_case);
+    }
+
+    // The next datum, None at the end of the input.
+    pub fn read(&mut self, interpreter: &mut Interpreter) -> Result<Option<HeapObject>, ReadError> {
+        self.labels.clear();
+        self.open.clear();
+        loop {
+            let token = match self.lexer.next_token()? {
+                Option::Some(token) => token,
+                Option::None => return match self.open.last() {
+                    Option::None => Result::Ok(Option::None),
+                    Option::Some(open) => Result::Err(ReadError::incomplete(match *open {
+                        Open::List{..} => "unclosed (",
+                        Open::Vector{..} => "unclosed #(",
+                        Open::Bytevector{..} => "unclosed #u8(",
+                        Open::Quote{..} => "expected datum after quote",
+                        Open::Label{..} => "expected datum after label",
+                        Open::DatumComment{..} => "expected datum after #;",
+                    }, open.span())),
+                },
+            };
+            if let Option::Some(datum) = self.token(token, interpreter)? {
+                if let Option::Some(datum) = self.close(datum, interpreter)? {
+                    return Result::Ok(Option::Some(datum));
+                }
             }
         }
+    }
+
+    pub fn read_all(&mut self, interpreter: &mut Interpreter) -> Result<Vec<HeapObject>, ReadError> {
+        let mut data = Vec::new();
+        while let Option::Some(datum) = self.read(interpreter)? {
+            data.push(datum);
+        }
+        Result::Ok(data)
+    }
+
+    fn push(&mut self, open: Open, span: Span) -> Result<Option<HeapObject>, ReadError> {
+        if self.open.len() == MAX_DEPTH {
+            return Result::Err(ReadError::new("nesting too deep", span));
+        }
+        self.open.push(open);
+        Result::Ok(Option::None)
+    }
 
-        if self.scanning_incomplete() {
-            for token in tokens {
-                println!("{}", token);
+    // Handles one token. Returns the datum it completes, if any.
+    fn token(&mut self, token: Token, interpreter: &mut Interpreter) -> Result<Option<HeapObject>, ReadError> {
+        let span = token.span;
+        if let Option::Some(&Open::List{dot: Dot::Close(_), ..}) = self.open.last() {
+            if !matches!(token.kind, TokenKind::ParenClose | TokenKind::Comment(_)) {
+                return Result::Err(ReadError::new("expected ) after dotted tail", span));
             }
+        }
+
+        let t = match token.kind {
+            TokenKind::Comment(_) => return Result::Ok(Option::None),
+            TokenKind::ParenOpen => return self.push(Open::List{span, elems: Vec::new(), dot: Dot::None}, span),
+            TokenKind::VectorOpen => return self.push(Open::Vector{span, elems: Vec::new()}, span),
+            TokenKind::BytevectorOpen => return self.push(Open::Bytevector{span, bytes: Vec::new()}, span),
+            TokenKind::DatumComment => return self.push(Open::DatumComment{span}, span),
+            TokenKind::Quote => return self.push(Open::Quote{span, name: "quote"}, span),
+            TokenKind::Quasiquote => return self.push(Open::Quote{span, name: "quasiquote"}, span),
+            TokenKind::Unquote => return self.push(Open::Quote{span, name: "unquote"}, span),
+            TokenKind::UnquoteSplicing => return self.push(Open::Quote{span, name: "unquote-splicing"}, span),
+            TokenKind::Label(n) => {
+                // references to the label inside its own datum are patched
+                // once the datum is read
+                let placeholder: HeapObject = Rc::new(Box::new(Object::new(Type::Nil)));
+                self.labels.insert(n, placeholder.clone());
+                return self.push(Open::Label{span, n, placeholder}, span);
+            },
+            TokenKind::LabelRef(n) => return match self.labels.get(&n) {
+                Option::Some(obj) => Result::Ok(Option::Some(obj.clone())),
+                Option::None => Result::Err(ReadError::new("undefined label", span)),
+            },
+            TokenKind::ParenClose => {
+                let datum = match self.open.pop() {
+                    Option::Some(Open::List{span, elems, dot}) => {
+                        let mut tail = match dot {
+                            Dot::None => interpreter.new_nil(),
+                            Dot::Tail => return Result::Err(ReadError::new("expected datum after .", token.span)),
+                            Dot::Close(tail) => tail,
+                        };
+                        for obj in elems.into_iter().rev() {
+                            tail = interpreter.new_object(Type::Pair(Pair::new(obj, tail)));
+                        }
+                        if tail.is_nil() {
+                            return Result::Ok(Option::Some(tail));
+                        }
+                        at(tail, span)
+                    },
+                    Option::Some(Open::Vector{span, elems}) => {
+                        at(interpreter.new_object(Type::Vector(RefCell::new(elems))), span)
+                    },
+                    Option::Some(Open::Bytevector{span, bytes}) => {
+                        at(interpreter.new_object(Type::Bytevector(RefCell::new(bytes))), span)
+                    },
+                    _ => return Result::Err(ReadError::new("unexpected )", span)),
+                };
+                return Result::Ok(Option::Some(datum));
+            },
+            TokenKind::Dot => {
+                return match self.open.last_mut() {
+                    Option::Some(&mut Open::List{ref elems, ref mut dot, ..}) if matches!(*dot, Dot::None) => {
+                        if elems.is_empty() {
+                            return Result::Err(ReadError::new("nothing before . in list", span));
+                        }
+                        *dot = Dot::Tail;
+                        Result::Ok(Option::None)
+                    },
+                    _ => Result::Err(ReadError::new("unexpected .", span)),
+                };
+            },
+            TokenKind::Symbol(s) => Type::Symbol(interpreter.intern(&s)),
+            TokenKind::String(s) => Type::String(RefCell::new(s)),
+            TokenKind::Character(c) => Type::Character(c),
+            TokenKind::Boolean(b) => return Result::Ok(Option::Some(interpreter.new_bool(b))),
+            TokenKind::Number(n) => n.into_type(),
+        };
+        Result::Ok(Option::Some(at(interpreter.new_object(t), span)))
+    }
 
-            let mut incomplete_str = actual_line.clone();
-            if self.scanning_string {
-                incomplete_str.push('\n');
+    // Adds a finished datum to the data that are open, closing those it
+    // completes. Returns the datum once it is complete at the top level.
+    fn close(&mut self, mut datum: HeapObject, interpreter: &mut Interpreter) -> Result<Option<HeapObject>, ReadError> {
+        loop {
+            match self.open.last_mut() {
+                Option::None => return Result::Ok(Option::Some(datum)),
+                Option::Some(&mut Open::List{ref mut elems, ref mut dot, ..}) => {
+                    if matches!(*dot, Dot::Tail) {
+                        *dot = Dot::Close(datum);
+                    } else {
+                        elems.push(datum);
+                    }
+                    return Result::Ok(Option::None);
+                },
+                Option::Some(&mut Open::Vector{ref mut elems, ..}) => {
+                    elems.push(datum);
+                    return Result::Ok(Option::None);
+                },
+                Option::Some(&mut Open::Bytevector{ref mut bytes, span}) => {
+                    return match datum.object_type {
+                        Type::Integer(n) if (0..=255).contains(&n) => {
+                            bytes.push(n as u8);
+                            Result::Ok(Option::None)
+                        },
+                        _ => Result::Err(ReadError::new("bytevector elements must be bytes",
+                                                        datum.span.get().unwrap_or(span))),
+                    };
+                },
+                _ => {},
             }
-            self.incomplete_str = Option::Some(incomplete_str);
-            Option::None
-        } else {
-            for token in &tokens {
-                println!("{}", token);
+
+            match self.open.pop().unwrap() {
+                Open::Quote{span, name} => {
+                    let sym = interpreter.intern(name);
+                    let sym = at(interpreter.new_object(Type::Symbol(sym)), span);
+                    let nil = interpreter.new_nil();
+                    let rest = interpreter.cons(datum, nil);
+                    datum = at(interpreter.cons(sym, rest), span);
+                },
+                Open::Label{span, n, placeholder} => {
+                    if Rc::ptr_eq(&datum, &placeholder) {
+                        return Result::Err(ReadError::new("label refers only to itself", span));
+                    }
+                    patch_label(&datum, &placeholder);
+                    self.labels.insert(n, datum.clone());
+                },
+                Open::DatumComment{..} => return Result::Ok(Option::None),
+                _ => unreachable!(),
             }
-            //flush last token
-            self.get_token(&word).map(|t| {tokens.push(t)});
-            Option::Some(Result::Ok(Box::new(tokens)))
         }
     }
 }
 
-pub fn parse_sexp(tokens: &Vec<Token>, interpreter: &mut Interpreter) -> Result<HeapObject, &'static str> {
-    if let Token::ParenOpen = tokens[0] {
-        Result::Ok(parse_list(&tokens, 1, interpreter))
-    } else {
-        if tokens.len() > 1 {
-            Result::Err("multiple sexps in input")
-        } else {
-            Result::Ok(parse(&tokens[0], interpreter))
+impl Open {
+    fn span(&self) -> Span {
+        match *self {
+            Open::List{span, ..} | Open::Vector{span, ..} | Open::Bytevector{span, ..} | Open::Quote{span, ..}
+            | Open::Label{span, ..} | Open::DatumComment{span} => span,
         }
     }
 }
 
-fn parse_list(tokens: &Vec<Token>, start: usize, interpreter: &mut Interpreter) -> HeapObject {
-    let mut list = Box::new(new_list());
-    for (i, token) in tokens.into_iter().skip(start).enumerate() {
-        match *token {
-            Token::ParenOpen => {
-                let obj = parse_list(tokens, i+1, interpreter);
-                list.as_mut().push_back(obj);
+#[inline]
+fn at(obj: HeapObject, span: Span) -> HeapObject {
+    obj.span.set(Option::Some(span));
+    obj
+}
+
+// Replaces the references to placeholder in the pairs and vectors
+// reachable from datum with datum itself.
+fn patch_label(datum: &HeapObject, placeholder: &HeapObject) {
+    let mut seen = HashSet::new();
+    let mut pending = vec![datum.clone()];
+    let patch = |slot: &mut HeapObject, pending: &mut Vec<HeapObject>| {
+        if Rc::ptr_eq(slot, placeholder) {
+            *slot = datum.clone();
+        } else {
+            pending.push(slot.clone());
+        }
+    };
+    while let Option::Some(obj) = pending.pop() {
+        if !seen.insert(&**obj as *const Object) {
+            continue;
+        }
+        match obj.object_type {
+            Type::Pair(ref p) => {
+                patch(&mut p.car.borrow_mut(), &mut pending);
+                patch(&mut p.cdr.borrow_mut(), &mut pending);
             },
-            Token::ParenClose => {
-                if list.len() == 0 {
-                    return interpreter.new_nil();
-                } else {
-                    return interpreter.new_object(Type::Cons(list));
+            Type::Vector(ref v) => {
+                for elem in v.borrow_mut().iter_mut() {
+                    patch(elem, &mut pending);
                 }
             },
-            _ => list.as_mut().push_back(parse(token, interpreter)),
+            _ => {},
         }
     }
-
-    panic!("unreachable")
 }
 
-fn parse(token: &Token, interpreter: &mut Interpreter) -> HeapObject {
-    match *token {
-        Token::Symbol(ref s) => {
-            match s.as_str() {
-                "#t" => interpreter.new_true(),
-                "#f" => interpreter.new_false(),
-                _ => interpreter.new_object(Type::Symbol(s.clone())),
-            }
-        },
-        Token::String(ref s) => interpreter.new_object(Type::String(s.clone())),
-        Token::Character(c) => interpreter.new_object(Type::Character(c)),
-        Token::Integer(i) => interpreter.new_object(Type::Integer(i)),
-        Token::Float(f) => interpreter.new_object(Type::Float(f)),
-        Token::ParenOpen | Token::ParenClose => panic!("cannot parse parens")
+// The length in bytes of the datum at the start of src, or of as much of
+// it as can be read.
+pub fn datum_len(src: &str) -> usize {
+    let mut lexer = Lexer::new(src);
+    let mut depth = 0;
+    while let Result::Ok(Option::Some(token)) = lexer.next_token() {
+        match token.kind {
+            TokenKind::ParenOpen | TokenKind::VectorOpen | TokenKind::BytevectorOpen => depth += 1,
+            TokenKind::ParenClose => depth -= 1,
+            // prefixes of the datum
+            TokenKind::Quote | TokenKind::Quasiquote | TokenKind::Unquote | TokenKind::UnquoteSplicing
+            | TokenKind::Label(_) | TokenKind::DatumComment | TokenKind::Comment(_) => continue,
+            _ => {},
+        }
+        if depth <= 0 {
+            break;
+        }
     }
+    lexer.pos
 }
 
-impl fmt::Display for Token {
-    fn fmt(&self, f: &mut fmt::Formatter) -> Result<(), fmt::Error> {
-        match *self {
-            Token::ParenOpen => write!(f, "("),
-            Token::ParenClose => write!(f, ")"),
-            Token::Symbol(ref s) => write!(f, "[sym {}]", s),
-            Token::String(ref s) => write!(f,"\"{}\"", s),
-            Token::Character(c) => write!(f, "?{}", c),
-            Token::Integer(i) => write!(f, "[i {}]", i),
-            Token::Float(fl) => write!(f, "[f {}]", fl),
-        }
-   }
+// The length in bytes of the #! line a script starts with, without its
+// line break, or 0 if there is none. The reader would take it for a
+// directive.
+pub fn shebang_len(src: &str) -> usize {
+    if src.starts_with("#!/") || src.starts_with("#! ") {
+        src.find('\n').unwrap_or(src.len())
+    } else {
+        0
+    }
 }
 
-impl fmt::Debug for ScanError {
-    fn fmt(&self, f: &mut fmt::Formatter) -> Result<(), fmt::Error> {
-        write!(f, "")
+// Reads the single datum in src.
+pub fn parse_sexp(src: &str, interpreter: &mut Interpreter) -> Result<HeapObject, ReadError> {
+    let mut reader = Reader::new(src);
+    let span = reader.lexer.span();
+    match reader.read(interpreter)? {
+        Option::Some(datum) => match reader.read(interpreter)? {
+            Option::Some(extra) => Result::Err(ReadError::new("multiple sexps in input", extra.span.get().unwrap_or(span))),
+            Option::None => Result::Ok(datum),
+        },
+        Option::None => Result::Err(ReadError::incomplete("empty input", span)),
     }
 }
 
@@ -273,19 +703,105 @@ impl fmt::Debug for ScanError {
 mod test {
     use super::*;
 
+    fn read_str(s: &str) -> Result<String, ReadError> {
+        let mut i = Interpreter::new();
+        parse_sexp(s, &mut i).map(|obj| format!("{}", obj))
+    }
+
+    fn read_err(s: &str) -> ReadError {
+        match read_str(s) {
+            Result::Ok(obj) => panic!("{} read as {}", s, obj),
+            Result::Err(e) => e,
+        }
+    }
+
     #[test]
     fn test_scan_err() {
-        let mut s = Scanner::new();
-        let res = s.scan("?abcd".to_string()).unwrap();
-        match res {
-            Result::Ok(_) => panic!("should error"),
-            Result::Err(e) => {
-                if let ScanError::InvalidChar = e {
+        assert_eq!(read_err("#\\abcd").message, "unknown character name");
+        assert_eq!(read_err("1a").message, "bad number syntax");
+        assert_eq!(read_err("1/0").message, "bad number syntax");
+        assert_eq!(read_err("#x1g").message, "bad number syntax");
+        assert_eq!(read_err("#e#i1").message, "bad number syntax");
+        assert_eq!(read_err("#u8(1 256)").message, "bytevector elements must be bytes");
+        assert!(read_err("#u8(1").incomplete);
+        assert_eq!(read_err("(1 . 2 3)").message, "expected ) after dotted tail");
+        assert_eq!(read_err("#<foo>").message, "unknown # syntax");
+        assert_eq!(read_err("\"a\\qb\"").message, "bad escape in string");
+        assert_eq!(read_err(")").message, "unexpected )");
+        assert_eq!(read_err("#1#").message, "undefined label");
+        assert!(read_err("(a (b").incomplete);
+        assert!(read_err("\"abc").incomplete);
+        assert!(!read_err("(a))").incomplete);
 
-                } else {
-                    panic!("should be invalidchar")
-                }
-            },
+        let deep = "(".repeat(MAX_DEPTH + 1);
+        assert_eq!(read_err(&deep).message, "nesting too deep");
+    }
+
+    #[test]
+    fn test_read() {
+        let cases = [
+            ("(a b . c)", "(a b . c)"),
+            ("(1 -2 3.5 .5 -1e2 +inf.0)", "(1 -2 3.5 0.5 -100.0 +inf.0)"),
+            ("(6/4 -1/3 99999999999999999999)", "(3/2 -1/3 99999999999999999999)"),
+            ("(#xff #B-101 #o17 #e1.5 #i1/4 #x#e10)", "(255 -5 15 3/2 0.25 16)"),
+            ("(+ - ... ->x a.b)", "(+ - ... ->x a.b)"),
+            ("'x", "(quote x)"),
+            ("`(a ,b ,@c)", "(quasiquote (a (unquote b) (unquote-splicing c)))"),
+            ("(#t #f #true #false)", "(#t #f #t #f)"),
+            ("|two words|", "|two words|"),
+            ("\"a\\tb\\x41;\"", "\"a\\tbA\""),
+            ("\"one \\\n    two\"", "\"one two\""),
+            ("(a ; comment\n b #| block #| nested |# |# c #;(skipped) d)", "(a b c d)"),
+            ("#(1 (2) #(3))", "#(1 (2) #(3))"),
+            ("(#u8(0 255) #u8())", "(#u8(0 255) #u8())"),
+            ("#!fold-case (Foo BAR)", "(foo bar)"),
+            ("(\tx\r\n y)", "(x y)"),
+        ];
+        for &(src, expected) in cases.iter() {
+            assert_eq!(read_str(src).unwrap(), expected, "{}", src);
         }
     }
+
+    #[test]
+    fn test_read_chars() {
+        let mut i = Interpreter::new();
+        let obj = parse_sexp("(#\\a #\\space #\\x41 #\\( #\\λ #\\newline)", &mut i).unwrap();
+        let chars: Vec<char> = Object::list_to_vec(&obj).unwrap().iter().map(|c| match c.object_type {
+            Type::Character(c) => c,
+            _ => panic!("not a character"),
+        }).collect();
+        assert_eq!(chars, vec!['a', ' ', 'A', '(', 'λ', '\n']);
+    }
+
+    #[test]
+    fn test_read_labels() {
+        let mut i = Interpreter::new();
+        let obj = parse_sexp("#0=(a b . #0#)", &mut i).unwrap();
+        let tail = obj.cdr().unwrap().cdr().unwrap();
+        assert!(Rc::ptr_eq(&obj, &tail));
+        assert_eq!(read_str("(#1=(x) #1#)").unwrap(), "((x) (x))");
+    }
+
+    #[test]
+    fn test_no_panics() {
+        let src = "(a #(1 . \"s\\x41;\\\n\") #0=#;x 'y #| #| |# ,@#\\x3bb . |q\\|| #u8( #\\ #!f 1e -.5e+3)";
+        let mut i = Interpreter::new();
+        for (end, _) in src.char_indices() {
+            let _ = Reader::new(&src[..end]).read_all(&mut i);
+            let _ = Reader::new(&src[end..]).read_all(&mut i);
+        }
+    }
+
+    #[test]
+    fn test_spans() {
+        let mut i = Interpreter::new();
+        let mut reader = Reader::new("foo\n  (bar\n   baz)");
+        let foo = reader.read(&mut i).unwrap().unwrap();
+        assert_eq!(foo.span.get(), Option::Some(Span{line: 1, column: 1, offset: 0}));
+        let list = reader.read(&mut i).unwrap().unwrap();
+        assert_eq!(list.span.get(), Option::Some(Span{line: 2, column: 3, offset: 6}));
+        let baz = list.cdr().unwrap().car().unwrap();
+        assert_eq!(baz.span.get(), Option::Some(Span{line: 3, column: 4, offset: 14}));
+        assert!(reader.read(&mut i).unwrap().is_none());
+    }
 }
@@ -0,0 +1,626 @@
+use types::{Object, Type, HeapObject, ListIter};
+use interpreter::Interpreter;
+use error::Err;
+use number::{self, format_real};
+use std::collections::{HashMap, HashSet};
+use std::fmt::{self, Write};
+use std::option::Option;
+use std::result::Result;
+
+// Which objects get datum labels, #0= where they are first printed and #0#
+// wherever they come up again.
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum Labels {
+    // none, so printing a cyclic structure never ends
+    Never,
+    // only those needed to print cycles, as write does
+    Cycles,
+    // every object that comes up more than once, as write-shared does
+    Shared,
+}
+
+// Prints objects as write does, so the reader reads them back, or as
+// display does, with strings and characters as their text.
+pub struct Printer {
+    display: bool,
+    // 'x for (quote x), and the like for quasiquote and unquote
+    abbreviate: bool,
+    // the labelled objects, and the number each has once it is printed
+    labels: HashMap<*const Object, Option<usize>>,
+    next_label: usize,
+}
+
+fn ptr(obj: &Object) -> *const Object {
+    obj as *const Object
+}
+
+// The objects printed as part of obj.
+fn children(obj: &Object) -> Vec<HeapObject> {
+    match obj.object_type {
+        Type::Pair(ref p) => vec![p.car.borrow().clone(), p.cdr.borrow().clone()],
+        Type::Vector(ref v) => v.borrow().clone(),
+        Type::Record(ref r) => r.fields.borrow().clone(),
+        Type::Error(ref e) => vec![e.irritants.clone()],
+        _ => Vec::new(),
+    }
+}
+
+fn is_container(obj: &Object) -> bool {
+    matches!(obj.object_type, Type::Pair(_) | Type::Vector(_) | Type::Record(_) | Type::Error(_))
+}
+
+enum Visit {
+    Enter(HeapObject),
+    Exit(*const Object),
+}
+
+// The containers in root that need labels. Walks depth first with a
+// stack of its own, so long lists don't overflow the Rust stack.
+fn find_labels(root: &Object, labels: Labels) -> HashSet<*const Object> {
+    let mut found = HashSet::new();
+    if labels == Labels::Never || !is_container(root) {
+        return found;
+    }
+    // for Cycles, the containers on the path from root to the current one
+    // and those already fully explored; for Shared, every one seen
+    let mut on_path = HashSet::new();
+    let mut done = HashSet::new();
+
+    on_path.insert(ptr(root));
+    let mut pending = vec![Visit::Exit(ptr(root))];
+    pending.extend(children(root).into_iter().rev().map(Visit::Enter));
+    while let Option::Some(visit) = pending.pop() {
+        let obj = match visit {
+            Visit::Enter(obj) => obj,
+            Visit::Exit(p) => {
+                on_path.remove(&p);
+                done.insert(p);
+                continue;
+            },
+        };
+        if !is_container(&obj) {
+            continue;
+        }
+        let p = ptr(&obj);
+        if on_path.contains(&p) || (labels == Labels::Shared && done.contains(&p)) {
+            found.insert(p);
+            continue;
+        }
+        if done.contains(&p) {
+            continue;
+        }
+        on_path.insert(p);
+        pending.push(Visit::Exit(p));
+        pending.extend(children(&obj).into_iter().rev().map(Visit::Enter));
+    }
+    found
+}
+
+// Whether name has to be written between bars to read back as a symbol.
+fn needs_bars(name: &str) -> bool {
+    name.is_empty() || name == "." || number::parse(name).is_some()
+        || name.starts_with(['#', '\'', '`', ','])
+        || name.chars().any(|c| c.is_whitespace() || c.is_control()
+                            || matches!(c, '(' | ')' | '"' | ';' | '|' | '\\' | '[' | ']' | '{' | '}'))
+}
+
+fn char_name(c: char) -> Option<&'static str> {
+    Option::Some(match c {
+        '\x07' => "alarm",
+        '\x08' => "backspace",
+        '\x7f' => "delete",
+        '\x1b' => "escape",
+        '\n' => "newline",
+        '\0' => "null",
+        '\r' => "return",
+        ' ' => "space",
+        '\t' => "tab",
+        _ => return Option::None,
+    })
+}
+
+// s between delimiter, with the escapes the reader understands.
+fn write_escaped(out: &mut dyn Write, s: &str, delimiter: char) -> fmt::Result {
+    out.write_char(delimiter)?;
+    for c in s.chars() {
+        match c {
+            '\\' => out.write_str("\\\\")?,
+            '\x07' => out.write_str("\\a")?,
+            '\x08' => out.write_str("\\b")?,
+            '\t' => out.write_str("\\t")?,
+            '\n' => out.write_str("\\n")?,
+            '\r' => out.write_str("\\r")?,
+            _ if c == delimiter => write!(out, "\\{}", c)?,
+            _ if c.is_control() => write!(out, "\\x{:x};", c as u32)?,
+            _ => out.write_char(c)?,
+        }
+    }
+    out.write_char(delimiter)
+}
+
+impl Printer {
+    pub fn new(root: &Object, labels: Labels, display: bool) -> Printer {
+        let labels = find_labels(root, labels).into_iter().map(|p| (p, Option::None)).collect();
+        Printer{display, abbreviate: false, labels, next_label: 0}
+    }
+
+    // Prints the label of obj, if it has one. True if that was a reference
+    // to where it was already printed, which is all there is to print.
+    fn label(&mut self, obj: &Object, out: &mut dyn Write) -> Result<bool, fmt::Error> {
+        match self.labels.get(&ptr(obj)) {
+            Option::Some(&Option::Some(n)) => {
+                write!(out, "#{}#", n)?;
+                Result::Ok(true)
+            },
+            Option::Some(&Option::None) => {
+                let n = self.next_label;
+                self.next_label += 1;
+                self.labels.insert(ptr(obj), Option::Some(n));
+                write!(out, "#{}=", n)?;
+                Result::Ok(false)
+            },
+            Option::None => Result::Ok(false),
+        }
+    }
+
+    // The prefix and datum of an abbreviable form like (quote x).
+    fn abbreviation(&self, obj: &Object) -> Option<(&'static str, HeapObject)> {
+        if !self.abbreviate {
+            return Option::None;
+        }
+        let prefix = match obj.car()?.object_type {
+            Type::Symbol(ref s) => match s.name() {
+                "quote" => "'",
+                "quasiquote" => "`",
+                "unquote" => ",",
+                "unquote-splicing" => ",@",
+                _ => return Option::None,
+            },
+            _ => return Option::None,
+        };
+        let rest = obj.cdr()?;
+        match (rest.car(), rest.cdr()) {
+            (Option::Some(datum), Option::Some(ref tail)) if tail.is_nil() && !self.labels.contains_key(&ptr(&rest)) =>
+                Option::Some((prefix, datum)),
+            _ => Option::None,
+        }
+    }
+
+    pub fn print(&mut self, obj: &Object, out: &mut dyn Write) -> fmt::Result {
+        if self.label(obj, out)? {
+            return Result::Ok(());
+        }
+        if let Option::Some((prefix, datum)) = self.abbreviation(obj) {
+            out.write_str(prefix)?;
+            return self.print(&datum, out);
+        }
+        self.print_datum(obj, out)
+    }
+
+    // Prints obj after its label.
+    fn print_datum(&mut self, obj: &Object, out: &mut dyn Write) -> fmt::Result {
+        match obj.object_type {
+            Type::Bool(b) => out.write_str(if b {"#t"} else {"#f"}),
+            Type::Integer(n) => write!(out, "{}", n),
+            Type::BigInt(ref n) => write!(out, "{}", n),
+            Type::Rational(ref r) => write!(out, "{}", r),
+            Type::Float(n) => out.write_str(&format_real(n)),
+            Type::Character(c) if self.display => out.write_char(c),
+            Type::Character(c) => match char_name(c) {
+                Option::Some(name) => write!(out, "#\\{}", name),
+                Option::None if c.is_control() => write!(out, "#\\x{:x}", c as u32),
+                Option::None => write!(out, "#\\{}", c),
+            },
+            Type::String(ref s) if self.display => out.write_str(&s.borrow()),
+            Type::String(ref s) => write_escaped(out, &s.borrow(), '"'),
+            Type::Symbol(ref s) if !self.display && needs_bars(s.name()) => write_escaped(out, s.name(), '|'),
+            Type::Symbol(ref s) => out.write_str(s.name()),
+            Type::Nil => out.write_str("()"),
+            Type::Pair(ref p) => {
+                out.write_char('(')?;
+                self.print(&p.car.borrow(), out)?;
+                let mut tail = p.cdr.borrow().clone();
+                loop {
+                    let next = match tail.object_type {
+                        Type::Nil => break,
+                        // a labelled tail has to be printed whole, after a dot
+                        Type::Pair(ref p) if !self.labels.contains_key(&ptr(&tail)) => {
+                            out.write_char(' ')?;
+                            self.print(&p.car.borrow(), out)?;
+                            p.cdr.borrow().clone()
+                        },
+                        _ => {
+                            out.write_str(" . ")?;
+                            self.print(&tail, out)?;
+                            break;
+                        },
+                    };
+                    tail = next;
+                }
+                out.write_char(')')
+            },
+            Type::Vector(ref v) => {
+                out.write_str("#(")?;
+                for (i, obj) in v.borrow().iter().enumerate() {
+                    if i > 0 {
+                        out.write_char(' ')?;
+                    }
+                    self.print(obj, out)?;
+                }
+                out.write_char(')')
+            },
+            Type::Bytevector(ref v) => {
+                out.write_str("#u8(")?;
+                for (i, byte) in v.borrow().iter().enumerate() {
+                    if i > 0 {
+                        out.write_char(' ')?;
+                    }
+                    write!(out, "{}", byte)?;
+                }
+                out.write_char(')')
+            },
+            Type::Record(ref r) => {
+                write!(out, "#<{}", r.rtd.name)?;
+                let fields = r.fields.borrow().clone();
+                for (name, value) in r.rtd.fields.iter().zip(fields) {
+                    write!(out, " {}: ", name)?;
+                    self.print(&value, out)?;
+                }
+                out.write_char('>')
+            },
+            Type::Error(ref e) => {
+                out.write_str("#<error ")?;
+                write_escaped(out, &e.message, '"')?;
+                for obj in ListIter::new(&e.irritants) {
+                    out.write_char(' ')?;
+                    self.print(&obj, out)?;
+                }
+                out.write_char('>')
+            },
+            Type::RecordType(ref rtd) => write!(out, "#<record-type {}>", rtd.name),
+            Type::HashTable(_) => out.write_str("#<hash-table>"),
+            Type::Procedure(_) => out.write_str("#<procedure>"),
+            Type::Frame(_) => out.write_str("#<environment>"),
+        }
+    }
+}
+
+// Collects printed text as long as it fits on the rest of a line, and
+// fails as soon as it doesn't, so measuring a long datum stops early.
+struct Limited {
+    text: String,
+    room: usize,
+}
+
+impl Write for Limited {
+    fn write_str(&mut self, s: &str) -> fmt::Result {
+        let len = s.chars().count();
+        if len > self.room || s.contains('\n') {
+            return Result::Err(fmt::Error);
+        }
+        self.room -= len;
+        self.text.push_str(s);
+        Result::Ok(())
+    }
+}
+
+// How many forms after the keyword of a special form stay on its first
+// line when it is broken, with the body indented two columns under it.
+// named is whether the form after the keyword is a symbol.
+pub fn header_forms(keyword: &str, named: bool) -> Option<usize> {
+    Option::Some(match keyword {
+        "let" if named => 2,
+        "define" | "define-syntax" | "define-values" | "define-record-type" | "lambda" | "let" | "let*"
+        | "letrec" | "letrec*" | "let-values" | "let*-values" | "let-syntax" | "letrec-syntax"
+        | "syntax-rules" | "when" | "unless" | "case" | "guard" | "parameterize" => 1,
+        "do" => 2,
+        "begin" | "case-lambda" => 0,
+        _ => return Option::None,
+    })
+}
+
+// The column the end of out is at.
+fn column(out: &str) -> usize {
+    out[out.rfind('\n').map_or(0, |i| i + 1)..].chars().count()
+}
+
+fn newline(out: &mut String, indent: usize) {
+    out.push('\n');
+    out.extend(::std::iter::repeat_n(' ', indent));
+}
+
+// Pretty-printing, which writes a datum on one line if it fits and breaks
+// it over several otherwise.
+impl Printer {
+    fn pretty(&mut self, obj: &Object, width: usize, out: &mut String) {
+        let mut flat = Limited{text: String::new(), room: width.saturating_sub(column(out))};
+        // measuring numbers the labels it prints, which has to be undone
+        let saved = if self.labels.is_empty() {Option::None} else {Option::Some(self.labels.clone())};
+        let next_label = self.next_label;
+        if self.print(obj, &mut flat).is_ok() {
+            out.push_str(&flat.text);
+            return;
+        }
+        if let Option::Some(labels) = saved {
+            self.labels = labels;
+        }
+        self.next_label = next_label;
+
+        // writing to a String can't fail
+        if self.label(obj, out).unwrap() {
+            return;
+        }
+        if let Option::Some((prefix, datum)) = self.abbreviation(obj) {
+            out.push_str(prefix);
+            return self.pretty(&datum, width, out);
+        }
+        match obj.object_type {
+            Type::Pair(_) => self.pretty_list(obj, width, out),
+            Type::Vector(ref v) => {
+                out.push_str("#(");
+                let indent = column(out);
+                let elems = v.borrow().clone();
+                for (i, elem) in elems.iter().enumerate() {
+                    if i > 0 {
+                        newline(out, indent);
+                    }
+                    self.pretty(elem, width, out);
+                }
+                out.push(')');
+            },
+            _ => self.print_datum(obj, out).unwrap(),
+        }
+    }
+
+    // Special forms keep their header forms on the first line, calls line
+    // their arguments up after the operator and other lists line their
+    // elements up under the first.
+    fn pretty_list(&mut self, obj: &Object, width: usize, out: &mut String) {
+        let start = column(out);
+        let mut elems = vec![obj.car().unwrap()];
+        let mut tail = obj.cdr().unwrap();
+        loop {
+            let next = match tail.object_type {
+                Type::Pair(ref p) if !self.labels.contains_key(&ptr(&tail)) => {
+                    elems.push(p.car.borrow().clone());
+                    p.cdr.borrow().clone()
+                },
+                _ => break,
+            };
+            tail = next;
+        }
+
+        out.push('(');
+        let mut rest = &elems[1..];
+        let indent = match elems[0].object_type {
+            Type::Symbol(ref keyword) => {
+                self.print(&elems[0], out).unwrap();
+                let named = elems.get(1).is_some_and(|name| matches!(name.object_type, Type::Symbol(_)));
+                match header_forms(keyword.name(), named) {
+                    Option::Some(n) => {
+                        let n = n.min(rest.len());
+                        for elem in &rest[..n] {
+                            out.push(' ');
+                            self.pretty(elem, width, out);
+                        }
+                        rest = &rest[n..];
+                        start + 2
+                    },
+                    // too far right to line arguments up after the operator
+                    Option::None if column(out) + 1 > start + (width - start) / 2 || rest.is_empty() => start + 2,
+                    Option::None => {
+                        out.push(' ');
+                        let indent = column(out);
+                        self.pretty(&rest[0], width, out);
+                        rest = &rest[1..];
+                        indent
+                    },
+                }
+            },
+            _ => {
+                self.pretty(&elems[0], width, out);
+                start + 1
+            },
+        };
+        for elem in rest {
+            newline(out, indent);
+            self.pretty(elem, width, out);
+        }
+        if !tail.is_nil() {
+            newline(out, indent);
+            out.push_str(". ");
+            self.pretty(&tail, width, out);
+        }
+        out.push(')');
+    }
+}
+
+// The width pretty-print lays data out in by default.
+pub const WIDTH: usize = 80;
+
+// obj as write prints it, but laid out over several lines where it
+// doesn't fit in width columns, with code indented in the usual style.
+pub fn pretty(obj: &Object, width: usize) -> String {
+    let mut printer = Printer::new(obj, Labels::Cycles, false);
+    printer.abbreviate = true;
+    let mut out = String::new();
+    printer.pretty(obj, width, &mut out);
+    out
+}
+
+fn to_string(obj: &Object, labels: Labels, display: bool) -> String {
+    let mut s = String::new();
+    Printer::new(obj, labels, display).print(obj, &mut s).unwrap();
+    s
+}
+
+// As the write procedure prints obj.
+pub fn write(obj: &Object) -> String {
+    to_string(obj, Labels::Cycles, false)
+}
+
+pub fn write_shared(obj: &Object) -> String {
+    to_string(obj, Labels::Shared, false)
+}
+
+pub fn write_simple(obj: &Object) -> String {
+    to_string(obj, Labels::Never, false)
+}
+
+// As the display procedure prints obj.
+pub fn display(obj: &Object) -> String {
+    to_string(obj, Labels::Cycles, true)
+}
+
+// The output primitives, which print to standard output.
+impl Interpreter {
+    fn print_with(&mut self, args: &[HeapObject], print: fn(&Object) -> String) -> Result<HeapObject, Err> {
+        print!("{}", print(&args[0]));
+        Result::Ok(self.new_nil())
+    }
+
+    pub fn write(&mut self, args: &[HeapObject]) -> Result<HeapObject, Err> {
+        self.print_with(args, write)
+    }
+
+    pub fn write_shared(&mut self, args: &[HeapObject]) -> Result<HeapObject, Err> {
+        self.print_with(args, write_shared)
+    }
+
+    pub fn write_simple(&mut self, args: &[HeapObject]) -> Result<HeapObject, Err> {
+        self.print_with(args, write_simple)
+    }
+
+    pub fn display(&mut self, args: &[HeapObject]) -> Result<HeapObject, Err> {
+        self.print_with(args, display)
+    }
+
+    pub fn write_string(&mut self, args: &[HeapObject]) -> Result<HeapObject, Err> {
+        match args[0].object_type {
+            Type::String(_) => self.display(args),
+            _ => Result::Err(self.wrong_type("string", &args[0])),
+        }
+    }
+
+    pub fn write_char(&mut self, args: &[HeapObject]) -> Result<HeapObject, Err> {
+        match args[0].object_type {
+            Type::Character(_) => self.display(args),
+            _ => Result::Err(self.wrong_type("character", &args[0])),
+        }
+    }
+
+    // (pretty-print obj [width])
+    pub fn pretty_print(&mut self, args: &[HeapObject]) -> Result<HeapObject, Err> {
+        let width = match args.get(1) {
+            Option::Some(obj) => self.index(obj, usize::MAX, true)?,
+            Option::None => WIDTH,
+        };
+        println!("{}", pretty(&args[0], width));
+        Result::Ok(self.new_nil())
+    }
+
+    pub fn newline(&mut self, _args: &[HeapObject]) -> Result<HeapObject, Err> {
+        println!();
+        Result::Ok(self.new_nil())
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use interpreter::Interpreter;
+    use parse::parse_sexp;
+    use test_util::eval_obj;
+    use super::{write, write_shared, display, pretty};
+
+    #[test]
+    fn test_write() {
+        let mut i = Interpreter::new();
+        let cases = [
+            ("(quote (a . b))", "(a . b)"),
+            ("(quote (1 (2 3) . #(4 \"five\")))", "(1 (2 3) . #(4 \"five\"))"),
+            ("(list #t #f (quote ()))", "(#t #f ())"),
+            ("\"say \\\"hi\\\"\\n\\ttab\\\\\"", "\"say \\\"hi\\\"\\n\\ttab\\\\\""),
+            ("(string #\\x1)", "\"\\x1;\""),
+            ("(list #\\a #\\space #\\newline #\\λ #\\x0)", "(#\\a #\\space #\\newline #\\λ #\\null)"),
+            ("(string->symbol \"hello world\")", "|hello world|"),
+            ("(string->symbol \"\")", "||"),
+            ("(string->symbol \"12\")", "|12|"),
+            ("(string->symbol \"a|b\")", "|a\\|b|"),
+            ("(quote |x|)", "x"),
+            ("(quote ...)", "..."),
+            ("1.5", "1.5"),
+            ("#u8(1 2)", "#u8(1 2)"),
+            ("car", "#<procedure>"),
+        ];
+        for &(src, expected) in cases.iter() {
+            let obj = eval_obj(&mut i, src);
+            assert_eq!(write(&obj), expected, "{}", src);
+            // what write prints reads back as an equal datum
+            let read = parse_sexp(&write(&obj), &mut i);
+            if let Result::Ok(read) = read {
+                if !src.starts_with("car") {
+                    assert_eq!(write(&read), expected, "{}", src);
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn test_display() {
+        let mut i = Interpreter::new();
+        let cases = [
+            ("(list \"a b\" #\\c (string->symbol \"d e\"))", "(a b c d e)"),
+            ("\"line\\n\"", "line\n"),
+            ("#\\space", " "),
+        ];
+        for &(src, expected) in cases.iter() {
+            assert_eq!(display(&eval_obj(&mut i, src)), expected, "{}", src);
+        }
+    }
+
+    #[test]
+    fn test_labels() {
+        let mut i = Interpreter::new();
+        let cases = [
+            ("(let ((x (list 1 2 3))) (set-cdr! (cdr (cdr x)) x) x)", "#0=(1 2 3 . #0#)", "#0=(1 2 3 . #0#)"),
+            ("(let ((x (list 1 2))) (set-car! x x) x)", "#0=(#0# 2)", "#0=(#0# 2)"),
+            ("(let ((v (vector 1 2))) (vector-set! v 1 v) v)", "#0=#(1 #0#)", "#0=#(1 #0#)"),
+            ("(let ((x (list 1))) (list x x))", "((1) (1))", "(#0=(1) #0#)"),
+            ("(let ((x (list 1 2)) (y (list 3))) (set-cdr! (cdr x) x) (set-cdr! y y) (list x y x))",
+             "(#0=(1 2 . #0#) #1=(3 . #1#) #0#)", "(#0=(1 2 . #0#) #1=(3 . #1#) #0#)"),
+            ("(let ((x (list 1 2))) (cons 0 x))", "(0 1 2)", "(0 1 2)"),
+        ];
+        for &(src, written, shared) in cases.iter() {
+            let obj = eval_obj(&mut i, src);
+            assert_eq!(write(&obj), written, "{}", src);
+            assert_eq!(write_shared(&obj), shared, "{}", src);
+        }
+    }
+
+    #[test]
+    fn test_pretty() {
+        let mut i = Interpreter::new();
+        let cases = [
+            ("(quote (a b c))", 80, "(a b c)"),
+            ("(quote (a (quote b) (quasiquote (c (unquote d) (unquote-splicing e)))))", 80, "(a 'b `(c ,d ,@e))"),
+            ("(quote (1000 2000 (3000 4000) 5000))", 14, "(1000\n 2000\n (3000 4000)\n 5000)"),
+            ("(quote (aaaa bbbb (cccc dddd) eeee))", 12, "(aaaa bbbb\n      (cccc\n        dddd)\n      eeee)"),
+            ("(quote (define (f x) (if (< x 0) (- x) x)))", 24,
+             "(define (f x)\n  (if (< x 0) (- x) x))"),
+            ("(quote (define (f x) (if (< x 0) (- x) x)))", 20,
+             "(define (f x)\n  (if (< x 0)\n      (- x)\n      x))"),
+            ("(quote (let loop ((i 0)) (if (< i 10) (loop (+ i 1)) i)))", 34,
+             "(let loop ((i 0))\n  (if (< i 10) (loop (+ i 1)) i))"),
+            ("(quote (lambda (x y) (display x) (display y)))", 20,
+             "(lambda (x y)\n  (display x)\n  (display y))"),
+            ("(quote (cond ((null? x) 0) (else (car x))))", 20,
+             "(cond ((null? x) 0)\n      (else (car x)))"),
+            ("(quote (1 2 . 3))", 5, "(1\n 2\n . 3)"),
+            ("(vector 100 200 300)", 8, "#(100\n  200\n  300)"),
+            ("(let ((x (list 1 2 3))) (set-cdr! (cdr (cdr x)) x) x)", 6, "#0=(1\n    2\n    3\n    . #0#)"),
+        ];
+        for &(src, width, expected) in cases.iter() {
+            assert_eq!(pretty(&eval_obj(&mut i, src), width), expected, "{}", src);
+        }
+    }
+}
@@ -0,0 +1,198 @@
+use interpreter::Interpreter;
+use types::{Object, Type, HeapObject, Procedure};
+use symbol::Symbol;
+use error::Err;
+use std::cell::RefCell;
+use std::rc::Rc;
+use std::option::Option;
+use std::result::Result;
+
+// What define-record-type binds the type name to. Types are compared by
+// identity, so two definitions with the same name make distinct types.
+pub struct RecordType {
+    // point for a type named <point>
+    pub name: String,
+    pub fields: Vec<Symbol>,
+}
+
+pub struct Record {
+    pub rtd: Rc<RecordType>,
+    pub fields: RefCell<Vec<HeapObject>>,
+}
+
+// The procedures define-record-type makes, each bound to its type.
+pub enum RecordProc {
+    // the index of the field each argument initializes
+    Constructor(Rc<RecordType>, Vec<usize>),
+    Predicate(Rc<RecordType>),
+    Accessor(Rc<RecordType>, usize),
+    Modifier(Rc<RecordType>, usize),
+}
+
+impl Interpreter {
+    fn as_record_type<'a>(&self, obj: &'a HeapObject) -> Result<&'a Rc<RecordType>, Err> {
+        match obj.object_type {
+            Type::RecordType(ref rtd) => Result::Ok(rtd),
+            _ => Result::Err(self.wrong_type("record-type", obj)),
+        }
+    }
+
+    // The index of the field named by obj.
+    fn field(&self, rtd: &RecordType, obj: &HeapObject) -> Result<usize, Err> {
+        let name = match obj.object_type {
+            Type::Symbol(ref name) => name,
+            _ => return Result::Err(self.wrong_type("symbol", obj)),
+        };
+        match rtd.fields.iter().position(|field| field == name) {
+            Option::Some(i) => Result::Ok(i),
+            Option::None => Result::Err(self.bad_syntax("define-record-type")),
+        }
+    }
+
+    fn new_record_proc(&mut self, proc: RecordProc) -> HeapObject {
+        self.new_object(Type::Procedure(Box::new(Procedure::Record(proc))))
+    }
+
+    // (make-record-type name fields), with the field names in a list
+    pub fn make_record_type(&mut self, args: &[HeapObject]) -> Result<HeapObject, Err> {
+        let name = match args[0].object_type {
+            Type::Symbol(ref name) => name.name(),
+            _ => return Result::Err(self.wrong_type("symbol", &args[0])),
+        };
+        let name = match name.strip_prefix('<').and_then(|name| name.strip_suffix('>')) {
+            Option::Some(inner) if !inner.is_empty() => inner.to_string(),
+            _ => name.to_string(),
+        };
+        let mut fields = Vec::new();
+        for obj in Object::list_to_vec(&args[1]).ok_or_else(|| self.wrong_type("list", &args[1]))? {
+            match obj.object_type {
+                Type::Symbol(ref field) if !fields.contains(field) => fields.push(field.clone()),
+                _ => return Result::Err(self.bad_syntax("define-record-type")),
+            }
+        }
+        Result::Ok(self.new_object(Type::RecordType(Rc::new(RecordType{name, fields}))))
+    }
+
+    // (record-constructor rtd fields), taking the listed fields in order
+    pub fn record_constructor(&mut self, args: &[HeapObject]) -> Result<HeapObject, Err> {
+        let rtd = self.as_record_type(&args[0])?.clone();
+        let names = Object::list_to_vec(&args[1]).ok_or_else(|| self.wrong_type("list", &args[1]))?;
+        let fields = names.iter().map(|name| self.field(&rtd, name)).collect::<Result<Vec<usize>, Err>>()?;
+        Result::Ok(self.new_record_proc(RecordProc::Constructor(rtd, fields)))
+    }
+
+    pub fn record_predicate(&mut self, args: &[HeapObject]) -> Result<HeapObject, Err> {
+        let rtd = self.as_record_type(&args[0])?.clone();
+        Result::Ok(self.new_record_proc(RecordProc::Predicate(rtd)))
+    }
+
+    // (record-accessor rtd field)
+    pub fn record_accessor(&mut self, args: &[HeapObject]) -> Result<HeapObject, Err> {
+        let rtd = self.as_record_type(&args[0])?.clone();
+        let i = self.field(&rtd, &args[1])?;
+        Result::Ok(self.new_record_proc(RecordProc::Accessor(rtd, i)))
+    }
+
+    // (record-modifier rtd field)
+    pub fn record_modifier(&mut self, args: &[HeapObject]) -> Result<HeapObject, Err> {
+        let rtd = self.as_record_type(&args[0])?.clone();
+        let i = self.field(&rtd, &args[1])?;
+        Result::Ok(self.new_record_proc(RecordProc::Modifier(rtd, i)))
+    }
+
+    // The fields of obj if it is a record of type rtd.
+    fn record_of<'a>(&self, rtd: &Rc<RecordType>, obj: &'a HeapObject) -> Result<&'a RefCell<Vec<HeapObject>>, Err> {
+        match obj.object_type {
+            Type::Record(ref r) if Rc::ptr_eq(&r.rtd, rtd) => Result::Ok(&r.fields),
+            _ => Result::Err(self.wrong_type(rtd.name.clone(), obj)),
+        }
+    }
+
+    pub fn call_record_proc(&mut self, proc: &RecordProc, args: &[HeapObject]) -> Result<HeapObject, Err> {
+        match *proc {
+            RecordProc::Constructor(ref rtd, ref indices) => {
+                self.check_args(indices.len(), args.len())?;
+                let mut fields = vec![self.new_false(); rtd.fields.len()];
+                for (&i, arg) in indices.iter().zip(args) {
+                    fields[i] = arg.clone();
+                }
+                let record = Record{rtd: rtd.clone(), fields: RefCell::new(fields)};
+                Result::Ok(self.new_object(Type::Record(record)))
+            },
+            RecordProc::Predicate(ref rtd) => {
+                self.check_args(1, args.len())?;
+                let is = matches!(args[0].object_type, Type::Record(ref r) if Rc::ptr_eq(&r.rtd, rtd));
+                Result::Ok(self.new_bool(is))
+            },
+            RecordProc::Accessor(ref rtd, i) => {
+                self.check_args(1, args.len())?;
+                let value = self.record_of(rtd, &args[0])?.borrow()[i].clone();
+                Result::Ok(value)
+            },
+            RecordProc::Modifier(ref rtd, i) => {
+                self.check_args(2, args.len())?;
+                self.record_of(rtd, &args[0])?.borrow_mut()[i] = args[1].clone();
+                Result::Ok(self.new_nil())
+            },
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use interpreter::Interpreter;
+    use error::ErrType;
+    use test_util::{eval, eval_str, assert_eval};
+
+    #[test]
+    fn test_records() {
+        let mut i = Interpreter::new();
+        eval(&mut i, "(define-record-type <point> (make-point x y) point? (x point-x set-point-x!) (y point-y))");
+        eval(&mut i, "(define p (make-point 1 2))");
+        let cases = [
+            ("p", "#<point x: 1 y: 2>"),
+            ("(point-x p)", "1"),
+            ("(point-y p)", "2"),
+            ("(begin (set-point-x! p 10) (point-x p))", "10"),
+            ("(point? p)", "#t"),
+            ("(point? (vector 1 2))", "#f"),
+            ("<point>", "#<record-type point>"),
+            ("(let () (define-record-type node (make-node value) node? (value node-value) (next node-next set-node-next!))
+               (make-node 1))", "#<node value: 1 next: #f>"),
+            // a second definition with the same name is a different type
+            ("(let () (define-record-type <point> (mp x) other? (x px)) (other? p))", "#f"),
+        ];
+        for &(src, expected) in cases.iter() {
+            assert_eval(&mut i, src, expected);
+        }
+
+        match eval_str(&mut i, "(point-x (vector 1))").map_err(|e| e.err_type) {
+            Result::Err(ErrType::WrongType{ref wanted, ref got}) => {
+                assert_eq!(wanted, "point");
+                assert_eq!(got, "vector");
+            },
+            _ => panic!("accessor should reject a vector"),
+        }
+        match eval_str(&mut i, "(let () (define-record-type <line> (ml) line?) (point-y (ml)))").map_err(|e| e.err_type) {
+            Result::Err(ErrType::WrongType{ref wanted, ref got}) => {
+                assert_eq!(wanted, "point");
+                assert_eq!(got, "line");
+            },
+            _ => panic!("accessor should reject a record of another type"),
+        }
+        assert!(matches!(eval_str(&mut i, "(make-point 1)").map_err(|e| e.err_type),
+                         Result::Err(ErrType::WrongArgsNum{..})));
+    }
+
+    #[test]
+    fn test_gc_records() {
+        let mut i = Interpreter::new();
+        eval(&mut i, "(define-record-type <cell> (make-cell value) cell? (value cell-value set-cell-value!))");
+        eval(&mut i, "(define c (make-cell (list 1 2)))");
+        i.gc();
+        // a record holding itself is still freed
+        eval(&mut i, "(let ((d (make-cell 0))) (set-cell-value! d d))");
+        assert!(i.gc() > 0);
+        assert_eval(&mut i, "(cell-value c)", "(1 2)");
+    }
+}
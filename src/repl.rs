@@ -0,0 +1,251 @@
+// The interactive REPL: line editing, history and completion, input
+// continued over lines until its parentheses balance, and meta-commands
+// that start with a comma.
+
+use skeem::interpreter::Interpreter;
+use skeem::parse::{Lexer, Reader, TokenKind};
+use skeem::error::{Err, ErrType, Source};
+use skeem::types::HeapObject;
+use skeem::printer;
+use ctrlc;
+use rustyline::{Editor, Helper, Context};
+use rustyline::completion::Completer;
+use rustyline::highlight::Highlighter;
+use rustyline::hint::Hinter;
+use rustyline::validate::Validator;
+use rustyline::history::DefaultHistory;
+use rustyline::error::ReadlineError;
+use std::env;
+use std::path::PathBuf;
+use std::rc::Rc;
+use std::sync::atomic::Ordering;
+use std::time::Instant;
+use std::option::Option;
+use std::result::Result;
+
+const HELP: &str = "\
+,help          show this help
+,load FILE     evaluate the data in FILE
+,time EXPR     evaluate EXPR and show how long it took
+,expand EXPR   show EXPR with its macros expanded
+,gc            collect garbage now";
+
+// Completes the names bound at the top level.
+struct Names {
+    // sorted
+    names: Vec<String>,
+}
+
+impl Completer for Names {
+    type Candidate = String;
+
+    fn complete(&self, line: &str, pos: usize, _: &Context) -> rustyline::Result<(usize, Vec<String>)> {
+        let start = line[..pos].rfind(|ch: char| ch.is_whitespace() || "()'`,\";|#".contains(ch))
+            .map_or(0, |i| i + 1);
+        let prefix = &line[start..pos];
+        let first = self.names.partition_point(|name| name.as_str() < prefix);
+        let matches = self.names[first..].iter().take_while(|name| name.starts_with(prefix)).cloned().collect();
+        Result::Ok((start, matches))
+    }
+}
+
+impl Hinter for Names {
+    type Hint = String;
+}
+
+impl Highlighter for Names {}
+
+impl Validator for Names {}
+
+impl Helper for Names {}
+
+// Whether input stops in the middle of a datum.
+fn incomplete(input: &str) -> bool {
+    let mut lexer = Lexer::new(input);
+    let mut depth = 0;
+    // a quote or a label waiting for its datum
+    let mut prefix = false;
+    loop {
+        let token = match lexer.next_token() {
+            Result::Ok(Option::Some(token)) => token,
+            Result::Ok(Option::None) => return depth > 0 || prefix,
+            Result::Err(e) => return e.incomplete,
+        };
+        prefix = match token.kind {
+            TokenKind::Comment(_) => continue,
+            TokenKind::ParenOpen | TokenKind::VectorOpen | TokenKind::BytevectorOpen => {
+                depth += 1;
+                false
+            },
+            TokenKind::ParenClose => {
+                depth -= 1;
+                false
+            },
+            TokenKind::Quote | TokenKind::Quasiquote | TokenKind::Unquote | TokenKind::UnquoteSplicing
+            | TokenKind::DatumComment | TokenKind::Label(_) => true,
+            _ => false,
+        };
+    }
+}
+
+// Prints the value or the error. Returns the status (exit) was called
+// with, if it was.
+fn print_result(res: Result<HeapObject, Err>) -> Option<i32> {
+    match res {
+        Result::Err(Err{err_type: ErrType::Exit(status), ..}) => return Option::Some(status),
+        // continuation lines line up under the value after "=> "
+        Result::Ok(obj) => println!("=> {}", printer::pretty(&obj, printer::WIDTH - 3).replace('\n', "\n   ")),
+        Result::Err(err) => println!("error: {}", err),
+    }
+    Option::None
+}
+
+// Evaluates the data in input, each on its own, and prints their values.
+fn eval(i: &mut Interpreter, input: &str, expand: bool) -> Option<i32> {
+    let source = Rc::new(Source::new("<stdin>", input.to_string()));
+    i.gc_disable();
+    let data = Reader::new(input).read_all(i);
+    i.gc_enable();
+    match data {
+        Result::Ok(data) => for datum in data {
+            let res = if expand {i.macroexpand(&[datum])} else {i.eval_source(datum, &source)};
+            if let Option::Some(status) = print_result(res) {
+                return Option::Some(status);
+            }
+        },
+        Result::Err(err) => println!("error: {}", Err::read(err, &source)),
+    }
+    Option::None
+}
+
+fn command(i: &mut Interpreter, input: &str) -> Option<i32> {
+    let input = &input[1..];
+    let (name, arg) = match input.find(char::is_whitespace) {
+        Option::Some(n) => (&input[..n], input[n..].trim()),
+        Option::None => (input, ""),
+    };
+    match name {
+        "help" => println!("{}", HELP),
+        "load" if !arg.is_empty() => {
+            match i.load_file(arg) {
+                Result::Ok(()) => {},
+                Result::Err(Err{err_type: ErrType::Exit(status), ..}) => return Option::Some(status),
+                Result::Err(e) => println!("error: {}", e),
+            }
+        },
+        "time" if !arg.is_empty() => {
+            let start = Instant::now();
+            let status = eval(i, arg, false);
+            println!("; {:?}", start.elapsed());
+            return status;
+        },
+        "expand" if !arg.is_empty() => return eval(i, arg, true),
+        "gc" => println!("; freed {} objects", i.gc()),
+        _ => println!("unknown command ,{}, try ,help", input),
+    }
+    Option::None
+}
+
+// Reads and evaluates input until the end of it or (exit). Returns the
+// exit status.
+pub fn repl(i: &mut Interpreter) -> i32 {
+    let mut editor: Editor<Names, DefaultHistory> = match Editor::new() {
+        Result::Ok(editor) => editor,
+        Result::Err(e) => {
+            eprintln!("{}", e);
+            return 1;
+        },
+    };
+    let history = env::var_os("HOME").map(|home| PathBuf::from(home).join(".skeem_history"));
+    if let Option::Some(ref path) = history {
+        // there is none the first time
+        let _ = editor.load_history(path);
+    }
+    // at the prompt the editor reads Ctrl-C as a key, while evaluating it
+    // interrupts the evaluation
+    let interrupted = i.interrupt_flag();
+    let flag = interrupted.clone();
+    if let Result::Err(e) = ctrlc::set_handler(move || flag.store(true, Ordering::Relaxed)) {
+        eprintln!("{}", e);
+    }
+
+    // lines read so far of an incomplete datum
+    let mut input = String::new();
+    let status = loop {
+        // what the last input defined can be completed
+        editor.set_helper(Option::Some(Names{names: i.bindings().into_iter().map(|(name, _)| name).collect()}));
+        match editor.readline(if input.is_empty() {"LISP> "} else {"> "}) {
+            Result::Ok(line) => {
+                input.push_str(&line);
+                input.push('\n');
+            },
+            Result::Err(ReadlineError::Interrupted) => {
+                input.clear();
+                continue;
+            },
+            Result::Err(ReadlineError::Eof) => break 0,
+            Result::Err(e) => {
+                eprintln!("{}", e);
+                break 1;
+            },
+        }
+        if input.trim().is_empty() {
+            input.clear();
+            continue;
+        }
+        if incomplete(&input) {
+            continue;
+        }
+
+        let _ = editor.add_history_entry(input.trim_end());
+        interrupted.store(false, Ordering::Relaxed);
+        let exit = if input.trim_start().starts_with(',') {
+            command(i, input.trim())
+        } else {
+            eval(i, &input, false)
+        };
+        input.clear();
+        if let Option::Some(status) = exit {
+            break status;
+        }
+    };
+
+    if let Option::Some(ref path) = history {
+        if let Result::Err(e) = editor.save_history(path) {
+            eprintln!("{}: {}", path.display(), e);
+        }
+    }
+    status
+}
+
+#[cfg(test)]
+mod test {
+    use super::{incomplete, Names};
+    use rustyline::Context;
+    use rustyline::completion::Completer;
+    use rustyline::history::DefaultHistory;
+
+    #[test]
+    fn test_incomplete() {
+        for &src in ["(a (b)", "\"abc", "#| comment", "'", "(a ; )\n", "#(1 2", "#0="].iter() {
+            assert!(incomplete(src), "{}", src);
+        }
+        for &src in ["(a (b))", "x", "\"a(\"", "; (\n", "(a))", "1a", "'x"].iter() {
+            assert!(!incomplete(src), "{}", src);
+        }
+    }
+
+    #[test]
+    fn test_complete() {
+        let names = Names{names: ["car", "cdr", "string-append", "string-length"].iter().map(|s| s.to_string()).collect()};
+        let history = DefaultHistory::new();
+        let context = Context::new(&history);
+        let (start, matches) = names.complete("(display (string-", 17, &context).unwrap();
+        assert_eq!(start, 10);
+        assert_eq!(matches, vec!["string-append", "string-length"]);
+        let (start, matches) = names.complete("'c x", 2, &context).unwrap();
+        assert_eq!(start, 1);
+        assert_eq!(matches, vec!["car", "cdr"]);
+        assert!(names.complete("(x", 2, &context).unwrap().1.is_empty());
+    }
+}
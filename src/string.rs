@@ -0,0 +1,562 @@
+use interpreter::{Interpreter, Step, Each};
+use types::{Object, Type, HeapObject};
+use error::Err;
+use std::cell::RefCell;
+use std::cmp::Ordering;
+use std::option::Option;
+use std::result::Result;
+use std::string::String;
+
+// The byte offset of the char at index i of s, or of its end.
+fn offset(s: &str, i: usize) -> usize {
+    s.char_indices().nth(i).map_or(s.len(), |(offset, _)| offset)
+}
+
+fn fold(s: &str) -> String {
+    s.to_lowercase()
+}
+
+// The single char c maps to, or c itself where the mapping is to several
+// chars, as for the upper case of ß.
+fn single(mut mapped: impl Iterator<Item = char>, c: char) -> char {
+    match (mapped.next(), mapped.next()) {
+        (Option::Some(m), Option::None) => m,
+        _ => c,
+    }
+}
+
+fn upcase(c: char) -> char {
+    single(c.to_uppercase(), c)
+}
+
+fn downcase(c: char) -> char {
+    single(c.to_lowercase(), c)
+}
+
+// The string and character primitives. Strings are indexed by char, the
+// Unicode scalar values they hold, not by byte.
+impl Interpreter {
+    fn as_string<'a>(&self, obj: &'a HeapObject) -> Result<&'a RefCell<String>, Err> {
+        match obj.object_type {
+            Type::String(ref s) => Result::Ok(s),
+            _ => Result::Err(self.wrong_type("string", obj)),
+        }
+    }
+
+    pub fn as_char(&self, obj: &HeapObject) -> Result<char, Err> {
+        match obj.object_type {
+            Type::Character(c) => Result::Ok(c),
+            _ => Result::Err(self.wrong_type("character", obj)),
+        }
+    }
+
+    fn strings(&self, args: &[HeapObject]) -> Result<Vec<String>, Err> {
+        args.iter().map(|obj| self.as_string(obj).map(|s| s.borrow().clone())).collect()
+    }
+
+    fn chars(&self, args: &[HeapObject]) -> Result<Vec<char>, Err> {
+        args.iter().map(|obj| self.as_char(obj)).collect()
+    }
+
+    #[inline]
+    pub fn new_string(&mut self, s: String) -> HeapObject {
+        self.new_object(Type::String(RefCell::new(s)))
+    }
+
+    #[inline]
+    fn new_char(&mut self, c: char) -> HeapObject {
+        self.new_object(Type::Character(c))
+    }
+
+    // The byte offsets in s of the optional start and end char indices in
+    // args[at], defaulting to all of s.
+    pub fn string_range(&self, s: &str, args: &[HeapObject], at: usize) -> Result<(usize, usize), Err> {
+        let (start, end) = self.range(args, at, s.chars().count())?;
+        let first = offset(s, start);
+        Result::Ok((first, first + offset(&s[first..], end - start)))
+    }
+
+    pub fn string_p(&mut self, args: &[HeapObject]) -> Result<HeapObject, Err> {
+        Result::Ok(self.new_bool(matches!(args[0].object_type, Type::String(_))))
+    }
+
+    // (make-string k [char])
+    pub fn make_string(&mut self, args: &[HeapObject]) -> Result<HeapObject, Err> {
+        let len = self.length(&args[0])?;
+        let fill = match args.get(1) {
+            Option::Some(obj) => self.as_char(obj)?,
+            Option::None => ' ',
+        };
+        let mut s = String::new();
+        len.checked_mul(fill.len_utf8())
+            .and_then(|bytes| s.try_reserve_exact(bytes).ok())
+            .ok_or_else(|| self.too_long(len))?;
+        s.extend(::std::iter::repeat_n(fill, len));
+        Result::Ok(self.new_string(s))
+    }
+
+    pub fn string(&mut self, args: &[HeapObject]) -> Result<HeapObject, Err> {
+        let s = self.chars(args)?.into_iter().collect();
+        Result::Ok(self.new_string(s))
+    }
+
+    pub fn string_length(&mut self, args: &[HeapObject]) -> Result<HeapObject, Err> {
+        let len = self.as_string(&args[0])?.borrow().chars().count();
+        Result::Ok(self.new_object(Type::Integer(len as i64)))
+    }
+
+    pub fn string_ref(&mut self, args: &[HeapObject]) -> Result<HeapObject, Err> {
+        let c = {
+            let s = self.as_string(&args[0])?.borrow();
+            let i = self.index(&args[1], s.chars().count(), false)?;
+            s.chars().nth(i).unwrap()
+        };
+        Result::Ok(self.new_char(c))
+    }
+
+    pub fn string_set(&mut self, args: &[HeapObject]) -> Result<HeapObject, Err> {
+        let c = self.as_char(&args[2])?;
+        let mut s = self.as_string(&args[0])?.borrow_mut();
+        let i = self.index(&args[1], s.chars().count(), false)?;
+        let start = offset(&s, i);
+        let end = start + s[start..].chars().next().unwrap().len_utf8();
+        s.replace_range(start..end, c.encode_utf8(&mut [0; 4]));
+        drop(s);
+        Result::Ok(self.new_nil())
+    }
+
+    // (substring s start [end])
+    pub fn substring(&mut self, args: &[HeapObject]) -> Result<HeapObject, Err> {
+        self.string_copy(args)
+    }
+
+    pub fn string_append(&mut self, args: &[HeapObject]) -> Result<HeapObject, Err> {
+        let s = self.strings(args)?.concat();
+        Result::Ok(self.new_string(s))
+    }
+
+    // (string-copy s [start [end]])
+    pub fn string_copy(&mut self, args: &[HeapObject]) -> Result<HeapObject, Err> {
+        let copy = {
+            let s = self.as_string(&args[0])?.borrow();
+            let (start, end) = self.string_range(&s, args, 1)?;
+            s[start..end].to_string()
+        };
+        Result::Ok(self.new_string(copy))
+    }
+
+    // (string-copy! to at from [start [end]]), which may overlap
+    pub fn string_copy_to(&mut self, args: &[HeapObject]) -> Result<HeapObject, Err> {
+        let copy = {
+            let from = self.as_string(&args[2])?.borrow();
+            let (start, end) = self.string_range(&from, args, 3)?;
+            from[start..end].to_string()
+        };
+        let mut to = self.as_string(&args[0])?.borrow_mut();
+        let len = to.chars().count();
+        let at = self.index(&args[1], len, true)?;
+        let count = copy.chars().count();
+        if len - at < count {
+            return Result::Err(self.out_of_range((at + count) as i64, len));
+        }
+        let start = offset(&to, at);
+        let end = start + offset(&to[start..], count);
+        to.replace_range(start..end, &copy);
+        drop(to);
+        Result::Ok(self.new_nil())
+    }
+
+    // (string-fill! s char [start [end]])
+    pub fn string_fill(&mut self, args: &[HeapObject]) -> Result<HeapObject, Err> {
+        let c = self.as_char(&args[1])?;
+        let mut s = self.as_string(&args[0])?.borrow_mut();
+        let (start, end) = self.string_range(&s, args, 2)?;
+        let fill: String = ::std::iter::repeat_n(c, s[start..end].chars().count()).collect();
+        s.replace_range(start..end, &fill);
+        drop(s);
+        Result::Ok(self.new_nil())
+    }
+
+    // (string->list s [start [end]])
+    pub fn string_to_list(&mut self, args: &[HeapObject]) -> Result<HeapObject, Err> {
+        let chars: Vec<char> = {
+            let s = self.as_string(&args[0])?.borrow();
+            let (start, end) = self.string_range(&s, args, 1)?;
+            s[start..end].chars().collect()
+        };
+        let elems = chars.into_iter().map(|c| self.new_char(c)).collect();
+        Result::Ok(self.vec_to_list(elems))
+    }
+
+    pub fn list_to_string(&mut self, args: &[HeapObject]) -> Result<HeapObject, Err> {
+        let elems = Object::list_to_vec(&args[0]).ok_or_else(|| self.wrong_type("list", &args[0]))?;
+        let s = self.chars(&elems)?.into_iter().collect();
+        Result::Ok(self.new_string(s))
+    }
+
+    fn map_string(&mut self, args: &[HeapObject], f: fn(&str) -> String) -> Result<HeapObject, Err> {
+        let s = f(&self.as_string(&args[0])?.borrow());
+        Result::Ok(self.new_string(s))
+    }
+
+    pub fn string_upcase(&mut self, args: &[HeapObject]) -> Result<HeapObject, Err> {
+        self.map_string(args, str::to_uppercase)
+    }
+
+    pub fn string_downcase(&mut self, args: &[HeapObject]) -> Result<HeapObject, Err> {
+        self.map_string(args, str::to_lowercase)
+    }
+
+    pub fn string_foldcase(&mut self, args: &[HeapObject]) -> Result<HeapObject, Err> {
+        self.map_string(args, fold)
+    }
+
+    // Whether each string argument is ordered relative to the next as ok
+    // wants, after folding case if ci.
+    fn compare_strings(&mut self, args: &[HeapObject], ci: bool, ok: fn(Ordering) -> bool)
+                       -> Result<HeapObject, Err> {
+        let mut strings = self.strings(args)?;
+        if ci {
+            strings = strings.iter().map(|s| fold(s)).collect();
+        }
+        // UTF-8 bytes order the same as the chars they encode
+        let res = strings.windows(2).all(|w| ok(w[0].cmp(&w[1])));
+        Result::Ok(self.new_bool(res))
+    }
+
+    pub fn string_eq(&mut self, args: &[HeapObject]) -> Result<HeapObject, Err> {
+        self.compare_strings(args, false, Ordering::is_eq)
+    }
+
+    pub fn string_lt(&mut self, args: &[HeapObject]) -> Result<HeapObject, Err> {
+        self.compare_strings(args, false, Ordering::is_lt)
+    }
+
+    pub fn string_gt(&mut self, args: &[HeapObject]) -> Result<HeapObject, Err> {
+        self.compare_strings(args, false, Ordering::is_gt)
+    }
+
+    pub fn string_le(&mut self, args: &[HeapObject]) -> Result<HeapObject, Err> {
+        self.compare_strings(args, false, Ordering::is_le)
+    }
+
+    pub fn string_ge(&mut self, args: &[HeapObject]) -> Result<HeapObject, Err> {
+        self.compare_strings(args, false, Ordering::is_ge)
+    }
+
+    pub fn string_ci_eq(&mut self, args: &[HeapObject]) -> Result<HeapObject, Err> {
+        self.compare_strings(args, true, Ordering::is_eq)
+    }
+
+    pub fn string_ci_lt(&mut self, args: &[HeapObject]) -> Result<HeapObject, Err> {
+        self.compare_strings(args, true, Ordering::is_lt)
+    }
+
+    pub fn string_ci_gt(&mut self, args: &[HeapObject]) -> Result<HeapObject, Err> {
+        self.compare_strings(args, true, Ordering::is_gt)
+    }
+
+    pub fn string_ci_le(&mut self, args: &[HeapObject]) -> Result<HeapObject, Err> {
+        self.compare_strings(args, true, Ordering::is_le)
+    }
+
+    pub fn string_ci_ge(&mut self, args: &[HeapObject]) -> Result<HeapObject, Err> {
+        self.compare_strings(args, true, Ordering::is_ge)
+    }
+
+    // The chars at index i of each of the strings in args, None once the
+    // shortest is exhausted.
+    pub fn string_args(&mut self, args: &[HeapObject], i: usize) -> Result<Option<Vec<HeapObject>>, Err> {
+        let mut chars = Vec::with_capacity(args.len());
+        for obj in args {
+            match self.as_string(obj)?.borrow().chars().nth(i) {
+                Option::Some(c) => chars.push(c),
+                Option::None => return Result::Ok(Option::None),
+            }
+        }
+        Result::Ok(Option::Some(chars.into_iter().map(|c| self.new_char(c)).collect()))
+    }
+
+    // (string-map f s1 s2 ...), as long as the shortest string
+    pub fn string_map(&mut self, args: Vec<HeapObject>) -> Result<Step, Err> {
+        self.each(Each::StringMap, args[0].clone(), args[1..].to_vec(), 0, Vec::new())
+    }
+
+    pub fn string_for_each(&mut self, args: Vec<HeapObject>) -> Result<Step, Err> {
+        self.each(Each::StringForEach, args[0].clone(), args[1..].to_vec(), 0, Vec::new())
+    }
+
+    // (string-split s [delimiter]) splits at each occurrence of delimiter,
+    // a char or a string, or at runs of whitespace when there is none.
+    pub fn string_split(&mut self, args: &[HeapObject]) -> Result<HeapObject, Err> {
+        let parts: Vec<String> = {
+            let s = self.as_string(&args[0])?.borrow();
+            match args.get(1).map(|obj| &obj.object_type) {
+                Option::None => s.split_whitespace().map(String::from).collect(),
+                Option::Some(&Type::Character(c)) => s.split(c).map(String::from).collect(),
+                Option::Some(Type::String(delimiter)) if !delimiter.borrow().is_empty() =>
+                    s.split(delimiter.borrow().as_str()).map(String::from).collect(),
+                Option::Some(_) => return Result::Err(self.wrong_type("character or non-empty string", &args[1])),
+            }
+        };
+        let elems = parts.into_iter().map(|part| self.new_string(part)).collect();
+        Result::Ok(self.vec_to_list(elems))
+    }
+
+    // (string-join strings [delimiter]), with a space between each by
+    // default
+    pub fn string_join(&mut self, args: &[HeapObject]) -> Result<HeapObject, Err> {
+        let elems = Object::list_to_vec(&args[0]).ok_or_else(|| self.wrong_type("list", &args[0]))?;
+        let delimiter = match args.get(1) {
+            Option::Some(obj) => self.as_string(obj)?.borrow().clone(),
+            Option::None => " ".to_string(),
+        };
+        let s = self.strings(&elems)?.join(&delimiter);
+        Result::Ok(self.new_string(s))
+    }
+
+    // (string-index s pred [start [end]]), the index of the first char
+    // that is pred, or equal to it if pred is a char, or #f if none is.
+    pub fn string_index(&mut self, args: &[HeapObject]) -> Result<HeapObject, Err> {
+        let (start, chars): (usize, Vec<char>) = {
+            let s = self.as_string(&args[0])?.borrow();
+            let (start, end) = self.range(args, 2, s.chars().count())?;
+            (start, s.chars().skip(start).take(end - start).collect())
+        };
+        for (i, c) in chars.into_iter().enumerate() {
+            let found = match args[1].object_type {
+                Type::Character(wanted) => c == wanted,
+                _ => {
+                    let c = self.new_char(c);
+                    self.apply(args[1].clone(), vec![c])?.is_true()
+                },
+            };
+            if found {
+                return Result::Ok(self.new_object(Type::Integer((start + i) as i64)));
+            }
+        }
+        Result::Ok(self.new_false())
+    }
+
+    pub fn char_p(&mut self, args: &[HeapObject]) -> Result<HeapObject, Err> {
+        Result::Ok(self.new_bool(matches!(args[0].object_type, Type::Character(_))))
+    }
+
+    fn char_predicate(&mut self, args: &[HeapObject], f: fn(char) -> bool) -> Result<HeapObject, Err> {
+        let c = self.as_char(&args[0])?;
+        Result::Ok(self.new_bool(f(c)))
+    }
+
+    pub fn char_alphabetic_p(&mut self, args: &[HeapObject]) -> Result<HeapObject, Err> {
+        self.char_predicate(args, char::is_alphabetic)
+    }
+
+    pub fn char_numeric_p(&mut self, args: &[HeapObject]) -> Result<HeapObject, Err> {
+        self.char_predicate(args, char::is_numeric)
+    }
+
+    pub fn char_whitespace_p(&mut self, args: &[HeapObject]) -> Result<HeapObject, Err> {
+        self.char_predicate(args, char::is_whitespace)
+    }
+
+    pub fn char_upper_case_p(&mut self, args: &[HeapObject]) -> Result<HeapObject, Err> {
+        self.char_predicate(args, char::is_uppercase)
+    }
+
+    pub fn char_lower_case_p(&mut self, args: &[HeapObject]) -> Result<HeapObject, Err> {
+        self.char_predicate(args, char::is_lowercase)
+    }
+
+    fn map_char(&mut self, args: &[HeapObject], f: fn(char) -> char) -> Result<HeapObject, Err> {
+        let c = f(self.as_char(&args[0])?);
+        Result::Ok(self.new_char(c))
+    }
+
+    pub fn char_upcase(&mut self, args: &[HeapObject]) -> Result<HeapObject, Err> {
+        self.map_char(args, upcase)
+    }
+
+    pub fn char_downcase(&mut self, args: &[HeapObject]) -> Result<HeapObject, Err> {
+        self.map_char(args, downcase)
+    }
+
+    pub fn char_foldcase(&mut self, args: &[HeapObject]) -> Result<HeapObject, Err> {
+        self.map_char(args, downcase)
+    }
+
+    // (digit-value c), #f unless c is a decimal digit
+    pub fn digit_value(&mut self, args: &[HeapObject]) -> Result<HeapObject, Err> {
+        match self.as_char(&args[0])?.to_digit(10) {
+            Option::Some(d) => Result::Ok(self.new_object(Type::Integer(d as i64))),
+            Option::None => Result::Ok(self.new_false()),
+        }
+    }
+
+    pub fn char_to_integer(&mut self, args: &[HeapObject]) -> Result<HeapObject, Err> {
+        let c = self.as_char(&args[0])?;
+        Result::Ok(self.new_object(Type::Integer(c as i64)))
+    }
+
+    pub fn integer_to_char(&mut self, args: &[HeapObject]) -> Result<HeapObject, Err> {
+        let c = match args[0].object_type {
+            Type::Integer(n) if (0..=0x10ffff).contains(&n) => char::from_u32(n as u32),
+            _ => Option::None,
+        };
+        match c {
+            Option::Some(c) => Result::Ok(self.new_char(c)),
+            Option::None => Result::Err(self.wrong_type("Unicode scalar value", &args[0])),
+        }
+    }
+
+    fn compare_chars(&mut self, args: &[HeapObject], ci: bool, ok: fn(Ordering) -> bool)
+                     -> Result<HeapObject, Err> {
+        let mut chars = self.chars(args)?;
+        if ci {
+            chars = chars.into_iter().map(downcase).collect();
+        }
+        let res = chars.windows(2).all(|w| ok(w[0].cmp(&w[1])));
+        Result::Ok(self.new_bool(res))
+    }
+
+    pub fn char_eq(&mut self, args: &[HeapObject]) -> Result<HeapObject, Err> {
+        self.compare_chars(args, false, Ordering::is_eq)
+    }
+
+    pub fn char_lt(&mut self, args: &[HeapObject]) -> Result<HeapObject, Err> {
+        self.compare_chars(args, false, Ordering::is_lt)
+    }
+
+    pub fn char_gt(&mut self, args: &[HeapObject]) -> Result<HeapObject, Err> {
+        self.compare_chars(args, false, Ordering::is_gt)
+    }
+
+    pub fn char_le(&mut self, args: &[HeapObject]) -> Result<HeapObject, Err> {
+        self.compare_chars(args, false, Ordering::is_le)
+    }
+
+    pub fn char_ge(&mut self, args: &[HeapObject]) -> Result<HeapObject, Err> {
+        self.compare_chars(args, false, Ordering::is_ge)
+    }
+
+    pub fn char_ci_eq(&mut self, args: &[HeapObject]) -> Result<HeapObject, Err> {
+        self.compare_chars(args, true, Ordering::is_eq)
+    }
+
+    pub fn char_ci_lt(&mut self, args: &[HeapObject]) -> Result<HeapObject, Err> {
+        self.compare_chars(args, true, Ordering::is_lt)
+    }
+
+    pub fn char_ci_gt(&mut self, args: &[HeapObject]) -> Result<HeapObject, Err> {
+        self.compare_chars(args, true, Ordering::is_gt)
+    }
+
+    pub fn char_ci_le(&mut self, args: &[HeapObject]) -> Result<HeapObject, Err> {
+        self.compare_chars(args, true, Ordering::is_le)
+    }
+
+    pub fn char_ci_ge(&mut self, args: &[HeapObject]) -> Result<HeapObject, Err> {
+        self.compare_chars(args, true, Ordering::is_ge)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use interpreter::Interpreter;
+    use error::ErrType;
+    use test_util::{eval_str, assert_eval};
+
+    #[test]
+    fn test_strings() {
+        let mut i = Interpreter::new();
+        let cases = [
+            ("(string-length \"λx.é\")", "4"),
+            ("(string-ref \"λx.é\" 3)", "#\\é"),
+            ("(substring \"hello world\" 6)", "\"world\""),
+            ("(substring \"aλbλc\" 1 4)", "\"λbλ\""),
+            ("(string-append \"foo\" \"\" \"bär\")", "\"foobär\""),
+            ("(let ((s (make-string 3 #\\a))) (string-set! s 1 #\\λ) s)", "\"aλa\""),
+            ("(let ((s (string-copy \"abc\"))) (string-set! s 2 #\\z) s)", "\"abz\""),
+            ("(string-copy \"λbc\" 1)", "\"bc\""),
+            ("(let ((s (string-copy \"abcde\"))) (string-copy! s 1 \"λλ\") s)", "\"aλλde\""),
+            ("(let ((s (string-copy \"abcde\"))) (string-fill! s #\\é 3) s)", "\"abcéé\""),
+            ("(string #\\a #\\b)", "\"ab\""),
+            ("(string->list \"aλc\")", "(#\\a #\\λ #\\c)"),
+            ("(string->list \"abc\" 1 2)", "(#\\b)"),
+            ("(list->string (list #\\a #\\λ))", "\"aλ\""),
+            ("(string-upcase \"straße\")", "\"STRASSE\""),
+            ("(string-downcase \"ÀB\")", "\"àb\""),
+            ("(string-foldcase \"ΣΑΣ\")", "\"σας\""),
+            ("(string<? \"abc\" \"abd\" \"b\")", "#t"),
+            ("(string<? \"abc\" \"ab\")", "#f"),
+            ("(string>=? \"b\" \"b\" \"a\")", "#t"),
+            ("(string=? \"a\" \"a\" \"b\")", "#f"),
+            ("(string-ci=? \"Straße\" \"STRASSE\")", "#f"),
+            ("(string-ci=? \"ABC\" \"abc\")", "#t"),
+            ("(string-ci<? \"a\" \"B\")", "#t"),
+            ("(string-map char-upcase \"abc\")", "\"ABC\""),
+            ("(let ((n 0)) (string-for-each (lambda (a b) (set! n (+ n 1))) \"abc\" \"xy\") n)", "2"),
+            ("(string-split \"a,b,,c\" #\\,)", "(\"a\" \"b\" \"\" \"c\")"),
+            ("(string-split \"a::b\" \"::\")", "(\"a\" \"b\")"),
+            ("(string-split \"  one two\\tthree \")", "(\"one\" \"two\" \"three\")"),
+            ("(string-join (list \"a\" \"b\" \"c\") \", \")", "\"a, b, c\""),
+            ("(string-join (list \"a\" \"b\"))", "\"a b\""),
+            ("(string-index \"λa1b\" char-numeric?)", "2"),
+            ("(string-index \"abcabc\" #\\a 1)", "3"),
+            ("(string-index \"abc\" #\\z)", "#f"),
+            ("(string->number \"1e3\")", "1000.0"),
+            ("(string? \"\")", "#t"),
+        ];
+        for &(src, expected) in cases.iter() {
+            assert_eval(&mut i, src, expected);
+        }
+
+        for src in &["(string-ref \"λ\" 1)", "(substring \"abc\" 2 1)", "(string-copy! (make-string 1) 0 \"ab\")"] {
+            assert!(matches!(eval_str(&mut i, src).map_err(|e| e.err_type), Result::Err(ErrType::IndexOutOfRange{..})), "{}", src);
+        }
+    }
+
+    #[test]
+    fn test_chars() {
+        let mut i = Interpreter::new();
+        let cases = [
+            ("(char-alphabetic? #\\λ)", "#t"),
+            ("(char-alphabetic? #\\1)", "#f"),
+            ("(char-numeric? #\\7)", "#t"),
+            ("(char-whitespace? #\\tab)", "#t"),
+            ("(char-upper-case? #\\A)", "#t"),
+            ("(char-lower-case? #\\A)", "#f"),
+            ("(char-upcase #\\ä)", "#\\Ä"),
+            ("(char-upcase #\\ß)", "#\\ß"),
+            ("(char-downcase #\\Σ)", "#\\σ"),
+            ("(char-foldcase #\\A)", "#\\a"),
+            ("(char->integer #\\λ)", "955"),
+            ("(integer->char 955)", "#\\λ"),
+            ("(digit-value #\\3)", "3"),
+            ("(digit-value #\\a)", "#f"),
+            ("(char<? #\\a #\\b #\\c)", "#t"),
+            ("(char=? #\\a #\\a #\\b)", "#f"),
+            ("(char-ci=? #\\a #\\A)", "#t"),
+            ("(char? #\\a)", "#t"),
+        ];
+        for &(src, expected) in cases.iter() {
+            assert_eval(&mut i, src, expected);
+        }
+
+        assert!(matches!(eval_str(&mut i, "(integer->char 55296)").map_err(|e| e.err_type), Result::Err(ErrType::WrongType{..})));
+    }
+
+    #[test]
+    fn test_gc_widened_strings() {
+        // strings that grew when wide chars were written into them are
+        // freed by the GC
+        let mut i = Interpreter::new();
+        eval_str(&mut i, "(define n 0)").unwrap();
+        eval_str(&mut i, "(while (< n 2000)
+                            (let ((s (make-string 3 #\\a)) (t (make-string 2 #\\b)))
+                              (string-set! s 0 #\\λ)
+                              (string-fill! t #\\€))
+                            (set! n (+ n 1)))").unwrap();
+        i.gc();
+        assert_eval(&mut i, "n", "2000");
+    }
+}
@@ -0,0 +1,106 @@
+use std::collections::HashMap;
+use std::hash::{Hash, Hasher};
+use std::option::Option;
+use std::rc::Rc;
+use std::fmt;
+
+// A handle to a symbol name. Interned symbols with the same name share
+// their handle, so symbols are compared and hashed by identity and never
+// by their name.
+#[derive(Clone)]
+pub struct Symbol(Rc<str>);
+
+impl Symbol {
+    #[inline]
+    pub fn name(&self) -> &str {
+        &self.0
+    }
+
+    // A symbol that is not in any interner, so it is distinct from every
+    // other symbol whatever its name.
+    pub fn uninterned(name: &str) -> Symbol {
+        Symbol(Rc::from(name))
+    }
+}
+
+impl PartialEq for Symbol {
+    #[inline]
+    fn eq(&self, other: &Symbol) -> bool {
+        Rc::ptr_eq(&self.0, &other.0)
+    }
+}
+
+impl Eq for Symbol {}
+
+impl Hash for Symbol {
+    #[inline]
+    fn hash<H: Hasher>(&self, state: &mut H) {
+        Rc::as_ptr(&self.0).hash(state);
+    }
+}
+
+impl fmt::Display for Symbol {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{}", self.0)
+    }
+}
+
+// Maps names to their interned symbol. Symbols live as long as the
+// interner.
+pub struct Interner {
+    table: HashMap<Rc<str>, Symbol>,
+    gensym_count: usize,
+}
+
+impl Default for Interner {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl Interner {
+    pub fn new() -> Interner {
+        Interner{table: HashMap::new(), gensym_count: 0}
+    }
+
+    pub fn intern(&mut self, name: &str) -> Symbol {
+        if let Option::Some(sym) = self.table.get(name) {
+            return sym.clone();
+        }
+        let name: Rc<str> = Rc::from(name);
+        let sym = Symbol(name.clone());
+        self.table.insert(name, sym.clone());
+        sym
+    }
+
+    // A symbol distinct from every other, interned or not, even if its
+    // name is the same.
+    pub fn gensym(&mut self, prefix: &str) -> Symbol {
+        self.gensym_count += 1;
+        Symbol::uninterned(&format!("{}{}", prefix, self.gensym_count))
+    }
+
+    pub fn is_interned(&self, sym: &Symbol) -> bool {
+        self.table.get(sym.name()).is_some_and(|s| s == sym)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_intern() {
+        let mut interner = Interner::new();
+        let a = interner.intern("foo");
+        assert!(a == interner.intern("foo"));
+        assert!(a != interner.intern("bar"));
+        assert!(interner.is_interned(&a));
+
+        let g = interner.gensym("foo");
+        assert!(!interner.is_interned(&g));
+        let same_name = interner.intern(g.name());
+        assert!(g != same_name);
+        assert!(interner.is_interned(&same_name));
+    }
+}
@@ -0,0 +1,27 @@
+// Evaluating source text in the tests of each module.
+
+use interpreter::Interpreter;
+use error::Err;
+use types::HeapObject;
+use parse::parse_sexp;
+
+pub fn eval_str(i: &mut Interpreter, s: &str) -> Result<HeapObject, Err> {
+    let obj = parse_sexp(s, i).unwrap();
+    i.eval(obj)
+}
+
+// The value of s, failing the test if there is an error.
+pub fn eval_obj(i: &mut Interpreter, s: &str) -> HeapObject {
+    match eval_str(i, s) {
+        Result::Ok(obj) => obj,
+        Result::Err(e) => panic!("{}: {}", s, e),
+    }
+}
+
+pub fn eval(i: &mut Interpreter, s: &str) -> String {
+    eval_obj(i, s).to_string()
+}
+
+pub fn assert_eval(i: &mut Interpreter, s: &str, expected: &str) {
+    assert_eq!(eval(i, s), expected, "{}", s);
+}
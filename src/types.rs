@@ -1,45 +1,174 @@
-use error::{Err, ErrType};
-use std::collections::{LinkedList, HashMap};
+use error::{Err, ErrType, Location};
+use interpreter::{Interpreter, Step, Continuation};
+use compile::LambdaCode;
+use symbol::Symbol;
+use parse::Span;
+use number::Number;
+use printer::{Printer, Labels};
+use hashtable::HashTable;
+use record::{Record, RecordType, RecordProc};
+use num_bigint::BigInt;
+use num_rational::BigRational;
 use std::boxed::Box;
 use std::rc::Rc;
-use std::cell::Cell;
-use std::ops::{Add, Mul, Div};
+use std::cell::{Cell, RefCell};
 use std::fmt;
+use std::borrow::Cow;
 use std::option::Option;
-use std::mem::size_of;
+use std::mem::{size_of, replace};
+use std::collections::HashSet;
 
 pub type HeapObject = Rc<Box<Object>>;
-pub type List = LinkedList<HeapObject>;
-
-pub fn new_list() -> List {
-    LinkedList::new()
-}
+pub type PrimitiveFn = fn(&mut Interpreter, &[HeapObject]) -> Result<HeapObject, Err>;
+pub type ControlFn = fn(&mut Interpreter, Vec<HeapObject>) -> Result<Step, Err>;
+// A builtin's Scheme name, its function, and the fewest and most arguments
+// it takes, with None for no limit.
+pub type Builtin<F> = (&'static str, F, usize, Option<usize>);
 
 pub enum Type {
     Bool(bool),
     Integer(i64),
+    // exact integers too large for an Integer
+    BigInt(BigInt),
+    // exact non-integers, in lowest terms
+    Rational(Box<BigRational>),
     Float(f64),
     Character(char),
-    String(Rc<String>),
-    Symbol(Rc<String>),
-
-    Cons(Box<List>),
+    String(RefCell<String>),
+    Symbol(Symbol),
+
+    Nil, //the empty list, there is only one
+    Pair(Pair),
+    Vector(RefCell<Vec<HeapObject>>),
+    Bytevector(RefCell<Vec<u8>>),
+    HashTable(Box<HashTable>),
+    RecordType(Rc<RecordType>),
+    Record(Record),
     Procedure(Box<Procedure>),
+    Frame(Frame),
+    Error(Box<ErrorObject>),
+}
+
+pub struct Pair {
+    pub car: RefCell<HeapObject>,
+    pub cdr: RefCell<HeapObject>,
+}
+
+impl Pair {
+    pub fn new(car: HeapObject, cdr: HeapObject) -> Pair {
+        Pair{car: RefCell::new(car), cdr: RefCell::new(cdr)}
+    }
+}
+
+// The variables bound by a lambda call or a let, in the order the compiler
+// assigned them.
+pub struct Frame {
+    pub vars: RefCell<Vec<HeapObject>>,
+    pub parent: Option<HeapObject>, //type is Frame
+}
+
+impl Frame {
+    pub fn new(vars: Vec<HeapObject>, parent: Option<HeapObject>) -> Frame {
+        Frame{vars: RefCell::new(vars), parent}
+    }
+}
+
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum ErrorKind {
+    Error,
+    Read,
+    File,
+}
+
+// What error makes, and what errors the interpreter finds become when
+// there is a handler to pass them to.
+pub struct ErrorObject {
+    pub kind: ErrorKind,
+    pub message: String,
+    pub irritants: HeapObject,
+    // the expression the interpreter was evaluating when it failed
+    pub location: Option<Location>,
+}
+
+thread_local!(static DROPPED_TAIL: HeapObject = Rc::new(Box::new(Object::new(Type::Nil))));
+
+// Unlinks the tail one pair at a time, so dropping a long list doesn't
+// recurse once per element.
+impl Drop for Pair {
+    fn drop(&mut self) {
+        DROPPED_TAIL.with(|nil| {
+            let mut tail = replace(self.cdr.get_mut(), nil.clone());
+            while let Result::Ok(mut obj) = Rc::try_unwrap(tail) {
+                tail = match obj.object_type {
+                    Type::Pair(ref mut p) => replace(p.cdr.get_mut(), nil.clone()),
+                    _ => break,
+                };
+            }
+        });
+    }
+}
+
+// Walks the cars of a list. Once it is exhausted, tail() is the object
+// that ended it: Nil for a proper list.
+pub struct ListIter {
+    tail: HeapObject,
+}
+
+impl ListIter {
+    pub fn new(list: &HeapObject) -> ListIter {
+        ListIter{tail: list.clone()}
+    }
+
+    pub fn tail(&self) -> &HeapObject {
+        &self.tail
+    }
+}
+
+impl Iterator for ListIter {
+    type Item = HeapObject;
+
+    fn next(&mut self) -> Option<HeapObject> {
+        let (car, cdr) = match self.tail.object_type {
+            Type::Pair(ref p) => (p.car.borrow().clone(), p.cdr.borrow().clone()),
+            _ => return Option::None,
+        };
+        self.tail = cdr;
+        Option::Some(car)
+    }
 }
 
 impl Type {
+    #[inline]
+    pub fn as_symbol(&self) -> Option<Symbol> {
+        match *self {
+            Type::Symbol(ref s) => Option::Some(s.clone()),
+            _ => Option::None,
+        }
+    }
+
     pub fn size_of(&self) -> usize {
         match *self {
             Type::Bool(_) => size_of::<bool>(),
             Type::Integer(_) => size_of::<i64>(),
+            Type::BigInt(ref n) => size_of::<BigInt>() + (n.bits() as usize).div_ceil(8),
+            Type::Rational(ref r) => size_of::<BigRational>() + (r.numer().bits() + r.denom().bits()) as usize / 8,
             Type::Float(_) => size_of::<f64>(),
             Type::Character(_) => size_of::<char>(),
-            Type::String(ref s) | Type::Symbol(ref s) => size_of::<u8>() * s.capacity(),
-            Type::Cons(_) => size_of::<List>(),
-            Type::Procedure(ref p) => {
-                if let &Procedure::Lambda(_) = p.as_ref() {
-                    size_of::<Lambda>()
-                } else {0}
+            Type::String(ref s) => size_of::<u8>() * s.borrow().capacity(),
+            Type::Symbol(_) => size_of::<Symbol>(),
+            Type::Nil => 0,
+            Type::Pair(_) => size_of::<Pair>(),
+            Type::Vector(ref v) => size_of::<HeapObject>() * v.borrow().capacity(),
+            Type::Bytevector(ref v) => v.borrow().capacity(),
+            Type::HashTable(ref t) => size_of::<HashTable>() + t.len() * size_of::<(HeapObject, HeapObject)>(),
+            Type::RecordType(_) => size_of::<RecordType>(),
+            Type::Record(ref r) => size_of::<Record>() + size_of::<HeapObject>() * r.fields.borrow().len(),
+            Type::Frame(_) => size_of::<Frame>(),
+            Type::Error(ref e) => size_of::<ErrorObject>() + e.message.capacity(),
+            Type::Procedure(ref p) => match **p {
+                Procedure::Lambda(_) => size_of::<Lambda>(),
+                Procedure::Continuation(ref k) => k.size(),
+                _ => 0,
             }
         }
     }
@@ -48,93 +177,266 @@ impl Type {
 pub struct Object {
     pub object_type: Type,
     pub marked: Cell<bool>,
+    // where the reader found the datum, if it came from source text
+    pub span: Cell<Option<Span>>,
 }
 
 // (lambda (a r g s) body)
 pub struct Lambda {
-    pub env: Option<HashMap<Rc<String>, HeapObject>>, //type is environment
-    pub params: HeapObject, //type is Cons, represents (a r g s)
-    pub body: HeapObject, //type is Cons, represents body
-}
-
-impl Lambda {
-    fn mark(&self) {
-        if let Some(ref env) = self.env {
-            for (_, obj) in env.iter() {
-                obj.mark();
-            }
-        }
-        self.params.mark();
-        self.body.mark();
-    }
+    pub env: HeapObject, //type is Frame, the frame the lambda was created in
+    pub code: Rc<LambdaCode>,
 }
 
 pub enum Procedure {
-    Lambda (Lambda), //env type is Environment
-    Primitive(&'static Fn(&List) -> Result<HeapObject, Err>)
+    Lambda (Lambda),
+    Primitive(&'static Builtin<PrimitiveFn>),
+    // primitives that take over the continuation stack, like call/cc
+    Control(&'static Builtin<ControlFn>),
+    Continuation(Continuation),
+    // the constructor, predicate and field procedures of a record type
+    Record(RecordProc),
 }
 
 impl Object {
     pub fn new(t: Type) -> Object {
-        Object{object_type: t, marked: Cell::new(false)}
+        Object{object_type: t, marked: Cell::new(false), span: Cell::new(Option::None)}
+    }
+
+    #[inline]
+    pub fn is_nil(&self) -> bool {
+        matches!(self.object_type, Type::Nil)
     }
 
     #[inline]
-    pub fn unwrap_list(&self) -> &List {
-        if let Type::Cons(ref l) = self.object_type {
-            l
+    pub fn car(&self) -> Option<HeapObject> {
+        if let Type::Pair(ref p) = self.object_type {
+            Option::Some(p.car.borrow().clone())
         } else {
-            panic!("object is not a list")
+            Option::None
         }
     }
+
     #[inline]
-    pub fn unwrap_sym(&self) -> Rc<String> {
-        if let Type::String(ref s) = self.object_type {
+    pub fn cdr(&self) -> Option<HeapObject> {
+        if let Type::Pair(ref p) = self.object_type {
+            Option::Some(p.cdr.borrow().clone())
+        } else {
+            Option::None
+        }
+    }
+
+    // The elements of a proper list, None for anything else. Cyclic lists
+    // are not detected.
+    pub fn list_to_vec(list: &HeapObject) -> Option<Vec<HeapObject>> {
+        let mut iter = ListIter::new(list);
+        let elems: Vec<HeapObject> = iter.by_ref().collect();
+        if iter.tail().is_nil() {
+            Option::Some(elems)
+        } else {
+            Option::None
+        }
+    }
+
+    // Whether list is a finite chain of pairs ending in Nil.
+    pub fn is_list(list: &HeapObject) -> bool {
+        let mut slow = list.clone();
+        let mut fast = list.clone();
+        loop {
+            for _ in 0..2 {
+                fast = match fast.object_type {
+                    Type::Nil => return true,
+                    Type::Pair(ref p) => p.cdr.borrow().clone(),
+                    _ => return false,
+                };
+            }
+            slow = slow.cdr().unwrap();
+            if Rc::ptr_eq(&slow, &fast) {
+                return false;
+            }
+        }
+    }
+
+    #[inline]
+    pub fn unwrap_sym(&self) -> Symbol {
+        if let Type::Symbol(ref s) = self.object_type {
             s.clone()
         } else {
-            panic!("object is not a string")
+            panic!("object is not a symbol")
         }
     }
 
-    pub fn get_type_string(&self) -> &'static str {
-        match self.object_type {
+    // The name of a record's type, for records.
+    pub fn get_type_string(&self) -> Cow<'static, str> {
+        Cow::Borrowed(match self.object_type {
             Type::Bool(_) => "boolean",
-            Type::Integer(_) => "integer",
+            Type::Integer(_) | Type::BigInt(_) => "integer",
+            Type::Rational(_) => "rational",
             Type::Float(_) => "float",
             Type::Character(_) => "character",
             Type::String(_) => "string",
-            Type::Cons(_) => "list",
+            Type::Nil => "null",
+            Type::Pair(_) => "pair",
+            Type::Vector(_) => "vector",
+            Type::Bytevector(_) => "bytevector",
+            Type::HashTable(_) => "hash-table",
+            Type::RecordType(_) => "record-type",
+            Type::Record(ref r) => return Cow::Owned(r.rtd.name.clone()),
             Type::Procedure(_) => "procedure",
             Type::Symbol(_) => "symbol",
-        }
+            Type::Frame(_) => "environment",
+            Type::Error(_) => "error-object",
+        })
     }
 
+    // Uses a work list rather than recursion, so long lists don't
+    // overflow the stack.
     pub fn mark(&self) {
+        let mut pending = Vec::new();
+        self.mark_one(&mut pending);
+        while let Option::Some(obj) = pending.pop() {
+            obj.mark_one(&mut pending);
+        }
+    }
+
+    // Marks self and queues the objects it refers to.
+    fn mark_one(&self, pending: &mut Vec<HeapObject>) {
         if self.marked.get() {
             return
         }
 
         self.marked.set(true);
+        self.for_each_ref(&mut |obj| {
+            if !obj.marked.get() {
+                pending.push(obj.clone());
+            }
+        });
+    }
+
+    // Calls f with every object self holds a reference to.
+    pub fn for_each_ref(&self, f: &mut dyn FnMut(&HeapObject)) {
         match self.object_type {
-            Type::Cons(ref cons) => Object::mark_list(cons),
-            Type::Procedure(ref procedure) => Object::mark_procedure(procedure),
+            Type::Pair(ref p) => {
+                f(&p.car.borrow());
+                f(&p.cdr.borrow());
+            },
+            Type::Vector(ref v) => v.borrow().iter().for_each(&mut *f),
+            Type::Procedure(ref procedure) => match **procedure {
+                Procedure::Lambda(ref l) => f(&l.env),
+                Procedure::Continuation(ref k) => k.for_each_ref(f),
+                _ => {},
+            },
+            Type::Frame(ref frame) => {
+                frame.vars.borrow().iter().for_each(&mut *f);
+                if let Option::Some(ref parent) = frame.parent {
+                    f(parent);
+                }
+            },
+            Type::Error(ref e) => f(&e.irritants),
+            Type::HashTable(ref t) => t.for_each_ref(f),
+            Type::Record(ref r) => r.fields.borrow().iter().for_each(&mut *f),
             _ => {},
         };
     }
 
-    fn mark_procedure(procedure: &Procedure) {
-        match procedure {
-            &Procedure::Lambda(ref procedure) => {procedure.mark();},
-            &Procedure::Primitive(_) => {},
+    // Calls f with every object self refers to without keeping it alive:
+    // the keys of weak hash tables.
+    pub fn for_each_weak_ref(&self, f: &mut dyn FnMut(&HeapObject)) {
+        if let Type::HashTable(ref t) = self.object_type {
+            t.for_each_weak_ref(f);
+        }
+    }
+
+    // Drops the references held in mutable fields, which every reference
+    // cycle passes through. Used by the GC on unreachable objects.
+    pub fn clear_refs(&self, nil: &HeapObject) {
+        match self.object_type {
+            Type::Pair(ref p) => {
+                *p.car.borrow_mut() = nil.clone();
+                *p.cdr.borrow_mut() = nil.clone();
+            },
+            Type::Vector(ref v) => v.borrow_mut().clear(),
+            Type::Frame(ref frame) => frame.vars.borrow_mut().clear(),
+            Type::HashTable(ref t) => t.clear(),
+            Type::Record(ref r) => r.fields.borrow_mut().clear(),
+            Type::Procedure(ref procedure) => if let Procedure::Continuation(ref k) = **procedure {
+                k.clear(nil);
+            },
+            _ => {},
         }
     }
 
-    fn mark_list(cons: &List) {
-        for obj in cons {
-            obj.mark();
+    // Identity, except that symbols with the same handle are the same.
+    pub fn is_eq(&self, other: &Object) -> bool {
+        match (&self.object_type, &other.object_type) {
+            (Type::Symbol(a), Type::Symbol(b)) => a == b,
+            _ => ::std::ptr::eq(self, other),
         }
     }
 
+    // Identity, except that numbers, characters, booleans, symbols and
+    // empty lists of equal value are always the same.
+    pub fn eqv(&self, other: &Object) -> bool {
+        if ::std::ptr::eq(self, other) {
+            return true
+        }
+
+        match (&self.object_type, &other.object_type) {
+            (&Type::Bool(a), &Type::Bool(b)) => a == b,
+            (&Type::Integer(a), &Type::Integer(b)) => a == b,
+            (Type::BigInt(a), Type::BigInt(b)) => a == b,
+            (Type::Rational(a), Type::Rational(b)) => a == b,
+            // so 0.0 and -0.0 differ, and a NaN is the same as itself
+            (&Type::Float(a), &Type::Float(b)) => a.to_bits() == b.to_bits(),
+            (&Type::Character(a), &Type::Character(b)) => a == b,
+            (Type::Symbol(a), Type::Symbol(b)) => a == b,
+            (&Type::Nil, &Type::Nil) => true,
+            _ => false,
+        }
+    }
+
+    // Whether a and b print the same: pairs, vectors, strings and
+    // bytevectors are compared by their contents, everything else by eqv.
+    // Terminates on cyclic structures, which are equal when they unfold
+    // into the same infinite trees.
+    pub fn equal(a: &HeapObject, b: &HeapObject) -> bool {
+        // pairs of containers already being compared, which are assumed
+        // equal when they come up again
+        let mut seen = HashSet::new();
+        let mut pending = vec![(a.clone(), b.clone())];
+        while let Option::Some((a, b)) = pending.pop() {
+            if Rc::ptr_eq(&a, &b) {
+                continue;
+            }
+            match (&a.object_type, &b.object_type) {
+                (Type::String(x), Type::String(y)) => if *x.borrow() != *y.borrow() {
+                    return false;
+                },
+                (Type::Bytevector(x), Type::Bytevector(y)) => if *x.borrow() != *y.borrow() {
+                    return false;
+                },
+                (Type::Pair(x), Type::Pair(y)) => {
+                    if seen.insert((&**a as *const Object, &**b as *const Object)) {
+                        pending.push((x.cdr.borrow().clone(), y.cdr.borrow().clone()));
+                        pending.push((x.car.borrow().clone(), y.car.borrow().clone()));
+                    }
+                },
+                (Type::Vector(x), Type::Vector(y)) => {
+                    let (x, y) = (x.borrow(), y.borrow());
+                    if x.len() != y.len() {
+                        return false;
+                    }
+                    if seen.insert((&**a as *const Object, &**b as *const Object)) {
+                        pending.extend(x.iter().cloned().zip(y.iter().cloned()).rev());
+                    }
+                },
+                _ => if !a.eqv(&b) {
+                    return false;
+                },
+            }
+        }
+        true
+    }
+
     pub fn is_true(&self) -> bool {
         if let Type::Bool(b) = self.object_type {
             b
@@ -143,171 +445,65 @@ impl Object {
         }
     }
     
-    pub fn add_list(nums: &List) -> Result<Object, ErrType> {
-        let mut sum = Object::new(Type::Integer(0));
-        for obj in nums {
-            match obj.object_type {
-                Type::Float(n) => {sum = sum + Object::new(Type::Float(n))},
-                Type::Integer(n) => {sum = sum + Object::new(Type::Integer(n))}
-                _ => return Result::Err(ErrType::WrongType{wanted: "numberp", got: obj.get_type_string()})
-            }
-        }
-
-        Result::Ok(sum)
+    // The number in obj, or the error for an argument that should have
+    // been one.
+    pub fn number(obj: &HeapObject) -> Result<Number, ErrType> {
+        Number::of(&obj.object_type).ok_or_else(
+            || ErrType::WrongType{wanted: "number".into(), got: obj.get_type_string()})
     }
 
-    pub fn sub_list(nums: &List) -> Result<Object, ErrType> {
-        let mut sum = Object::new(Type::Integer(0));
+    pub fn add_list(nums: &[HeapObject]) -> Result<Object, ErrType> {
+        let mut sum = Number::Fixnum(0);
         for obj in nums {
-            match obj.as_ref().object_type {
-                Type::Float(n) => {sum = sum + Object::new(Type::Float(-n))},
-                Type::Integer(n) => {sum = sum + Object::new(Type::Integer(-n))}
-                _ => return Result::Err(ErrType::WrongType{wanted: "numberp", got: obj.get_type_string()})
-
-           }
+            sum = sum.add(&Object::number(obj)?);
         }
 
-        Result::Ok(sum)
+        Result::Ok(Object::new(sum.into_type()))
     }
 
-    pub fn mul_list(nums: &List) -> Result<Object, ErrType> {
-        let mut prod = Object::new(Type::Integer(0));
-        for obj in nums {
-            match obj.object_type {
-                Type::Float(n) => {prod = prod * Object::new(Type::Float(n))},
-                Type::Integer(n) => {prod = prod * Object::new(Type::Integer(n))}
-                _ => return Result::Err(ErrType::WrongType{wanted: "numberp", got: obj.get_type_string()})
-            }
+    // (- x) is the negation of x, (- x y z) is x-y-z.
+    pub fn sub_list(nums: &[HeapObject]) -> Result<Object, ErrType> {
+        let (first, rest) = nums.split_first().ok_or(ErrType::WrongMinArgsNum{min: 1, got: 0})?;
+        let first = Object::number(first)?;
+        if rest.is_empty() {
+            return Result::Ok(Object::new(first.neg().into_type()));
+        }
+        let mut diff = first;
+        for obj in rest {
+            diff = diff.sub(&Object::number(obj)?);
         }
 
-        Result::Ok(prod)
+        Result::Ok(Object::new(diff.into_type()))
     }
 
-    pub fn div_list(nums: &List) -> Result<Object, ErrType> {
-        let mut prod = Object::new(Type::Integer(0));
+    pub fn mul_list(nums: &[HeapObject]) -> Result<Object, ErrType> {
+        let mut prod = Number::Fixnum(1);
         for obj in nums {
-            match obj.object_type {
-                Type::Float(n) => {prod = prod / Object::new(Type::Float(n))},
-                Type::Integer(n) => {prod = prod / Object::new(Type::Integer(n))}
-                _ => return Result::Err(ErrType::WrongType{wanted: "numberp", got: obj.get_type_string()})
-            }
+            prod = prod.mul(&Object::number(obj)?);
         }
 
-        Result::Ok(prod)
+        Result::Ok(Object::new(prod.into_type()))
     }
-}
-
-impl Add for Object {
-    type Output = Object;
-
-    fn add(self, other: Object) -> Object {
-        match self.object_type {
-            Type::Integer(n1) => match other.object_type {
-                Type::Integer(n2) => (Object::new(Type::Integer(n1+n2))),
-                Type::Float(n2) => (Object::new(Type::Float(n1 as f64+n2))),
-                _ => panic!("n2 is not a number")
-            },
-
-            Type::Float(n1) => match other.object_type {
-                Type::Integer(n2) => Object::new(Type::Float(n1+n2 as f64)),
-                Type::Float(n2) => Object::new(Type::Float(n1+n2)),
-                _ => panic!("n2 is not a number")
-            },
 
-            _ => panic!("n1 is not a number")
+    // (/ x) is the reciprocal of x, (/ x y z) is x/y/z.
+    pub fn div_list(nums: &[HeapObject]) -> Result<Object, ErrType> {
+        let (first, rest) = nums.split_first().ok_or(ErrType::WrongMinArgsNum{min: 1, got: 0})?;
+        let first = Object::number(first)?;
+        if rest.is_empty() {
+            return Result::Ok(Object::new(Number::Fixnum(1).div(&first)?.into_type()));
         }
-    }
-}
-
-impl fmt::Display for Object {
-    fn fmt(&self, f: &mut fmt::Formatter) -> Result<(),fmt::Error> {
-        match self.object_type {
-            Type::Bool(b) => write!(f, "{}", b),
-            Type::Integer(n) => write!(f,"{}", n),
-            Type::Float(n) => write!(f, "{}", n),
-            Type::Character(c) => write!(f, "?{}", c),
-            Type::String(ref s) => write!(f, "\"{}\"", s),
-            Type::Cons(ref l) => {
-                if l.len() == 0 {
-                    return write!(f, "nil");
-                }
-                for obj in l.iter() {
-                    let res = write!(f, "{}", *obj.as_ref());
-                    match res {
-                        Ok(_) => {},
-                        Err(e) => return Result::Err(e),
-                    }
-                };
-                Result::Ok(())
-            },
-            Type::Procedure(_) => {
-                write!(f, "procedure")
-            },
-            Type::Symbol(_) => unreachable!()
-        }
-    }
-}
-
-impl Mul for Object {
-    type Output = Object;
-
-    fn mul(self, other: Object) -> Object {
-        match self.object_type {
-            Type::Integer(n1) => match other.object_type {
-                Type::Integer(n2) => (Object::new(Type::Integer(n1*n2))),
-                Type::Float(n2) => (Object::new(Type::Float(n1 as f64*n2))),
-                _ => panic!("n2 is not a number")
-            },
-
-            Type::Float(n1) => match other.object_type {
-                Type::Integer(n2) => Object::new(Type::Float(n1*n2 as f64)),
-                Type::Float(n2) => Object::new(Type::Float(n1*n2)),
-                _ => panic!("n2 is not a number")
-            },
-
-            _ => panic!("n1 is not a number")
+        let mut quot = first;
+        for obj in rest {
+            quot = quot.div(&Object::number(obj)?)?;
         }
-    }
-}
-
-impl Div for Object {
-    type Output = Object;
-
-    fn div(self, other: Object) -> Object {
-        match self.object_type {
-            Type::Integer(n1) => match other.object_type {
-                Type::Integer(n2) => (Object::new(Type::Integer(n1/n2))),
-                Type::Float(n2) => (Object::new(Type::Float(n1 as f64/n2))),
-                _ => panic!("n2 is not a number")
-            },
 
-            Type::Float(n1) => match other.object_type {
-                Type::Integer(n2) => Object::new(Type::Float(n1/n2 as f64)),
-                Type::Float(n2) => Object::new(Type::Float(n1/n2)),
-                _ => panic!("n2 is not a number")
-            },
-
-            _ => panic!("n1 is not a number")
-        }
+        Result::Ok(Object::new(quot.into_type()))
     }
 }
 
-#[cfg(test)]
-mod test {
-    use super::*;
-
-    #[test]
-    fn test_ops() {
-        let o3 = Object::new(Type::Integer(1)) + Object::new(Type::Float(2.0));
-        match o3.object_type {
-            Type::Float(n) => {assert_eq!(n, 3.0)},
-            _ => panic!("o3 should be a float")
-        };
-
-        let o3 = Object::new(Type::Integer(2)) * Object::new(Type::Float(2.0));
-        match o3.object_type {
-            Type::Float(n) => {assert_eq!(n, 4.0)},
-            _ => panic!("o3 should be a float")
-        };
+// The external representation write gives obj.
+impl fmt::Display for Object {
+    fn fmt(&self, f: &mut fmt::Formatter) -> Result<(),fmt::Error> {
+        Printer::new(self, Labels::Cycles, false).print(self, f)
     }
 }
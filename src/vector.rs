@@ -0,0 +1,357 @@
+use interpreter::{Interpreter, Step, Each};
+use types::{Object, Type, HeapObject};
+use error::Err;
+use std::cell::RefCell;
+use std::option::Option;
+use std::result::Result;
+
+// The vector and bytevector primitives.
+impl Interpreter {
+    fn as_vector<'a>(&self, obj: &'a HeapObject) -> Result<&'a RefCell<Vec<HeapObject>>, Err> {
+        match obj.object_type {
+            Type::Vector(ref v) => Result::Ok(v),
+            _ => Result::Err(self.wrong_type("vector", obj)),
+        }
+    }
+
+    fn as_bytevector<'a>(&self, obj: &'a HeapObject) -> Result<&'a RefCell<Vec<u8>>, Err> {
+        match obj.object_type {
+            Type::Bytevector(ref v) => Result::Ok(v),
+            _ => Result::Err(self.wrong_type("bytevector", obj)),
+        }
+    }
+
+    fn byte(&self, obj: &HeapObject) -> Result<u8, Err> {
+        match obj.object_type {
+            Type::Integer(n) if (0..=255).contains(&n) => Result::Ok(n as u8),
+            _ => Result::Err(self.wrong_type("byte", obj)),
+        }
+    }
+
+    #[inline]
+    pub fn new_vector(&mut self, elems: Vec<HeapObject>) -> HeapObject {
+        self.new_object(Type::Vector(RefCell::new(elems)))
+    }
+
+    #[inline]
+    pub fn new_bytevector(&mut self, bytes: Vec<u8>) -> HeapObject {
+        self.new_object(Type::Bytevector(RefCell::new(bytes)))
+    }
+
+    pub fn vector_p(&mut self, args: &[HeapObject]) -> Result<HeapObject, Err> {
+        Result::Ok(self.new_bool(matches!(args[0].object_type, Type::Vector(_))))
+    }
+
+    // (make-vector k [fill])
+    pub fn make_vector(&mut self, args: &[HeapObject]) -> Result<HeapObject, Err> {
+        let len = self.length(&args[0])?;
+        let fill = args.get(1).cloned().unwrap_or_else(|| self.new_false());
+        let mut elems = Vec::new();
+        elems.try_reserve_exact(len).map_err(|_| self.too_long(len))?;
+        elems.resize(len, fill);
+        Result::Ok(self.new_vector(elems))
+    }
+
+    pub fn vector(&mut self, args: &[HeapObject]) -> Result<HeapObject, Err> {
+        Result::Ok(self.new_vector(args.to_vec()))
+    }
+
+    pub fn vector_length(&mut self, args: &[HeapObject]) -> Result<HeapObject, Err> {
+        let len = self.as_vector(&args[0])?.borrow().len();
+        Result::Ok(self.new_object(Type::Integer(len as i64)))
+    }
+
+    pub fn vector_ref(&mut self, args: &[HeapObject]) -> Result<HeapObject, Err> {
+        let v = self.as_vector(&args[0])?.borrow();
+        let i = self.index(&args[1], v.len(), false)?;
+        Result::Ok(v[i].clone())
+    }
+
+    pub fn vector_set(&mut self, args: &[HeapObject]) -> Result<HeapObject, Err> {
+        let mut v = self.as_vector(&args[0])?.borrow_mut();
+        let i = self.index(&args[1], v.len(), false)?;
+        v[i] = args[2].clone();
+        drop(v);
+        Result::Ok(self.new_nil())
+    }
+
+    // (vector->list v [start [end]])
+    pub fn vector_to_list(&mut self, args: &[HeapObject]) -> Result<HeapObject, Err> {
+        let elems = {
+            let v = self.as_vector(&args[0])?.borrow();
+            let (start, end) = self.range(args, 1, v.len())?;
+            v[start..end].to_vec()
+        };
+        Result::Ok(self.vec_to_list(elems))
+    }
+
+    pub fn list_to_vector(&mut self, args: &[HeapObject]) -> Result<HeapObject, Err> {
+        match Object::list_to_vec(&args[0]) {
+            Option::Some(elems) => Result::Ok(self.new_vector(elems)),
+            Option::None => Result::Err(self.wrong_type("list", &args[0])),
+        }
+    }
+
+    // (vector-fill! v fill [start [end]])
+    pub fn vector_fill(&mut self, args: &[HeapObject]) -> Result<HeapObject, Err> {
+        let mut v = self.as_vector(&args[0])?.borrow_mut();
+        let (start, end) = self.range(args, 2, v.len())?;
+        for elem in &mut v[start..end] {
+            *elem = args[1].clone();
+        }
+        drop(v);
+        Result::Ok(self.new_nil())
+    }
+
+    // (vector-copy v [start [end]])
+    pub fn vector_copy(&mut self, args: &[HeapObject]) -> Result<HeapObject, Err> {
+        let elems = {
+            let v = self.as_vector(&args[0])?.borrow();
+            let (start, end) = self.range(args, 1, v.len())?;
+            v[start..end].to_vec()
+        };
+        Result::Ok(self.new_vector(elems))
+    }
+
+    // (vector-copy! to at from [start [end]]), which may overlap
+    pub fn vector_copy_to(&mut self, args: &[HeapObject]) -> Result<HeapObject, Err> {
+        let elems = {
+            let from = self.as_vector(&args[2])?.borrow();
+            let (start, end) = self.range(args, 3, from.len())?;
+            from[start..end].to_vec()
+        };
+        let mut to = self.as_vector(&args[0])?.borrow_mut();
+        let at = self.index(&args[1], to.len(), true)?;
+        if to.len() - at < elems.len() {
+            return Result::Err(self.out_of_range((at + elems.len()) as i64, to.len()));
+        }
+        to[at..at + elems.len()].clone_from_slice(&elems);
+        drop(to);
+        Result::Ok(self.new_nil())
+    }
+
+    pub fn vector_append(&mut self, args: &[HeapObject]) -> Result<HeapObject, Err> {
+        let mut elems = Vec::new();
+        for obj in args {
+            elems.extend(self.as_vector(obj)?.borrow().iter().cloned());
+        }
+        Result::Ok(self.new_vector(elems))
+    }
+
+    // The elements at index i of each of the vectors in args, None once
+    // the shortest is exhausted.
+    pub fn vector_args(&self, args: &[HeapObject], i: usize) -> Result<Option<Vec<HeapObject>>, Err> {
+        let mut elems = Vec::with_capacity(args.len());
+        for obj in args {
+            match self.as_vector(obj)?.borrow().get(i) {
+                Option::Some(elem) => elems.push(elem.clone()),
+                Option::None => return Result::Ok(Option::None),
+            }
+        }
+        Result::Ok(Option::Some(elems))
+    }
+
+    // (vector-map f v1 v2 ...), as long as the shortest vector
+    pub fn vector_map(&mut self, args: Vec<HeapObject>) -> Result<Step, Err> {
+        self.each(Each::VectorMap, args[0].clone(), args[1..].to_vec(), 0, Vec::new())
+    }
+
+    pub fn vector_for_each(&mut self, args: Vec<HeapObject>) -> Result<Step, Err> {
+        self.each(Each::VectorForEach, args[0].clone(), args[1..].to_vec(), 0, Vec::new())
+    }
+
+    pub fn bytevector_p(&mut self, args: &[HeapObject]) -> Result<HeapObject, Err> {
+        Result::Ok(self.new_bool(matches!(args[0].object_type, Type::Bytevector(_))))
+    }
+
+    // (make-bytevector k [byte])
+    pub fn make_bytevector(&mut self, args: &[HeapObject]) -> Result<HeapObject, Err> {
+        let len = self.length(&args[0])?;
+        let fill = match args.get(1) {
+            Option::Some(obj) => self.byte(obj)?,
+            Option::None => 0,
+        };
+        let mut bytes = Vec::new();
+        bytes.try_reserve_exact(len).map_err(|_| self.too_long(len))?;
+        bytes.resize(len, fill);
+        Result::Ok(self.new_bytevector(bytes))
+    }
+
+    pub fn bytevector(&mut self, args: &[HeapObject]) -> Result<HeapObject, Err> {
+        let bytes = args.iter().map(|obj| self.byte(obj)).collect::<Result<Vec<u8>, Err>>()?;
+        Result::Ok(self.new_bytevector(bytes))
+    }
+
+    pub fn bytevector_length(&mut self, args: &[HeapObject]) -> Result<HeapObject, Err> {
+        let len = self.as_bytevector(&args[0])?.borrow().len();
+        Result::Ok(self.new_object(Type::Integer(len as i64)))
+    }
+
+    pub fn bytevector_u8_ref(&mut self, args: &[HeapObject]) -> Result<HeapObject, Err> {
+        let byte = {
+            let v = self.as_bytevector(&args[0])?.borrow();
+            v[self.index(&args[1], v.len(), false)?]
+        };
+        Result::Ok(self.new_object(Type::Integer(byte as i64)))
+    }
+
+    pub fn bytevector_u8_set(&mut self, args: &[HeapObject]) -> Result<HeapObject, Err> {
+        let byte = self.byte(&args[2])?;
+        let mut v = self.as_bytevector(&args[0])?.borrow_mut();
+        let i = self.index(&args[1], v.len(), false)?;
+        v[i] = byte;
+        drop(v);
+        Result::Ok(self.new_nil())
+    }
+
+    // (bytevector-copy v [start [end]])
+    pub fn bytevector_copy(&mut self, args: &[HeapObject]) -> Result<HeapObject, Err> {
+        let bytes = {
+            let v = self.as_bytevector(&args[0])?.borrow();
+            let (start, end) = self.range(args, 1, v.len())?;
+            v[start..end].to_vec()
+        };
+        Result::Ok(self.new_bytevector(bytes))
+    }
+
+    // (bytevector-copy! to at from [start [end]]), which may overlap
+    pub fn bytevector_copy_to(&mut self, args: &[HeapObject]) -> Result<HeapObject, Err> {
+        let bytes = {
+            let from = self.as_bytevector(&args[2])?.borrow();
+            let (start, end) = self.range(args, 3, from.len())?;
+            from[start..end].to_vec()
+        };
+        let mut to = self.as_bytevector(&args[0])?.borrow_mut();
+        let at = self.index(&args[1], to.len(), true)?;
+        if to.len() - at < bytes.len() {
+            return Result::Err(self.out_of_range((at + bytes.len()) as i64, to.len()));
+        }
+        to[at..at + bytes.len()].copy_from_slice(&bytes);
+        drop(to);
+        Result::Ok(self.new_nil())
+    }
+
+    pub fn bytevector_append(&mut self, args: &[HeapObject]) -> Result<HeapObject, Err> {
+        let mut bytes = Vec::new();
+        for obj in args {
+            bytes.extend_from_slice(&self.as_bytevector(obj)?.borrow());
+        }
+        Result::Ok(self.new_bytevector(bytes))
+    }
+
+    // (utf8->string v [start [end]])
+    pub fn utf8_to_string(&mut self, args: &[HeapObject]) -> Result<HeapObject, Err> {
+        let text = {
+            let v = self.as_bytevector(&args[0])?.borrow();
+            let (start, end) = self.range(args, 1, v.len())?;
+            match String::from_utf8(v[start..end].to_vec()) {
+                Result::Ok(text) => text,
+                Result::Err(_) => return Result::Err(self.wrong_type("UTF-8 bytevector", &args[0])),
+            }
+        };
+        Result::Ok(self.new_string(text))
+    }
+
+    // (string->utf8 s [start [end]]), start and end count characters
+    pub fn string_to_utf8(&mut self, args: &[HeapObject]) -> Result<HeapObject, Err> {
+        let bytes = match args[0].object_type {
+            Type::String(ref s) => {
+                let s = s.borrow();
+                let (start, end) = self.string_range(&s, args, 1)?;
+                s[start..end].as_bytes().to_vec()
+            },
+            _ => return Result::Err(self.wrong_type("string", &args[0])),
+        };
+        Result::Ok(self.new_bytevector(bytes))
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use interpreter::Interpreter;
+    use error::ErrType;
+    use test_util::{eval_str, assert_eval};
+
+    #[test]
+    fn test_vectors() {
+        let mut i = Interpreter::new();
+        let cases = [
+            ("(vector 1 2 3)", "#(1 2 3)"),
+            ("(make-vector 2 (quote a))", "#(a a)"),
+            ("(vector-length #(1 2 3))", "3"),
+            ("(vector-ref #(1 1 2 3 5 8 13 21) 5)", "8"),
+            ("(let ((v (vector 0 (quote (2 2 2 2)) \"Anna\"))) (vector-set! v 1 (quote (\"Sue\" \"Sue\"))) v)",
+             "#(0 (\"Sue\" \"Sue\") \"Anna\")"),
+            ("(vector->list #(dah dah didah))", "(dah dah didah)"),
+            ("(vector->list #(dah dah didah) 1)", "(dah didah)"),
+            ("(vector->list #(dah dah didah) 1 2)", "(dah)"),
+            ("(list->vector (quote (dididit dah)))", "#(dididit dah)"),
+            ("(let ((v (vector 1 2 3 4 5))) (vector-fill! v (quote smash) 2 4) v)", "#(1 2 smash smash 5)"),
+            ("(vector-copy #(1 8 2 8) 1 3)", "#(8 2)"),
+            ("(let ((a (vector 1 2 3 4 5)) (b (vector 10 20 30 40 50))) (vector-copy! b 1 a 0 2) b)",
+             "#(10 1 2 40 50)"),
+            ("(let ((a (vector 1 2 3 4 5))) (vector-copy! a 1 a 0 3) a)", "#(1 1 2 3 5)"),
+            ("(vector-append #(a b c) #(d e f))", "#(a b c d e f)"),
+            ("(vector-map (lambda (x) (car (cdr x))) (quote #((a b) (d e) (g h))))", "#(b e h)"),
+            ("(vector-map + #(1 2) #(10 20 30))", "#(11 22)"),
+            ("(let ((n 0)) (vector-for-each (lambda (x) (set! n (+ n x))) #(1 2 3)) n)", "6"),
+            ("(vector? #(1))", "#t"),
+            ("(vector? (quote (1)))", "#f"),
+        ];
+        for &(src, expected) in cases.iter() {
+            assert_eval(&mut i, src, expected);
+        }
+    }
+
+    #[test]
+    fn test_bytevectors() {
+        let mut i = Interpreter::new();
+        let cases = [
+            ("#u8(1 2 255)", "#u8(1 2 255)"),
+            ("(bytevector 1 3 5 1 3 5)", "#u8(1 3 5 1 3 5)"),
+            ("(make-bytevector 2 12)", "#u8(12 12)"),
+            ("(bytevector-u8-ref #u8(1 1 2 3 5 8 13 21) 5)", "8"),
+            ("(let ((bv (bytevector 1 2 3 4))) (bytevector-u8-set! bv 1 3) bv)", "#u8(1 3 3 4)"),
+            ("(bytevector-length #u8())", "0"),
+            ("(bytevector-copy #u8(1 2 3 4 5) 2 4)", "#u8(3 4)"),
+            ("(let ((a (bytevector 1 2 3 4 5)) (b (bytevector 10 20 30 40 50))) (bytevector-copy! b 1 a 0 2) b)",
+             "#u8(10 1 2 40 50)"),
+            ("(bytevector-append #u8(0 1 2) #u8(3 4 5))", "#u8(0 1 2 3 4 5)"),
+            ("(utf8->string #u8(65))", "\"A\""),
+            ("(utf8->string #u8(206 187 120) 0 2)", "\"λ\""),
+            ("(string->utf8 \"λx\")", "#u8(206 187 120)"),
+            ("(string->utf8 \"abc\" 1)", "#u8(98 99)"),
+            ("(bytevector? #u8(1))", "#t"),
+        ];
+        for &(src, expected) in cases.iter() {
+            assert_eval(&mut i, src, expected);
+        }
+    }
+
+    #[test]
+    fn test_bounds() {
+        let mut i = Interpreter::new();
+        for src in &["(vector-ref #(1 2) 2)", "(vector-ref #(1 2) -1)", "(vector-set! (vector) 0 1)",
+                     "(vector-copy #(1 2 3) 2 1)", "(vector-copy! (vector 1) 0 #(1 2))",
+                     "(bytevector-u8-ref #u8(1) 1)", "(vector->list #(1) 0 2)"] {
+            match eval_str(&mut i, src).map_err(|e| e.err_type) {
+                Result::Err(ErrType::IndexOutOfRange{..}) => {},
+                _ => panic!("{} should be out of range", src),
+            }
+        }
+        assert_eval(&mut i, "(guard (e ((error-object? e) (error-object-message e))) (vector-ref #(a) 3))",
+                    "\"Index 3 out of range for length 1\"");
+        for src in &["(bytevector 256)", "(make-bytevector 1 -1)", "(utf8->string #u8(255))"] {
+            assert!(matches!(eval_str(&mut i, src).map_err(|e| e.err_type), Result::Err(ErrType::WrongType{..})), "{}", src);
+        }
+        let cases = [
+            ("(make-vector -1)", "Length -1 is negative"),
+            ("(make-vector 100000000000000)", "Length 100000000000000 is too long to allocate"),
+            ("(make-bytevector 100000000000000000000)", "Length 100000000000000000000 is too long to allocate"),
+            ("(make-string 100000000000000 #\\x)", "Length 100000000000000 is too long to allocate"),
+        ];
+        for &(src, expected) in cases.iter() {
+            assert_eq!(eval_str(&mut i, src).err().map(|e| e.err_type.to_string()).as_deref(), Option::Some(expected), "{}", src);
+        }
+    }
+}